@@ -1,96 +1,108 @@
-use std::ops::FnOnce;
-use crate::states::state_machine::StateMachine;
-use crate::resources::*;
+use piston_window::{Event, Input, TimeStamp, UpdateArgs};
+use std::error;
+use std::path::Path;
 
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Transform {
     pub x : f64,
     pub y : f64,
 }
 
-pub struct Color {
-
+impl Transform {
+    pub fn new(x : f64, y : f64) -> Transform {
+        Transform { x, y }
+    }
 }
 
-pub struct TextureHandle<T> where T : RenderContext {
-    pub handle : T::TextureHandle,
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Color {
+    pub r : f32,
+    pub g : f32,
+    pub b : f32,
+    pub a : f32,
 }
 
-// impl<T> TextureHandle<T> where T : RenderContext {
-//     pub fn load(path : &str, ctx : &T) -> Self {
-//         Self{ handle : ctx.texture(path)}
-//     }
-// }
+impl Color {
+    pub const WHITE : Color = Color::new(1.0, 1.0, 1.0, 1.0);
+    pub const BLACK : Color = Color::new(0.0, 0.0, 0.0, 1.0);
 
-pub struct Font<T> where T : RenderContext {
-    handle : T::FontHandle,
-}
+    pub const fn new(r : f32, g : f32, b : f32, a : f32) -> Color {
+        Color { r, g, b, a }
+    }
 
-// impl<T> Font<T> where T : RenderContext {
-//     pub fn load(path : &str, ctx : &T) -> Self {
-//         Self{ handle : ctx.font(path)}
-//     }
-// }
-
-pub trait ResourceLoader<T> where T : RenderContext {
-    // fn texture(&self, path : &str) -> TextureHandle<T>;
+    pub fn rgba(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
 }
 
-pub trait RenderContext {
-    type TextureHandle;
-    type FontHandle;
-    fn draw_image<T>(&mut self, image : &TextureHandle<T>, transform : &Transform) where T : RenderContext;
-    fn draw_text<T>(&self, text : &str, font : &Font<T>, transform : &Transform, color : &Color) where T : RenderContext;
-    fn font(&self, path : &str) -> Self::FontHandle;
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Rect {
+    pub x : f64,
+    pub y : f64,
+    pub width : f64,
+    pub height : f64,
 }
 
-pub trait AbstractionLayer {
-    type Ctx : RenderContext;
-    fn run(&mut self, game : &StateMachine, resources : &Resources);
-    fn load_texture(&mut self, path : &str) ->TextureHandle<Self::Ctx>;
-    // fn resource_loader(&self) -> &ResourceLoader<Self::Ctx>;
+impl Rect {
+    pub fn new(x : f64, y : f64, width : f64, height : f64) -> Rect {
+        Rect { x, y, width, height }
+    }
 }
 
-// struct Gosho {
-//     a : Texture<Gosho>
-// }
+/// A texture loaded by the abstraction layer, valid for the layer that made it.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TextureHandle(pub usize);
 
-// impl Gosho {
-//     fn new() -> Gosho {
-//         Gosho {a : Texture::<Self> {handle : 5}}
-//     }
-// }
-//
-// impl RenderContext for Gosho {
-//     type TextureHandle = usize;
-//     fn print(&self) {
-//         println!("Gosho handle {}", self.a.handle);
-//     }
-// }
+/// A font loaded by the abstraction layer, valid for the layer that made it.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FontHandle(pub usize);
 
-// trait Something {
-//     type TextureHandle;
-// }
-//
-// struct Pesho {
-//
-// }
-//
-// impl Something for Pesho {
-//     type TextureHandle = i32;
-// }
+/// Drawing for one frame. Text is positioned by its baseline.
+pub trait RenderContext {
+    /// Size of the area being drawn, in the coordinates used for drawing.
+    fn size(&self) -> [f64; 2];
+    fn clear(&mut self, color : &Color);
+    fn draw_image(&mut self, image : TextureHandle, transform : &Transform) {
+        self.draw_image_scaled(image, transform, 1.0);
+    }
+    /// Draws the image at `scale` times its own size.
+    fn draw_image_scaled(&mut self, image : TextureHandle, transform : &Transform, scale : f64);
+    fn draw_rect(&mut self, rect : &Rect, color : &Color);
+    fn draw_rect_border(&mut self, rect : &Rect, color : &Color, width : f64);
+    fn draw_text(&mut self, text : &str, font : FontHandle, size : u32, transform : &Transform, color : &Color);
+    fn text_width(&mut self, text : &str, font : FontHandle, size : u32) -> f64;
+    /// Limits drawing to `rect` until the clip is cleared with `None`.
+    fn set_clip(&mut self, rect : Option<Rect>);
+    /// Smears what has been drawn so far by `radius` pixels. Frontends that
+    /// cannot read back what they drew leave it sharp.
+    fn blur(&mut self, _radius : u32) {}
+}
 
+/// The game as the abstraction layer sees it.
+pub trait Application {
+    /// Returns false once the game is over and the layer should stop.
+    /// `loader` is the running layer, for resources that change mid-game.
+    fn update(&mut self, update_args : &UpdateArgs, event : Event, loader : &mut dyn ResourceLoader) -> bool;
+    fn handle_input(&mut self, input : Input, time : Option<TimeStamp>);
+    /// `ext_dt` is how many seconds passed since the last update.
+    fn render(&mut self, ctx : &mut dyn RenderContext, ext_dt : f64);
+}
 
+pub trait ResourceLoader {
+    fn load_texture(&mut self, path : &Path) -> Result<TextureHandle, Box<dyn error::Error>>;
+    fn load_font(&mut self, path : &Path) -> Result<FontHandle, Box<dyn error::Error>>;
+    /// Loads `path` in place of what `handle` refers to. The handle stays
+    /// valid and keeps its old contents if loading fails.
+    fn reload_texture(&mut self, handle : TextureHandle, path : &Path) -> Result<(), Box<dyn error::Error>>;
+    fn reload_font(&mut self, handle : FontHandle, path : &Path) -> Result<(), Box<dyn error::Error>>;
+}
 
-// struct ImputState {
-//
-// }
+pub fn replace<T>(items : &mut [T], index : usize, item : T) -> Result<(), Box<dyn error::Error>> {
+    let slot = items.get_mut(index).ok_or_else(|| format!("no resource with handle {}", index))?;
+    *slot = item;
+    Ok(())
+}
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-//
-//     #[test]
-//     fn generics_test() {
-//
-//     }
-// }
\ No newline at end of file
+pub trait AbstractionLayer : ResourceLoader {
+    fn run(&mut self, application : &mut dyn Application);
+}
@@ -1,2 +1,4 @@
 pub mod abstraction_layer;
-pub mod piston_abstraction;
\ No newline at end of file
+pub mod piston_abstraction;
+pub mod software_abstraction;
+pub mod terminal_abstraction;
@@ -1,98 +1,147 @@
 use crate::abstraction::abstraction_layer::*;
-use crate::states::state_machine::StateMachine;
-use crate::resources::*;
 use piston_window::*;
-use std::path::PathBuf;
+use piston_window::character::CharacterCache;
+use std::path::Path;
 use std::error;
+use std::sync::Once;
 
 const GAME_NAME: &str = "Tetris";
-const WINDOW_WIDTH: u32 = 1024;
-const WINDOW_HEIGHT: u32 = 1280;
+// Text is drawn every frame, a glyph that fails once fails every time.
+static TEXT_ERROR: Once = Once::new();
 
-const ASSET_DIRECTORY : &str = "assets";
-const GAME_FONT : &str = "TetrisFont2.ttf";
-const PARENT_DEPTH : find_folder::ParentsDepth = 3;
-const KIDS_DEPTH : find_folder::KidsDepth = 3;
-
-type TextureHandle<T> = crate::abstraction::abstraction_layer::TextureHandle<T>;
-pub struct PistonRenderContext<'a, 'g2d, 'args, 'device> {
+pub struct PistonRenderContext<'a, 'g2d, 'resources> {
     c: Context,
     g: &'g2d mut G2d<'a>,
-    arguments: &'args RenderArgs,
-    device: &'device gfx_device_gl::Device,
+    textures: &'resources [G2dTexture],
+    fonts: &'resources mut [Glyphs],
 }
 
-impl<'a, 'g2d, 'args, 'device> PistonRenderContext<'a, 'g2d, 'args, 'device> {
+impl<'a, 'g2d, 'resources> PistonRenderContext<'a, 'g2d, 'resources> {
     fn new(
         c: Context,
         g: &'g2d mut G2d<'a>,
-        arguments: &'args RenderArgs,
-        device: &'device mut gfx_device_gl::Device,
-    ) -> PistonRenderContext<'a, 'g2d, 'args, 'device> {
-        PistonRenderContext{c, g, arguments, device }
+        textures: &'resources [G2dTexture],
+        fonts: &'resources mut [Glyphs],
+    ) -> PistonRenderContext<'a, 'g2d, 'resources> {
+        PistonRenderContext { c, g, textures, fonts }
+    }
+
+    // Scissor rectangles are in framebuffer pixels, which differ from window
+    // coordinates on high density displays.
+    fn scissor(&self, rect: &Rect) -> [u32; 4] {
+        let scale = match self.c.viewport {
+            Some(viewport) => viewport.draw_size[0] as f64 / viewport.window_size[0],
+            None => 1.0,
+        };
+        [
+            (rect.x * scale).max(0.0) as u32,
+            (rect.y * scale).max(0.0) as u32,
+            (rect.width * scale).max(0.0) as u32,
+            (rect.height * scale).max(0.0) as u32,
+        ]
     }
 }
 
-impl<'a, 'g2d, 'args, 'device> RenderContext for PistonRenderContext<'a, 'g2d, 'args, 'device> {
-    type TextureHandle = G2dTexture;
-    type FontHandle = i32;
-    fn draw_image<T>(&mut self, handle : &TextureHandle<T>, transform : &Transform) where T : RenderContext {
-        image(&handle.handle, self.c.transform.trans(transform.x, transform.y), self.g);
+impl<'a, 'g2d, 'resources> RenderContext for PistonRenderContext<'a, 'g2d, 'resources> {
+    fn size(&self) -> [f64; 2] {
+        self.c.get_view_size()
+    }
+
+    fn clear(&mut self, color: &Color) {
+        clear(color.rgba(), self.g);
+    }
+
+    fn draw_image_scaled(&mut self, handle: TextureHandle, transform: &Transform, scale: f64) {
+        if let Some(texture) = self.textures.get(handle.0) {
+            let transform = self.c.transform.trans(transform.x, transform.y).scale(scale, scale);
+            Image::new().draw(texture, &self.c.draw_state, transform, self.g);
+        }
+    }
+
+    fn draw_rect(&mut self, rect: &Rect, color: &Color) {
+        Rectangle::new(color.rgba()).draw(
+            [rect.x, rect.y, rect.width, rect.height],
+            &self.c.draw_state,
+            self.c.transform,
+            self.g,
+        );
     }
 
-    fn draw_text<T>(&self, text : &str, font : &Font<T>, transform : &Transform, color : &Color) where T : RenderContext {
-        // text::Text::new_color([1.0, 1.0, 0.0, 1.0], 32)
-        //     .draw(
-        //         text,
-        //         &mut resources.font,
-        //         &c.draw_state,
-        //         c.transform
-        //             .trans(SCORE_TEXT_POSITION_X as f64, SCORE_TEXT_POSITION_Y as f64),
-        //         g,
-        //     )
-        //     .unwrap();
+    fn draw_rect_border(&mut self, rect: &Rect, color: &Color, width: f64) {
+        Rectangle::new_border(color.rgba(), width / 2.0).draw(
+            [rect.x, rect.y, rect.width, rect.height],
+            &self.c.draw_state,
+            self.c.transform,
+            self.g,
+        );
     }
 
-    fn font(&self, path : &str) -> Self::FontHandle {
-        0
+    fn draw_text(&mut self, text: &str, font: FontHandle, size: u32, transform: &Transform, color: &Color) {
+        if let Some(glyphs) = self.fonts.get_mut(font.0) {
+            let transform = self.c.transform.trans(transform.x, transform.y);
+            if let Err(error) = text::Text::new_color(color.rgba(), size).draw(text, glyphs, &self.c.draw_state, transform, self.g) {
+                TEXT_ERROR.call_once(|| eprintln!("cannot draw text: {:?}", error));
+            }
+        }
+    }
+
+    fn text_width(&mut self, text: &str, font: FontHandle, size: u32) -> f64 {
+        match self.fonts.get_mut(font.0) {
+            Some(glyphs) => glyphs.width(size, text).unwrap_or(0.0),
+            None => 0.0,
+        }
+    }
+
+    fn set_clip(&mut self, rect: Option<Rect>) {
+        self.c.draw_state.scissor = rect.map(|rect| self.scissor(&rect));
     }
 }
 
 pub struct PistonAbstraction {
     window: PistonWindow,
-    assets: PathBuf,
-    // resource_loader : ResourceLoader<PistonAbstraction::Ctx>
-    // resource_loader : ResourceLoader<abstraction::piston_abstraction::PistonAbstraction as Trait>::Ctx
+    textures: Vec<G2dTexture>,
+    fonts: Vec<Glyphs>,
 }
 
 impl PistonAbstraction {
-    pub fn new() -> Result<PistonAbstraction, Box<dyn error::Error>> {
-        let assets = find_folder::Search::ParentsThenKids(PARENT_DEPTH, KIDS_DEPTH)
-            .for_folder(ASSET_DIRECTORY)?;
-
-        let mut window = WindowSettings::new(GAME_NAME, [WINDOW_WIDTH, WINDOW_HEIGHT])
+    pub fn new(window_size : [u32; 2]) -> Result<PistonAbstraction, Box<dyn error::Error>> {
+        let window = WindowSettings::new(GAME_NAME, window_size)
             .exit_on_esc(false)
             .graphics_api(OpenGL::V3_2)
             .build()?;
         Ok(PistonAbstraction {
             window,
-            assets,
+            textures: vec![],
+            fonts: vec![],
         })
     }
 
-    fn loop_handler(&mut self, game : &StateMachine, resources : &Resources, loop_arg: Loop, event: Event) -> bool {
+    fn read_texture(&mut self, path : &Path) -> Result<G2dTexture, Box<dyn error::Error>> {
+        let texture = Texture::from_path(
+            &mut self.window.create_texture_context(),
+            path,
+            Flip::None,
+            &TextureSettings::new()
+        ).map_err(|error| format!("{}: {}", path.display(), error))?;
+        Ok(texture)
+    }
+
+    fn loop_handler(&mut self, application : &mut dyn Application, loop_arg: Loop, event: Event) -> bool {
         match loop_arg {
             Loop::Update(update_args) => {
-                // if !self.update(update_args, event.clone()) {
-                //     return true;
-                // }
+                return !application.update(&update_args, event, self);
             }
 
             Loop::Render(render_args) => {
+                let textures = &self.textures;
+                let fonts = &mut self.fonts;
                 self.window.draw_2d(&event, |c, g, device| {
                     clear([1.0; 4], g);
-                    let mut ctx = PistonRenderContext::new(c, g, &render_args, device);
-                    game.render(&mut ctx, resources);
+                    let mut ctx = PistonRenderContext::new(c, g, textures, fonts);
+                    application.render(&mut ctx, render_args.ext_dt);
+                    for glyphs in fonts.iter_mut() {
+                        glyphs.factory.encoder.flush(device);
+                    }
                 });
             }
 
@@ -101,64 +150,49 @@ impl PistonAbstraction {
 
         false
     }
-
-    fn input_handler(&mut self, game : &StateMachine, input: Input, time: Option<TimeStamp>) {
-        // self.logic.handle_input(input, time, &mut self.data);
-    }
-}
-
-struct PistonResourceLoader<'a> {
-    window: &'a mut PistonWindow
-}
-
-impl PistonResourceLoader<'_> {
-    fn new(piston_window : &mut PistonWindow) -> PistonResourceLoader {
-        PistonResourceLoader{window : piston_window}
-    }
-}
-
-impl<'a> ResourceLoader<<crate::abstraction::piston_abstraction::PistonAbstraction as crate::abstraction::abstraction_layer::AbstractionLayer>::Ctx> for PistonResourceLoader<'a> {
-    // fn texture(&self, path : &str) -> TextureHandle<T> {
-    //
-    // }
 }
 
 impl AbstractionLayer for PistonAbstraction {
-    type Ctx = PistonRenderContext<'static, 'static, 'static, 'static>;
-    fn run(&mut self, game : &StateMachine, resources : &Resources) {
+    fn run(&mut self, application : &mut dyn Application) {
         while let Some(event) = self.window.next() {
-            match event {
-                Event::Loop(_loop) => {
-                    if self.loop_handler(game, resources, _loop, event) {
-                        return;
-                    }
-                }
+            let finished = match event {
+                Event::Loop(_loop) => self.loop_handler(application, _loop, event),
 
                 Event::Input(args, time) => {
-                    self.input_handler(game, args, time);
+                    application.handle_input(args, time);
+                    false
                 }
 
-                _ => {}
-            }
+                _ => false,
+            };
 
-            // if game.data.running == false {
-            //     break;
-            // }
+            if finished {
+                break;
+            }
         }
     }
+}
 
-    fn load_texture(&mut self, path : &str) ->TextureHandle<Self::Ctx> {
-        let handle = Texture::from_path(
-            &mut self.window.create_texture_context(),
-            self.assets.join(String::from(path)),
-            Flip::None,
-            &TextureSettings::new()
-        ).unwrap();
+impl ResourceLoader for PistonAbstraction {
+    fn load_texture(&mut self, path : &Path) -> Result<TextureHandle, Box<dyn error::Error>> {
+        let texture = self.read_texture(path)?;
+        self.textures.push(texture);
+        Ok(TextureHandle(self.textures.len() - 1))
+    }
+
+    fn load_font(&mut self, path : &Path) -> Result<FontHandle, Box<dyn error::Error>> {
+        let font = self.window.load_font(path)?;
+        self.fonts.push(font);
+        Ok(FontHandle(self.fonts.len() - 1))
+    }
 
-        TextureHandle::<Self::Ctx> {handle}
+    fn reload_texture(&mut self, handle : TextureHandle, path : &Path) -> Result<(), Box<dyn error::Error>> {
+        let texture = self.read_texture(path)?;
+        replace(&mut self.textures, handle.0, texture)
     }
 
-    // fn resource_loader(&self) -> &ResourceLoader<Self::Ctx> {
-    //     &self.resource_loader
-    // }
-}
\ No newline at end of file
+    fn reload_font(&mut self, handle : FontHandle, path : &Path) -> Result<(), Box<dyn error::Error>> {
+        let font = self.window.load_font(path)?;
+        replace(&mut self.fonts, handle.0, font)
+    }
+}
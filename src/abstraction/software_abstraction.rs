@@ -0,0 +1,450 @@
+use crate::abstraction::abstraction_layer::*;
+use image::{Rgba, RgbaImage};
+use piston_window::{Event, Input, Loop, UpdateArgs};
+use rusttype::{point, Font, Scale};
+use std::collections::VecDeque;
+use std::error;
+use std::fs;
+use std::path::Path;
+
+pub const FRAME_TIME: f64 = 1.0 / 60.0;
+// Piston takes font sizes in points and rasterizes them at this many pixels
+// per point, text has to come out the same size here.
+const PIXELS_PER_POINT: f32 = 1.333;
+
+fn blend(target: &mut Rgba<u8>, color: [f32; 4], coverage: f32) {
+    let alpha = (color[3] * coverage).clamp(0.0, 1.0);
+    if alpha <= 0.0 {
+        return;
+    }
+    for (destination, source) in target.0.iter_mut().zip(color.iter()).take(3) {
+        let source = source.clamp(0.0, 1.0) * 255.0;
+        *destination = (source * alpha + *destination as f32 * (1.0 - alpha)).round() as u8;
+    }
+    let destination = target.0[3] as f32 / 255.0;
+    target.0[3] = ((alpha + destination * (1.0 - alpha)) * 255.0).round() as u8;
+}
+
+/// Draws into an RGBA image in memory, no window or GPU needed.
+pub struct SoftwareRenderContext<'a> {
+    framebuffer: &'a mut RgbaImage,
+    textures: &'a [RgbaImage],
+    fonts: &'a [Font<'static>],
+    clip: Option<Rect>,
+}
+
+impl<'a> SoftwareRenderContext<'a> {
+    pub fn new(framebuffer: &'a mut RgbaImage, textures: &'a [RgbaImage], fonts: &'a [Font<'static>]) -> SoftwareRenderContext<'a> {
+        SoftwareRenderContext {
+            framebuffer,
+            textures,
+            fonts,
+            clip: None,
+        }
+    }
+
+    /// Pixel bounds `[left, top, right, bottom)` that may be drawn to.
+    fn bounds(&self) -> [i64; 4] {
+        let mut bounds = [0, 0, self.framebuffer.width() as i64, self.framebuffer.height() as i64];
+        if let Some(clip) = self.clip {
+            bounds[0] = bounds[0].max(clip.x.round() as i64);
+            bounds[1] = bounds[1].max(clip.y.round() as i64);
+            bounds[2] = bounds[2].min((clip.x + clip.width).round() as i64);
+            bounds[3] = bounds[3].min((clip.y + clip.height).round() as i64);
+        }
+        bounds
+    }
+
+    fn plot(&mut self, x: i64, y: i64, color: [f32; 4], coverage: f32) {
+        let bounds = self.bounds();
+        if x >= bounds[0] && x < bounds[2] && y >= bounds[1] && y < bounds[3] {
+            blend(self.framebuffer.get_pixel_mut(x as u32, y as u32), color, coverage);
+        }
+    }
+
+    fn font_scale(size: u32) -> Scale {
+        Scale::uniform((size as f32 * PIXELS_PER_POINT).round())
+    }
+}
+
+impl<'a> RenderContext for SoftwareRenderContext<'a> {
+    fn size(&self) -> [f64; 2] {
+        [self.framebuffer.width() as f64, self.framebuffer.height() as f64]
+    }
+
+    fn clear(&mut self, color: &Color) {
+        let pixel = color.rgba().map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+        for target in self.framebuffer.pixels_mut() {
+            *target = Rgba(pixel);
+        }
+    }
+
+    // Nearest neighbour, which keeps scale 1 an exact copy.
+    fn draw_image_scaled(&mut self, image: TextureHandle, transform: &Transform, scale: f64) {
+        let texture = match self.textures.get(image.0) {
+            Some(texture) if texture.width() > 0 && texture.height() > 0 => texture,
+            _ => return,
+        };
+        let left = transform.x.round() as i64;
+        let top = transform.y.round() as i64;
+        let width = (texture.width() as f64 * scale).round() as u32;
+        let height = (texture.height() as f64 * scale).round() as u32;
+        for y in 0..height {
+            let source_y = (((y as f64 + 0.5) / scale) as u32).min(texture.height() - 1);
+            for x in 0..width {
+                let source_x = (((x as f64 + 0.5) / scale) as u32).min(texture.width() - 1);
+                let color = texture.get_pixel(source_x, source_y).0.map(|channel| channel as f32 / 255.0);
+                self.plot(left + x as i64, top + y as i64, color, 1.0);
+            }
+        }
+    }
+
+    fn draw_rect(&mut self, rect: &Rect, color: &Color) {
+        let bounds = self.bounds();
+        let left = (rect.x.round() as i64).max(bounds[0]);
+        let top = (rect.y.round() as i64).max(bounds[1]);
+        let right = ((rect.x + rect.width).round() as i64).min(bounds[2]);
+        let bottom = ((rect.y + rect.height).round() as i64).min(bounds[3]);
+        for y in top..bottom {
+            for x in left..right {
+                blend(self.framebuffer.get_pixel_mut(x as u32, y as u32), color.rgba(), 1.0);
+            }
+        }
+    }
+
+    // Centered on the edges of `rect`, like Piston's bordered rectangles.
+    fn draw_rect_border(&mut self, rect: &Rect, color: &Color, width: f64) {
+        let half = width / 2.0;
+        let outer = Rect::new(rect.x - half, rect.y - half, rect.width + width, rect.height + width);
+        self.draw_rect(&Rect::new(outer.x, outer.y, outer.width, width), color);
+        self.draw_rect(&Rect::new(outer.x, outer.y + outer.height - width, outer.width, width), color);
+        self.draw_rect(&Rect::new(outer.x, outer.y + width, width, outer.height - 2.0 * width), color);
+        self.draw_rect(&Rect::new(outer.x + outer.width - width, outer.y + width, width, outer.height - 2.0 * width), color);
+    }
+
+    fn draw_text(&mut self, text: &str, font: FontHandle, size: u32, transform: &Transform, color: &Color) {
+        let fonts = self.fonts;
+        let font = match fonts.get(font.0) {
+            Some(font) => font,
+            None => return,
+        };
+        let scale = SoftwareRenderContext::font_scale(size);
+        let mut x = transform.x as f32;
+        for character in text.chars() {
+            let glyph = font.glyph(character).scaled(scale);
+            let advance = glyph.h_metrics().advance_width;
+            let glyph = glyph.positioned(point(x, transform.y as f32));
+            if let Some(bounding_box) = glyph.pixel_bounding_box() {
+                glyph.draw(|glyph_x, glyph_y, coverage| {
+                    let pixel_x = bounding_box.min.x as i64 + glyph_x as i64;
+                    let pixel_y = bounding_box.min.y as i64 + glyph_y as i64;
+                    self.plot(pixel_x, pixel_y, color.rgba(), coverage);
+                });
+            }
+            x += advance;
+        }
+    }
+
+    fn text_width(&mut self, text: &str, font: FontHandle, size: u32) -> f64 {
+        let font = match self.fonts.get(font.0) {
+            Some(font) => font,
+            None => return 0.0,
+        };
+        let scale = SoftwareRenderContext::font_scale(size);
+        text.chars()
+            .map(|character| font.glyph(character).scaled(scale).h_metrics().advance_width as f64)
+            .sum()
+    }
+
+    fn set_clip(&mut self, rect: Option<Rect>) {
+        self.clip = rect;
+    }
+
+    // A box blur, across and then down, within the clip.
+    fn blur(&mut self, radius: u32) {
+        if radius == 0 {
+            return;
+        }
+        let [left, top, right, bottom] = self.bounds();
+        let radius = radius as i64;
+        for across in [true, false] {
+            let source = self.framebuffer.clone();
+            for y in top..bottom {
+                for x in left..right {
+                    let mut sum = [0u32; 4];
+                    let mut count = 0;
+                    for offset in -radius..=radius {
+                        let (x, y) = if across { (x + offset, y) } else { (x, y + offset) };
+                        if x < left || x >= right || y < top || y >= bottom {
+                            continue;
+                        }
+                        for (total, channel) in sum.iter_mut().zip(source.get_pixel(x as u32, y as u32).0.iter()) {
+                            *total += *channel as u32;
+                        }
+                        count += 1;
+                    }
+                    let pixel = self.framebuffer.get_pixel_mut(x as u32, y as u32);
+                    for (channel, total) in pixel.0.iter_mut().zip(sum.iter()) {
+                        *channel = (*total as f32 / count as f32).round() as u8;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Runs the game headless for a fixed number of frames, feeding it scripted
+/// input. Every frame is rendered into `framebuffer`.
+pub struct SoftwareAbstraction {
+    framebuffer: RgbaImage,
+    textures: Vec<RgbaImage>,
+    fonts: Vec<Font<'static>>,
+    frames: u32,
+    inputs: VecDeque<(u32, Input)>,
+}
+
+impl SoftwareAbstraction {
+    pub fn new(width: u32, height: u32) -> SoftwareAbstraction {
+        SoftwareAbstraction {
+            framebuffer: RgbaImage::new(width, height),
+            textures: vec![],
+            fonts: vec![],
+            frames: 1,
+            inputs: VecDeque::new(),
+        }
+    }
+
+    pub fn set_frames(&mut self, frames: u32) {
+        self.frames = frames;
+    }
+
+    /// Delivers `input` before the update of frame `frame`.
+    pub fn push_input(&mut self, frame: u32, input: Input) {
+        let index = self.inputs.iter().position(|(queued, _)| *queued > frame).unwrap_or(self.inputs.len());
+        self.inputs.insert(index, (frame, input));
+    }
+
+    pub fn framebuffer(&self) -> &RgbaImage {
+        &self.framebuffer
+    }
+
+    pub fn render(&mut self, application: &mut dyn Application) {
+        let mut ctx = SoftwareRenderContext::new(&mut self.framebuffer, &self.textures, &self.fonts);
+        ctx.clear(&Color::WHITE);
+        application.render(&mut ctx, 0.0);
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn error::Error>> {
+        self.framebuffer.save(path).map_err(|error| format!("{}: {}", path.display(), error).into())
+    }
+}
+
+impl AbstractionLayer for SoftwareAbstraction {
+    fn run(&mut self, application: &mut dyn Application) {
+        for frame in 0..self.frames {
+            while self.inputs.front().is_some_and(|(queued, _)| *queued <= frame) {
+                let (_, input) = self.inputs.pop_front().unwrap();
+                application.handle_input(input, None);
+            }
+
+            let update_args = UpdateArgs { dt: FRAME_TIME };
+            if !application.update(&update_args, Event::Loop(Loop::Update(update_args)), self) {
+                return;
+            }
+            self.render(application);
+        }
+    }
+}
+
+fn read_texture(path: &Path) -> Result<RgbaImage, Box<dyn error::Error>> {
+    let texture = image::open(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    Ok(texture.to_rgba8())
+}
+
+fn read_font(path: &Path) -> Result<Font<'static>, Box<dyn error::Error>> {
+    let bytes = fs::read(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    Ok(Font::from_bytes(bytes).map_err(|error| format!("{}: {}", path.display(), error))?)
+}
+
+impl ResourceLoader for SoftwareAbstraction {
+    fn load_texture(&mut self, path: &Path) -> Result<TextureHandle, Box<dyn error::Error>> {
+        self.textures.push(read_texture(path)?);
+        Ok(TextureHandle(self.textures.len() - 1))
+    }
+
+    fn load_font(&mut self, path: &Path) -> Result<FontHandle, Box<dyn error::Error>> {
+        self.fonts.push(read_font(path)?);
+        Ok(FontHandle(self.fonts.len() - 1))
+    }
+
+    fn reload_texture(&mut self, handle: TextureHandle, path: &Path) -> Result<(), Box<dyn error::Error>> {
+        replace(&mut self.textures, handle.0, read_texture(path)?)
+    }
+
+    fn reload_font(&mut self, handle: FontHandle, path: &Path) -> Result<(), Box<dyn error::Error>> {
+        replace(&mut self.fonts, handle.0, read_font(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_data::{GameData, HEIGHT, WIDTH};
+    use crate::high_scores::GameMode;
+    use crate::finesse::Finesse;
+    use crate::settings::{Settings, DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_WIDTH};
+    use crate::statistics::{GameStatistics, LifetimeStatistics};
+    use crate::states::main_menu::MainMenu;
+    use crate::states::pause::Pause;
+    use crate::states::play::PlayState;
+    use crate::states::score_screen::ScoreScreen;
+    use crate::states::state_machine::{State, StateMachine};
+    use crate::tetramino::*;
+    use crate::tetris::Game;
+    use std::env;
+    use std::path::PathBuf;
+
+    // Golden images are compared at full size, the tolerance only allows for
+    // rounding in the blending.
+    const GOLDEN_TOLERANCE: i32 = 1;
+
+    /// Compares against `tests/golden/<name>.png`, run with `UPDATE_GOLDEN=1`
+    /// to write the images instead.
+    fn check_golden(name: &str, image: &RgbaImage) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{}.png", name));
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            image.save(&path).unwrap();
+            return;
+        }
+
+        let expected = image::open(&path)
+            .unwrap_or_else(|error| panic!("{}: {}, run with UPDATE_GOLDEN=1 to create it", path.display(), error))
+            .to_rgba8();
+        let matches = expected.dimensions() == image.dimensions()
+            && expected.pixels().zip(image.pixels()).all(|(expected, actual)| {
+                expected.0.iter().zip(actual.0.iter()).all(|(e, a)| (*e as i32 - *a as i32).abs() <= GOLDEN_TOLERANCE)
+            });
+        if !matches {
+            let actual_path = env::temp_dir().join(format!("{}.actual.png", name));
+            image.save(&actual_path).unwrap();
+            panic!("{} differs from {}", actual_path.display(), path.display());
+        }
+    }
+
+    fn game_data() -> GameData {
+        GameData::new(Settings::default()).unwrap()
+    }
+
+    fn render(state: Box<dyn State>, data: GameData) -> RgbaImage {
+        render_stack(vec![state], data)
+    }
+
+    fn render_stack(stack: Vec<Box<dyn State>>, data: GameData) -> RgbaImage {
+        let mut abstraction = SoftwareAbstraction::new(DEFAULT_WINDOW_WIDTH, DEFAULT_WINDOW_HEIGHT);
+        let mut game = Game::new(&mut abstraction, data, StateMachine::from_stack(stack).unwrap()).unwrap();
+        abstraction.render(&mut game);
+        abstraction.framebuffer().clone()
+    }
+
+    #[test]
+    fn test_rasterizer() {
+        let mut framebuffer = RgbaImage::new(8, 8);
+        let mut ctx = SoftwareRenderContext::new(&mut framebuffer, &[], &[]);
+        ctx.clear(&Color::BLACK);
+        ctx.draw_rect(&Rect::new(1.0, 1.0, 2.0, 2.0), &Color::WHITE);
+        ctx.set_clip(Some(Rect::new(4.0, 4.0, 4.0, 4.0)));
+        ctx.draw_rect(&Rect::new(0.0, 0.0, 6.0, 6.0), &Color::new(1.0, 0.0, 0.0, 0.5));
+        ctx.set_clip(None);
+        ctx.draw_rect_border(&Rect::new(5.0, 1.0, 2.0, 2.0), &Color::WHITE, 1.0);
+
+        assert_eq!([255, 255, 255, 255], framebuffer.get_pixel(2, 2).0);
+        assert_eq!([0, 0, 0, 255], framebuffer.get_pixel(3, 3).0);
+        assert_eq!([128, 0, 0, 255], framebuffer.get_pixel(5, 5).0);
+        assert_eq!([0, 0, 0, 255], framebuffer.get_pixel(6, 6).0);
+        assert_eq!([255, 255, 255, 255], framebuffer.get_pixel(5, 1).0);
+        assert_eq!([0, 0, 0, 255], framebuffer.get_pixel(6, 2).0);
+    }
+
+    #[test]
+    fn test_empty_texture() {
+        let mut framebuffer = RgbaImage::new(4, 4);
+        let textures = [RgbaImage::new(0, 0)];
+        let mut ctx = SoftwareRenderContext::new(&mut framebuffer, &textures, &[]);
+        ctx.draw_image_scaled(TextureHandle(0), &Transform::new(0.0, 0.0), 2.0);
+        assert_eq!([0, 0, 0, 0], framebuffer.get_pixel(0, 0).0);
+    }
+
+    #[test]
+    fn test_reload_texture() {
+        let directory = std::env::temp_dir().join(format!("tetris-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let red = directory.join("red.png");
+        let blue = directory.join("blue.png");
+        RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255])).save(&red).unwrap();
+        RgbaImage::from_pixel(2, 2, image::Rgba([0, 0, 255, 255])).save(&blue).unwrap();
+
+        let mut abstraction = SoftwareAbstraction::new(2, 2);
+        let handle = abstraction.load_texture(&red).unwrap();
+        abstraction.reload_texture(handle, &blue).unwrap();
+        assert!(abstraction.reload_texture(handle, &directory.join("missing.png")).is_err());
+        assert!(abstraction.reload_texture(TextureHandle(1), &blue).is_err());
+
+        let mut framebuffer = RgbaImage::new(2, 2);
+        let mut ctx = SoftwareRenderContext::new(&mut framebuffer, &abstraction.textures, &[]);
+        ctx.draw_image(handle, &Transform::new(0.0, 0.0));
+        assert_eq!([0, 0, 255, 255], framebuffer.get_pixel(1, 1).0);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_run_headless() {
+        let mut abstraction = SoftwareAbstraction::new(DEFAULT_WINDOW_WIDTH, DEFAULT_WINDOW_HEIGHT);
+        let logic = StateMachine::new(MainMenu::new().unwrap()).unwrap();
+        let mut game = Game::new(&mut abstraction, game_data(), logic).unwrap();
+        abstraction.set_frames(3);
+        abstraction.run(&mut game);
+        assert!(abstraction.framebuffer().pixels().any(|pixel| pixel.0 != [255; 4]));
+    }
+
+    #[test]
+    fn test_golden_main_menu() {
+        check_golden("main_menu", &render(MainMenu::new().unwrap(), game_data()));
+    }
+
+    fn play_field_data() -> GameData {
+        let mut data = game_data();
+        for x in 0..WIDTH - 1 {
+            data.play_table[(HEIGHT - 1) * WIDTH + x] = TetrominoType::I;
+            data.play_table[(HEIGHT - 2) * WIDTH + x + 1] = TetrominoType::O;
+        }
+        data.current_figure = Tetramino::new(TetrominoType::T as usize);
+        data.next_figure = TetrominoType::L as usize;
+        data.hold = Some(TetrominoType::S as usize);
+        data.score = 1200;
+        data.dificulty = 3;
+        data.hud.show(String::from("Tetris!"));
+        data
+    }
+
+    #[test]
+    fn test_golden_play_field() {
+        check_golden("play_field", &render(PlayState::new().unwrap(), play_field_data()));
+    }
+
+    #[test]
+    fn test_golden_pause() {
+        let stack = vec![PlayState::new().unwrap(), Pause::new(true).unwrap()];
+        check_golden("pause", &render_stack(stack, play_field_data()));
+    }
+
+    #[test]
+    fn test_golden_score_screen() {
+        let mut statistics = GameStatistics::new();
+        statistics.record_lock(TetrominoType::I as usize, false, 4);
+        statistics.add_time(75.0);
+        let lifetime = LifetimeStatistics { games: 3, totals: statistics.clone() };
+        let state = ScoreScreen::new(GameMode::Demo, 4800, 4, 2, Finesse::new(), statistics, Ok(Some(lifetime))).unwrap();
+        check_golden("score_screen", &render(state, game_data()));
+    }
+}
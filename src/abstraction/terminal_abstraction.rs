@@ -0,0 +1,519 @@
+use crate::abstraction::abstraction_layer::*;
+use crossterm::event::{self, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEvent, MouseEventKind};
+use crossterm::style::{self, Print, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, queue, terminal};
+use image::RgbaImage;
+use piston_window::{Button, ButtonArgs, ButtonState, Event, Input, Key, Loop, Motion, MouseButton, UpdateArgs};
+use std::error;
+use std::io::{self, Write};
+use std::panic;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const FRAME_TIME: f64 = 1.0 / 60.0;
+// Samples per cell side when an image is shrunk to cell colors.
+const IMAGE_SAMPLES: u32 = 3;
+// Text keeps the cells behind it readable by darkening them.
+const TEXT_BACKGROUND_DIM: f32 = 0.3;
+const F1_CODE: u32 = 0x4000_003A;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Cell {
+    character: char,
+    foreground: [f32; 3],
+    background: [f32; 3],
+}
+
+const BLANK: Cell = Cell {
+    character: ' ',
+    foreground: [1.0; 3],
+    background: [0.0; 3],
+};
+
+fn mix(target: [f32; 3], color: &Color) -> [f32; 3] {
+    let alpha = color.a.clamp(0.0, 1.0);
+    [
+        color.r * alpha + target[0] * (1.0 - alpha),
+        color.g * alpha + target[1] * (1.0 - alpha),
+        color.b * alpha + target[2] * (1.0 - alpha),
+    ]
+}
+
+fn terminal_color(color: [f32; 3]) -> style::Color {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    style::Color::Rgb {
+        r: channel(color[0]),
+        g: channel(color[1]),
+        b: channel(color[2]),
+    }
+}
+
+/// Game coordinates mapped onto a grid of text cells. Images become cell
+/// colors and every character of text takes one cell.
+pub struct TerminalRenderContext<'a> {
+    cells: &'a mut Vec<Cell>,
+    columns: usize,
+    rows: usize,
+    // Size of one cell in game coordinates.
+    cell_size: [f64; 2],
+    textures: &'a [RgbaImage],
+    clip: Option<Rect>,
+}
+
+impl<'a> TerminalRenderContext<'a> {
+    fn cell(&self, x: f64, y: f64) -> Option<usize> {
+        if let Some(clip) = self.clip {
+            if x < clip.x || y < clip.y || x >= clip.x + clip.width || y >= clip.y + clip.height {
+                return None;
+            }
+        }
+        let column = (x / self.cell_size[0]).floor();
+        let row = (y / self.cell_size[1]).floor();
+        if column < 0.0 || row < 0.0 || column >= self.columns as f64 || row >= self.rows as f64 {
+            return None;
+        }
+        Some(row as usize * self.columns + column as usize)
+    }
+
+    /// Cells whose centers lie inside `rect`, as `(index, x, y)` of the
+    /// cell's top left corner in game coordinates.
+    fn covered(&self, rect: &Rect) -> Vec<(usize, f64, f64)> {
+        let [width, height] = self.cell_size;
+        let first_column = ((rect.x / width) - 0.5).ceil().max(0.0) as usize;
+        let first_row = ((rect.y / height) - 0.5).ceil().max(0.0) as usize;
+        let mut covered = vec![];
+        let mut row = first_row;
+        while row < self.rows && (row as f64 + 0.5) * height < rect.y + rect.height {
+            let mut column = first_column;
+            while column < self.columns && (column as f64 + 0.5) * width < rect.x + rect.width {
+                let (x, y) = (column as f64 * width, row as f64 * height);
+                if let Some(index) = self.cell(x + width / 2.0, y + height / 2.0) {
+                    covered.push((index, x, y));
+                }
+                column += 1;
+            }
+            row += 1;
+        }
+        covered
+    }
+}
+
+impl<'a> RenderContext for TerminalRenderContext<'a> {
+    fn size(&self) -> [f64; 2] {
+        [self.columns as f64 * self.cell_size[0], self.rows as f64 * self.cell_size[1]]
+    }
+
+    fn clear(&mut self, color: &Color) {
+        let background = mix([0.0; 3], color);
+        for cell in self.cells.iter_mut() {
+            *cell = Cell { background, ..BLANK };
+        }
+    }
+
+    fn draw_image_scaled(&mut self, image: TextureHandle, transform: &Transform, scale: f64) {
+        let texture = match self.textures.get(image.0) {
+            Some(texture) => texture,
+            None => return,
+        };
+        let rect = Rect::new(transform.x, transform.y, texture.width() as f64 * scale, texture.height() as f64 * scale);
+        let [width, height] = self.cell_size;
+        for (index, x, y) in self.covered(&rect) {
+            let mut sum = [0.0; 4];
+            for sample_y in 0..IMAGE_SAMPLES {
+                for sample_x in 0..IMAGE_SAMPLES {
+                    let offset = |sample: u32| (sample as f64 + 0.5) / IMAGE_SAMPLES as f64;
+                    let pixel_x = x + width * offset(sample_x) - transform.x;
+                    let pixel_y = y + height * offset(sample_y) - transform.y;
+                    if pixel_x < 0.0 || pixel_y < 0.0 || pixel_x >= rect.width || pixel_y >= rect.height {
+                        continue;
+                    }
+                    let pixel = texture.get_pixel((pixel_x / scale) as u32, (pixel_y / scale) as u32).0;
+                    let alpha = pixel[3] as f32 / 255.0;
+                    for channel in 0..3 {
+                        sum[channel] += pixel[channel] as f32 / 255.0 * alpha;
+                    }
+                    sum[3] += alpha;
+                }
+            }
+            if sum[3] > 0.0 {
+                let samples = (IMAGE_SAMPLES * IMAGE_SAMPLES) as f32;
+                let color = Color::new(sum[0] / sum[3], sum[1] / sum[3], sum[2] / sum[3], sum[3] / samples);
+                let cell = &mut self.cells[index];
+                cell.background = mix(cell.background, &color);
+            }
+        }
+    }
+
+    fn draw_rect(&mut self, rect: &Rect, color: &Color) {
+        for (index, _, _) in self.covered(rect) {
+            let cell = &mut self.cells[index];
+            cell.background = mix(cell.background, color);
+        }
+    }
+
+    // Cells are too coarse for a line, the sides are marked with brackets.
+    fn draw_rect_border(&mut self, rect: &Rect, color: &Color, _width: f64) {
+        let [width, _] = self.cell_size;
+        let covered = self.covered(rect);
+        for (index, x, _) in covered {
+            let character = if x < rect.x + width / 2.0 {
+                '['
+            } else if x + width * 1.5 > rect.x + rect.width {
+                ']'
+            } else {
+                continue;
+            };
+            let cell = &mut self.cells[index];
+            cell.character = character;
+            cell.foreground = mix(cell.foreground, color);
+        }
+    }
+
+    // The cell row is taken halfway up the text, which is where the glyphs
+    // are when drawn with their baseline at `transform.y`.
+    fn draw_text(&mut self, text: &str, _font: FontHandle, size: u32, transform: &Transform, color: &Color) {
+        let y = transform.y - size as f64 / 2.0;
+        for (offset, character) in text.chars().enumerate() {
+            let x = transform.x + offset as f64 * self.cell_size[0];
+            if let Some(index) = self.cell(x, y) {
+                let cell = &mut self.cells[index];
+                cell.character = character;
+                cell.foreground = [color.r, color.g, color.b];
+                cell.background = cell.background.map(|channel| channel * TEXT_BACKGROUND_DIM);
+            }
+        }
+    }
+
+    fn text_width(&mut self, text: &str, _font: FontHandle, _size: u32) -> f64 {
+        text.chars().count() as f64 * self.cell_size[0]
+    }
+
+    fn set_clip(&mut self, rect: Option<Rect>) {
+        self.clip = rect;
+    }
+}
+
+/// Translates a terminal key to the key Piston would report. Terminals do not
+/// report releases, so every key is released right after it is pressed.
+fn key_inputs(key: KeyEvent) -> Vec<Input> {
+    let (button, text) = match key.code {
+        KeyCode::Char(character) if character.is_ascii() => {
+            (Key::from(character.to_ascii_lowercase() as u32), Some(character.to_string()))
+        }
+        KeyCode::Char(character) => (Key::Unknown, Some(character.to_string())),
+        KeyCode::Enter => (Key::Return, None),
+        KeyCode::Esc => (Key::Escape, None),
+        KeyCode::Backspace => (Key::Backspace, None),
+        KeyCode::Delete => (Key::Delete, None),
+        KeyCode::Tab => (Key::Tab, None),
+        KeyCode::Left => (Key::Left, None),
+        KeyCode::Right => (Key::Right, None),
+        KeyCode::Up => (Key::Up, None),
+        KeyCode::Down => (Key::Down, None),
+        KeyCode::Home => (Key::Home, None),
+        KeyCode::End => (Key::End, None),
+        KeyCode::PageUp => (Key::PageUp, None),
+        KeyCode::PageDown => (Key::PageDown, None),
+        KeyCode::Insert => (Key::Insert, None),
+        KeyCode::F(number) if (1..=12).contains(&number) => (Key::from(F1_CODE + number as u32 - 1), None),
+        _ => return vec![],
+    };
+
+    let press = |state| {
+        Input::Button(ButtonArgs {
+            state,
+            button: Button::Keyboard(button),
+            scancode: None,
+        })
+    };
+    let mut inputs = vec![];
+    if button != Key::Unknown {
+        inputs.push(press(ButtonState::Press));
+    }
+    if let Some(text) = text {
+        inputs.push(Input::Text(text));
+    }
+    if button != Key::Unknown {
+        inputs.push(press(ButtonState::Release));
+    }
+    inputs
+}
+
+/// Plays in the terminal: raw mode keyboard and mouse input, ANSI colored
+/// text cells for output. Only cells that changed are redrawn.
+pub struct TerminalAbstraction {
+    size: [u32; 2],
+    textures: Vec<RgbaImage>,
+    fonts: usize,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Cell>,
+    drawn: Vec<Cell>,
+}
+
+impl TerminalAbstraction {
+    /// `size` is the area the game draws to, it is scaled to the terminal.
+    pub fn new(size: [u32; 2]) -> TerminalAbstraction {
+        TerminalAbstraction {
+            size,
+            textures: vec![],
+            fonts: 0,
+            columns: 0,
+            rows: 0,
+            cells: vec![],
+            drawn: vec![],
+        }
+    }
+
+    fn cell_size(&self) -> [f64; 2] {
+        [
+            self.size[0] as f64 / self.columns.max(1) as f64,
+            self.size[1] as f64 / self.rows.max(1) as f64,
+        ]
+    }
+
+    fn resize(&mut self, columns: u16, rows: u16) {
+        self.columns = columns as usize;
+        self.rows = rows as usize;
+        self.cells = vec![BLANK; self.columns * self.rows];
+        // Nothing on screen is known to match, draw every cell again.
+        self.drawn = vec![Cell { character: '\0', ..BLANK }; self.columns * self.rows];
+    }
+
+    fn mouse_inputs(&self, mouse: MouseEvent) -> Vec<Input> {
+        let [width, height] = self.cell_size();
+        let position = [
+            (mouse.column as f64 + 0.5) * width,
+            (mouse.row as f64 + 0.5) * height,
+        ];
+        let mut inputs = vec![Input::Move(Motion::MouseCursor(position))];
+        let state = match mouse.kind {
+            MouseEventKind::Down(event::MouseButton::Left) => ButtonState::Press,
+            MouseEventKind::Up(event::MouseButton::Left) => ButtonState::Release,
+            _ => return inputs,
+        };
+        inputs.push(Input::Button(ButtonArgs {
+            state,
+            button: Button::Mouse(MouseButton::Left),
+            scancode: None,
+        }));
+        inputs
+    }
+
+    /// Returns false when the player asked to leave with Ctrl+C.
+    fn handle_events(&mut self, application: &mut dyn Application, timeout: Duration) -> io::Result<bool> {
+        if !event::poll(timeout)? {
+            return Ok(true);
+        }
+
+        loop {
+            let inputs = match event::read()? {
+                event::Event::Key(key) if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') => {
+                    return Ok(false);
+                }
+                event::Event::Key(key) if key.kind != KeyEventKind::Release => key_inputs(key),
+                event::Event::Mouse(mouse) => self.mouse_inputs(mouse),
+                event::Event::Resize(columns, rows) => {
+                    self.resize(columns, rows);
+                    vec![]
+                }
+                _ => vec![],
+            };
+            for input in inputs {
+                application.handle_input(input, None);
+            }
+
+            if !event::poll(Duration::from_secs(0))? {
+                return Ok(true);
+            }
+        }
+    }
+
+    fn render(&mut self, application: &mut dyn Application) {
+        let cell_size = self.cell_size();
+        let mut ctx = TerminalRenderContext {
+            cells: &mut self.cells,
+            columns: self.columns,
+            rows: self.rows,
+            cell_size,
+            textures: &self.textures,
+            clip: None,
+        };
+        ctx.clear(&Color::BLACK);
+        // Frames are drawn right after their update.
+        application.render(&mut ctx, 0.0);
+    }
+
+    fn flush(&mut self, output: &mut impl Write) -> io::Result<()> {
+        for (index, (cell, drawn)) in self.cells.iter().zip(self.drawn.iter_mut()).enumerate() {
+            if cell == drawn {
+                continue;
+            }
+            *drawn = *cell;
+            let (column, row) = ((index % self.columns) as u16, (index / self.columns) as u16);
+            queue!(
+                output,
+                cursor::MoveTo(column, row),
+                SetForegroundColor(terminal_color(cell.foreground)),
+                SetBackgroundColor(terminal_color(cell.background)),
+                Print(cell.character)
+            )?;
+        }
+        output.flush()
+    }
+
+    fn run_loop(&mut self, application: &mut dyn Application) -> io::Result<()> {
+        let (columns, rows) = terminal::size()?;
+        self.resize(columns, rows);
+        let mut output = io::BufWriter::new(io::stdout());
+        let mut last_update = Instant::now();
+
+        loop {
+            let elapsed = last_update.elapsed().as_secs_f64();
+            let timeout = Duration::from_secs_f64((FRAME_TIME - elapsed).max(0.0));
+            if !self.handle_events(application, timeout)? {
+                return Ok(());
+            }
+            if last_update.elapsed().as_secs_f64() < FRAME_TIME {
+                continue;
+            }
+
+            let update_args = UpdateArgs { dt: last_update.elapsed().as_secs_f64() };
+            last_update = Instant::now();
+            if !application.update(&update_args, Event::Loop(Loop::Update(update_args)), self) {
+                return Ok(());
+            }
+            self.render(application);
+            self.flush(&mut output)?;
+        }
+    }
+}
+
+/// Keeps the terminal in raw mode on the alternate screen while it lives and
+/// puts it back the way it was when dropped, a panic included.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> io::Result<TerminalGuard> {
+        // The panic message is printed before unwinding drops the guard, on
+        // the alternate screen it would be gone with it.
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            hook(info);
+        }));
+
+        terminal::enable_raw_mode()?;
+        let guard = TerminalGuard;
+        crossterm::execute!(io::stdout(), terminal::EnterAlternateScreen, event::EnableMouseCapture, cursor::Hide)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+fn restore_terminal() {
+    let _ = crossterm::execute!(
+        io::stdout(),
+        style::ResetColor,
+        cursor::Show,
+        event::DisableMouseCapture,
+        terminal::LeaveAlternateScreen
+    );
+    let _ = terminal::disable_raw_mode();
+}
+
+impl AbstractionLayer for TerminalAbstraction {
+    fn run(&mut self, application: &mut dyn Application) {
+        let result = TerminalGuard::enter().and_then(|_guard| self.run_loop(application));
+        if let Err(error) = result {
+            eprintln!("{}", error);
+        }
+    }
+}
+
+fn read_texture(path: &Path) -> Result<RgbaImage, Box<dyn error::Error>> {
+    let texture = image::open(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    Ok(texture.to_rgba8())
+}
+
+impl ResourceLoader for TerminalAbstraction {
+    fn load_texture(&mut self, path: &Path) -> Result<TextureHandle, Box<dyn error::Error>> {
+        self.textures.push(read_texture(path)?);
+        Ok(TextureHandle(self.textures.len() - 1))
+    }
+
+    // Text is drawn with the terminal's own font.
+    fn load_font(&mut self, _path: &Path) -> Result<FontHandle, Box<dyn error::Error>> {
+        self.fonts += 1;
+        Ok(FontHandle(self.fonts - 1))
+    }
+
+    fn reload_texture(&mut self, handle: TextureHandle, path: &Path) -> Result<(), Box<dyn error::Error>> {
+        replace(&mut self.textures, handle.0, read_texture(path)?)
+    }
+
+    fn reload_font(&mut self, handle: FontHandle, _path: &Path) -> Result<(), Box<dyn error::Error>> {
+        if handle.0 < self.fonts {
+            Ok(())
+        } else {
+            Err(format!("no resource with handle {}", handle.0).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_inputs() {
+        let inputs = key_inputs(KeyEvent::new(KeyCode::Char('Z'), KeyModifiers::SHIFT));
+        assert_eq!(3, inputs.len());
+        assert_eq!(
+            Input::Button(ButtonArgs { state: ButtonState::Press, button: Button::Keyboard(Key::Z), scancode: None }),
+            inputs[0]
+        );
+        assert_eq!(Input::Text(String::from("Z")), inputs[1]);
+
+        let inputs = key_inputs(KeyEvent::new(KeyCode::F(1), KeyModifiers::NONE));
+        assert_eq!(
+            Input::Button(ButtonArgs { state: ButtonState::Release, button: Button::Keyboard(Key::F1), scancode: None }),
+            inputs[1]
+        );
+        assert!(key_inputs(KeyEvent::new(KeyCode::Null, KeyModifiers::NONE)).is_empty());
+    }
+
+    #[test]
+    fn test_terminal_cells() {
+        let mut abstraction = TerminalAbstraction::new([40, 20]);
+        abstraction.resize(4, 2);
+        abstraction.textures.push(RgbaImage::from_pixel(20, 10, image::Rgba([255, 0, 0, 255])));
+        let mut ctx = TerminalRenderContext {
+            cells: &mut abstraction.cells,
+            columns: 4,
+            rows: 2,
+            cell_size: [10.0, 10.0],
+            textures: &abstraction.textures,
+            clip: None,
+        };
+        ctx.clear(&Color::BLACK);
+        ctx.draw_image(TextureHandle(0), &Transform::new(20.0, 10.0));
+        ctx.draw_text("Hi", FontHandle(0), 10, &Transform::new(0.0, 10.0), &Color::WHITE);
+        ctx.set_clip(Some(Rect::new(0.0, 10.0, 10.0, 10.0)));
+        ctx.draw_text("ab", FontHandle(0), 10, &Transform::new(0.0, 20.0), &Color::WHITE);
+        let width = ctx.text_width("abc", FontHandle(0), 10);
+
+        let cells = &abstraction.cells;
+        assert_eq!(30.0, width);
+        assert_eq!(['H', 'i', ' ', ' '], [cells[0].character, cells[1].character, cells[2].character, cells[3].character]);
+        assert_eq!(['a', ' '], [cells[4].character, cells[5].character]);
+        assert_eq!([1.0, 0.0, 0.0], cells[6].background);
+        assert_eq!([1.0, 0.0, 0.0], cells[7].background);
+        assert_eq!([0.0, 0.0, 0.0], cells[5].background);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::ai::move_generator::*;
+use crate::ai::evaluator::*;
+use crate::states::play::{check_for_collision, clear_lines, fill_field};
+use crate::game_data::*;
+use crate::tetramino::*;
+use crate::input::Action;
+use piston_window::*;
+use std::collections::VecDeque;
+
+const STEP_INTERVAL: f64 = 0.12;
+
+pub struct Placement {
+    pub inputs: Vec<Move>,
+    pub score: f64,
+}
+
+pub fn apply_placement(
+    figure_type: usize,
+    position: &Point,
+    rotation: usize,
+    data: &TetraminoesData,
+    play_table: &mut GameField,
+) -> usize {
+    let rotation = &data[figure_type].rotations[rotation];
+    fill_field(position, rotation, play_table, TETRAMINOES[figure_type]);
+    clear_lines(play_table)
+}
+
+pub fn best_placement(
+    current: &Tetramino,
+    next: Option<usize>,
+    data: &TetraminoesData,
+    collision_table: &RotationCollisionTable,
+    play_table: &GameField,
+    weights: &Weights,
+) -> Option<Placement> {
+    let mut best: Option<Placement> = None;
+    for placement in generate_moves(current, data, collision_table, play_table) {
+        let mut field = *play_table;
+        let lines = apply_placement(
+            current.get_type(),
+            &placement.position,
+            placement.rotation,
+            data,
+            &mut field,
+        );
+
+        let score = match next {
+            Some(next_type) => {
+                let next_figure = Tetramino::new(next_type);
+                generate_moves(&next_figure, data, collision_table, &field)
+                    .iter()
+                    .map(|next_placement| {
+                        let mut next_field = field;
+                        let next_lines = apply_placement(
+                            next_type,
+                            &next_placement.position,
+                            next_placement.rotation,
+                            data,
+                            &mut next_field,
+                        );
+                        Features::new(&next_field, lines + next_lines).score(weights)
+                    })
+                    .fold(f64::NEG_INFINITY, f64::max)
+            }
+            None => Features::new(&field, lines).score(weights),
+        };
+
+        if best.as_ref().is_none_or(|best| score > best.score) {
+            best = Some(Placement {
+                inputs: placement.inputs,
+                score,
+            });
+        }
+    }
+
+    best
+}
+
+pub struct Bot {
+    weights: Weights,
+    lookahead: bool,
+    inputs: VecDeque<Move>,
+    dropped: bool,
+    planned_spawn: Option<u32>,
+    step_time: f64,
+}
+
+impl Bot {
+    pub fn new(weights: Weights, lookahead: bool) -> Bot {
+        Bot {
+            weights,
+            lookahead,
+            inputs: VecDeque::new(),
+            dropped: false,
+            planned_spawn: None,
+            step_time: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, dt: f64, data: &GameData) -> Vec<(Action, ButtonState)> {
+        if self.planned_spawn != Some(data.spawn_count) {
+            self.planned_spawn = Some(data.spawn_count);
+            let next = if self.lookahead { Some(data.next_figure) } else { None };
+            let placement = best_placement(
+                &data.current_figure,
+                next,
+                &data.tetraminoes_data,
+                &data.collision_table,
+                &data.play_table,
+                &self.weights,
+            );
+            self.inputs = placement
+                .map(|placement| tapped_inputs(&placement.inputs, data))
+                .unwrap_or_default();
+            self.dropped = false;
+            self.step_time = 0.0;
+        }
+
+        self.step_time += dt;
+        if self.step_time < STEP_INTERVAL || !self.ready(data) {
+            return vec![];
+        }
+        self.step_time = 0.0;
+
+        match self.inputs.pop_front() {
+            Some(movement) => {
+                self.dropped |= movement == Move::Drop;
+                tap(movement_action(movement))
+            }
+            None => vec![],
+        }
+    }
+
+    // A drop is carried out by the fast fall state, which ignores input until
+    // the figure lands, so later inputs wait for the figure to be grounded.
+    fn ready(&self, data: &GameData) -> bool {
+        let current = &data.current_figure;
+        let rotation = &data.tetraminoes_data[current.get_type()].rotations[current.get_rotation()];
+        let below = Point { x: current.get_position().x, y: current.get_position().y + 1 };
+        !self.dropped || check_for_collision(&below, rotation, &data.play_table)
+    }
+}
+
+// The bot taps instead of holding keys, so moves to a wall become as many
+// taps as the figure travels.
+fn tapped_inputs(inputs: &[Move], data: &GameData) -> VecDeque<Move> {
+    let mut figure = data.current_figure;
+    let mut taps = VecDeque::new();
+    for movement in inputs {
+        let next = match apply_move(
+            &figure,
+            *movement,
+            &data.tetraminoes_data,
+            &data.collision_table,
+            &data.play_table,
+        ) {
+            Some(next) => next,
+            None => break,
+        };
+        let distance = next.get_position().x - figure.get_position().x;
+        match movement {
+            Move::LeftWall | Move::RightWall => {
+                let tap = if distance < 0 { Move::Left } else { Move::Right };
+                taps.extend(std::iter::repeat_n(tap, distance.unsigned_abs() as usize));
+            }
+            _ => taps.push_back(*movement),
+        }
+        figure = next;
+    }
+    taps
+}
+
+fn movement_action(movement: Move) -> Action {
+    match movement {
+        Move::Left | Move::LeftWall => Action::MoveLeft,
+        Move::Right | Move::RightWall => Action::MoveRight,
+        Move::RotateLeft => Action::RotateCounterClockwise,
+        Move::RotateRight => Action::RotateClockwise,
+        Move::Drop => Action::HardDrop,
+    }
+}
+
+fn tap(action: Action) -> Vec<(Action, ButtonState)> {
+    vec![(action, ButtonState::Press), (action, ButtonState::Release)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_best_placement_clears_line() {
+        let data = initialize_tetraminoes_data();
+        let mut play_table: GameField = [TetrominoType::E; WIDTH * HEIGHT];
+        for column in 4..WIDTH {
+            play_table[(HEIGHT - 1) * WIDTH + column] = TetrominoType::O;
+        }
+
+        let figure = Tetramino::new(TetrominoType::I as usize);
+        let collision_table = RotationCollisionTable::new();
+        let placement = best_placement(
+            &figure,
+            None,
+            &data,
+            &collision_table,
+            &play_table,
+            &Weights::default(),
+        )
+        .unwrap();
+
+        // Playing the inputs from the spawn lands the I flat in the gap.
+        let mut landed = figure;
+        for movement in &placement.inputs {
+            landed = apply_move(&landed, *movement, &data, &collision_table, &play_table).unwrap();
+        }
+        assert_eq!(
+            [0, 1, 2, 3].map(|x| Point { x, y: HEIGHT as i32 - 1 }),
+            placement_cells(landed.get_type(), landed.get_position(), landed.get_rotation(), &data)
+        );
+
+        let mut field = play_table;
+        let lines = apply_placement(
+            landed.get_type(),
+            landed.get_position(),
+            landed.get_rotation(),
+            &data,
+            &mut field,
+        );
+        assert_eq!(1, lines);
+        assert_eq!(0, Features::new(&field, 0).aggregate_height);
+    }
+}
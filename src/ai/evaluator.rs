@@ -0,0 +1,125 @@
+use crate::game_data::{GameField, HEIGHT, WIDTH};
+use crate::tetramino::TetrominoType;
+
+#[derive(Copy, Clone)]
+pub struct Weights {
+    pub aggregate_height: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+    pub lines: f64,
+    pub wells: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            aggregate_height: -0.510066,
+            holes: -0.35663,
+            bumpiness: -0.184483,
+            lines: 0.760666,
+            wells: -0.1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct Features {
+    pub aggregate_height: u32,
+    pub holes: u32,
+    pub bumpiness: u32,
+    pub lines: u32,
+    pub wells: u32,
+}
+
+impl Features {
+    pub fn new(play_table: &GameField, lines: usize) -> Self {
+        let heights = column_heights(play_table);
+        let mut features = Features {
+            lines: lines as u32,
+            ..Default::default()
+        };
+
+        for column in 0..WIDTH {
+            features.aggregate_height += heights[column];
+
+            let top = HEIGHT - heights[column] as usize;
+            for row in top..HEIGHT {
+                if let TetrominoType::E = play_table[row * WIDTH + column] {
+                    features.holes += 1;
+                }
+            }
+
+            if column + 1 < WIDTH {
+                let difference = heights[column] as i32 - heights[column + 1] as i32;
+                features.bumpiness += difference.unsigned_abs();
+            }
+
+            let left = if column == 0 { HEIGHT as u32 } else { heights[column - 1] };
+            let right = if column + 1 == WIDTH { HEIGHT as u32 } else { heights[column + 1] };
+            let edge = left.min(right);
+            if edge > heights[column] {
+                features.wells += edge - heights[column];
+            }
+        }
+
+        features
+    }
+
+    pub fn score(&self, weights: &Weights) -> f64 {
+        weights.aggregate_height * self.aggregate_height as f64
+            + weights.holes * self.holes as f64
+            + weights.bumpiness * self.bumpiness as f64
+            + weights.lines * self.lines as f64
+            + weights.wells * self.wells as f64
+    }
+}
+
+pub fn column_heights(play_table: &GameField) -> [u32; WIDTH] {
+    let mut heights = [0; WIDTH];
+    for (column, height) in heights.iter_mut().enumerate() {
+        for row in 0..HEIGHT {
+            match play_table[row * WIDTH + column] {
+                TetrominoType::E => {}
+                _ => {
+                    *height = (HEIGHT - row) as u32;
+                    break;
+                }
+            }
+        }
+    }
+
+    heights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_features_empty_field() {
+        let play_table: GameField = [TetrominoType::E; WIDTH * HEIGHT];
+        let features = Features::new(&play_table, 0);
+        assert_eq!(0, features.aggregate_height);
+        assert_eq!(0, features.holes);
+        assert_eq!(0, features.bumpiness);
+        assert_eq!(0, features.wells);
+    }
+
+    #[test]
+    fn test_features_holes_and_wells() {
+        let mut play_table: GameField = [TetrominoType::E; WIDTH * HEIGHT];
+        // Bottom two rows filled except column 9, with a covered hole in column 0.
+        for column in 0..WIDTH - 1 {
+            play_table[(HEIGHT - 2) * WIDTH + column] = TetrominoType::O;
+            play_table[(HEIGHT - 1) * WIDTH + column] = TetrominoType::O;
+        }
+        play_table[(HEIGHT - 1) * WIDTH] = TetrominoType::E;
+
+        let features = Features::new(&play_table, 1);
+        assert_eq!(18, features.aggregate_height);
+        assert_eq!(1, features.holes);
+        assert_eq!(2, features.bumpiness);
+        assert_eq!(2, features.wells);
+        assert_eq!(1, features.lines);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod evaluator;
+pub mod move_generator;
+pub mod bot;
\ No newline at end of file
@@ -0,0 +1,282 @@
+use crate::states::play::{check_for_collision, rotation_kick};
+use crate::game_data::*;
+use crate::tetramino::*;
+use std::collections::{HashSet, VecDeque};
+
+const MARGIN: i32 = 3;
+const COLUMNS: i32 = WIDTH as i32 + 2 * MARGIN;
+const ROWS: i32 = HEIGHT as i32 + 2 * MARGIN;
+const ROTATIONS: i32 = 4;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Move {
+    Left,
+    Right,
+    /// Holding left until the figure stops, one input however far it goes.
+    LeftWall,
+    /// Holding right until the figure stops.
+    RightWall,
+    RotateLeft,
+    RotateRight,
+    Drop,
+}
+
+const MOVES: [Move; 7] = [
+    Move::Left,
+    Move::Right,
+    Move::LeftWall,
+    Move::RightWall,
+    Move::RotateLeft,
+    Move::RotateRight,
+    Move::Drop,
+];
+
+pub struct ReachablePlacement {
+    pub position: Point,
+    pub rotation: usize,
+    pub cells: [Point; 4],
+    pub inputs: Vec<Move>,
+}
+
+pub fn drop_position(
+    position: &Point,
+    rotation: &TetrominoRotation,
+    play_table: &GameField,
+) -> Point {
+    let mut landing = *position;
+    loop {
+        let next = Point { x: landing.x, y: landing.y + 1 };
+        if check_for_collision(&next, rotation, play_table) {
+            return landing;
+        }
+        landing = next;
+    }
+}
+
+pub fn placement_cells(
+    figure_type: usize,
+    position: &Point,
+    rotation: usize,
+    tetraminoes_data: &TetraminoesData,
+) -> [Point; 4] {
+    let mut cells = [Point { x: 0, y: 0 }; 4];
+    let rotation = &tetraminoes_data[figure_type].rotations[rotation];
+    for (cell, offset) in cells.iter_mut().zip(rotation) {
+        *cell = position.add(&offset);
+    }
+    cells.sort_by_key(|cell| (cell.y, cell.x));
+    cells
+}
+
+fn state_index(position: &Point, rotation: usize) -> Option<usize> {
+    let x = position.x + MARGIN;
+    let y = position.y + MARGIN;
+    if !(0..COLUMNS).contains(&x) || !(0..ROWS).contains(&y) {
+        return None;
+    }
+
+    Some(((rotation as i32 * ROWS + y) * COLUMNS + x) as usize)
+}
+
+/// Carries out one input, returns `None` when it is blocked.
+pub fn apply_move(
+    figure: &Tetramino,
+    movement: Move,
+    tetraminoes_data: &TetraminoesData,
+    collision_table: &RotationCollisionTable,
+    play_table: &GameField,
+) -> Option<Tetramino> {
+    let rotation = &tetraminoes_data[figure.get_type()].rotations[figure.get_rotation()];
+    let mut result = Tetramino::new(figure.get_type());
+    result.set_rotation(figure.get_rotation());
+    result.set_position(*figure.get_position());
+
+    match movement {
+        Move::Left | Move::Right => {
+            let mut position = *figure.get_position();
+            position.x += if movement == Move::Left { -1 } else { 1 };
+            if check_for_collision(&position, rotation, play_table) {
+                return None;
+            }
+            result.set_position(position);
+        }
+
+        Move::LeftWall | Move::RightWall => {
+            let step = Point { x: if movement == Move::LeftWall { -1 } else { 1 }, y: 0 };
+            let mut position = *figure.get_position();
+            while !check_for_collision(&position.add(&step), rotation, play_table) {
+                position = position.add(&step);
+            }
+            if position == *figure.get_position() {
+                return None;
+            }
+            result.set_position(position);
+        }
+
+        Move::RotateLeft | Move::RotateRight => {
+            let next_rotation = if movement == Move::RotateLeft {
+                figure.peek_left_rotation()
+            } else {
+                figure.peek_right_rotation()
+            };
+            let kick = rotation_kick(
+                figure,
+                next_rotation,
+                tetraminoes_data,
+                collision_table,
+                play_table,
+            )?;
+            result.move_it(&kick);
+            result.set_rotation(next_rotation);
+        }
+
+        Move::Drop => {
+            let position = drop_position(figure.get_position(), rotation, play_table);
+            if position.y == figure.get_position().y {
+                return None;
+            }
+            result.set_position(position);
+        }
+    }
+
+    Some(result)
+}
+
+/// Finds every placement the figure can lock into and the shortest input
+/// sequence leading to it. Gravity is ignored while searching, so the inputs
+/// assume they are entered faster than the figure falls on its own.
+pub fn generate_moves(
+    figure: &Tetramino,
+    tetraminoes_data: &TetraminoesData,
+    collision_table: &RotationCollisionTable,
+    play_table: &GameField,
+) -> Vec<ReachablePlacement> {
+    let mut placements = Vec::new();
+    let start_rotation = &tetraminoes_data[figure.get_type()].rotations[figure.get_rotation()];
+    let start_index = match state_index(figure.get_position(), figure.get_rotation()) {
+        Some(index) => index,
+        None => return placements,
+    };
+    if check_for_collision(figure.get_position(), start_rotation, play_table) {
+        return placements;
+    }
+
+    let mut parents: Vec<Option<(usize, Move)>> = vec![None; (ROTATIONS * ROWS * COLUMNS) as usize];
+    let mut visited = vec![false; parents.len()];
+    let mut found: HashSet<Vec<(i32, i32)>> = HashSet::new();
+    let mut queue: VecDeque<(usize, Tetramino)> = VecDeque::new();
+
+    let mut start = Tetramino::new(figure.get_type());
+    start.set_rotation(figure.get_rotation());
+    start.set_position(*figure.get_position());
+    visited[start_index] = true;
+    queue.push_back((start_index, start));
+
+    while let Some((index, current)) = queue.pop_front() {
+        let rotation = &tetraminoes_data[current.get_type()].rotations[current.get_rotation()];
+        let below = Point { x: current.get_position().x, y: current.get_position().y + 1 };
+        if check_for_collision(&below, rotation, play_table) {
+            let cells = placement_cells(
+                current.get_type(),
+                current.get_position(),
+                current.get_rotation(),
+                tetraminoes_data,
+            );
+            if found.insert(cells.iter().map(|cell| (cell.x, cell.y)).collect()) {
+                placements.push(ReachablePlacement {
+                    position: *current.get_position(),
+                    rotation: current.get_rotation(),
+                    cells,
+                    inputs: input_path(&parents, index),
+                });
+            }
+        }
+
+        for movement in MOVES.iter() {
+            let next = match apply_move(&current, *movement, tetraminoes_data, collision_table, play_table) {
+                Some(next) => next,
+                None => continue,
+            };
+            let next_index = match state_index(next.get_position(), next.get_rotation()) {
+                Some(next_index) => next_index,
+                None => continue,
+            };
+            if visited[next_index] {
+                continue;
+            }
+
+            visited[next_index] = true;
+            parents[next_index] = Some((index, *movement));
+            queue.push_back((next_index, next));
+        }
+    }
+
+    placements
+}
+
+fn input_path(parents: &[Option<(usize, Move)>], index: usize) -> Vec<Move> {
+    let mut inputs = Vec::new();
+    let mut current = index;
+    while let Some((parent, movement)) = parents[current] {
+        inputs.push(movement);
+        current = parent;
+    }
+    inputs.reverse();
+    inputs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placements_for(figure_type: TetrominoType, play_table: &GameField) -> Vec<ReachablePlacement> {
+        let figure = Tetramino::new(figure_type as usize);
+        generate_moves(
+            &figure,
+            &initialize_tetraminoes_data(),
+            &RotationCollisionTable::new(),
+            play_table,
+        )
+    }
+
+    #[test]
+    fn test_generate_moves_empty_field() {
+        let play_table: GameField = [TetrominoType::E; WIDTH * HEIGHT];
+        assert_eq!(9, placements_for(TetrominoType::O, &play_table).len());
+        assert_eq!(17, placements_for(TetrominoType::I, &play_table).len());
+        assert_eq!(34, placements_for(TetrominoType::T, &play_table).len());
+    }
+
+    #[test]
+    fn test_generate_moves_shortest_path() {
+        let play_table: GameField = [TetrominoType::E; WIDTH * HEIGHT];
+        let placements = placements_for(TetrominoType::O, &play_table);
+        let below_spawn = placements
+            .iter()
+            .find(|placement| placement.position.x == 5)
+            .unwrap();
+        assert_eq!(vec![Move::Drop], below_spawn.inputs);
+    }
+
+    #[test]
+    fn test_generate_moves_tuck() {
+        let mut play_table: GameField = [TetrominoType::E; WIDTH * HEIGHT];
+        for column in 0..2 {
+            play_table[(HEIGHT - 3) * WIDTH + column] = TetrominoType::O;
+        }
+        for column in 4..WIDTH {
+            play_table[(HEIGHT - 2) * WIDTH + column] = TetrominoType::O;
+            play_table[(HEIGHT - 1) * WIDTH + column] = TetrominoType::O;
+        }
+
+        let placements = placements_for(TetrominoType::O, &play_table);
+        let tuck = placements
+            .iter()
+            .find(|placement| {
+                placement.position.x == 0 && placement.position.y == HEIGHT as i32 - 2
+            })
+            .unwrap();
+        assert_eq!(4, tuck.inputs.len());
+        assert_eq!(Some(&Move::LeftWall), tuck.inputs.last());
+    }
+}
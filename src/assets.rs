@@ -0,0 +1,239 @@
+extern crate find_folder;
+use crate::storage::{data_directory, KeyValueFile};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::env;
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::OnceLock;
+
+const ASSET_DIRECTORY: &str = "assets";
+const MANIFEST_FILE: &str = "manifest.cfg";
+const ASSETS_VARIABLE: &str = "TETRIS_ASSETS";
+const EMBEDDED_DIRECTORY: &str = "embedded-assets";
+const PARENT_DEPTH: find_folder::ParentsDepth = 3;
+const KIDS_DEPTH: find_folder::KidsDepth = 3;
+
+macro_rules! embedded {
+    ($($path:literal),* $(,)?) => {
+        &[$(($path, include_bytes!(concat!("../assets/", $path)))),*]
+    };
+}
+
+// Keep in step with `assets/manifest.cfg`, `test_embedded_assets` checks it.
+const EMBEDDED: &[(&str, &[u8])] = embedded![
+    "manifest.cfg",
+    "puzzles/01_four_lines.puzzle",
+    "puzzles/02_perfect_clear.puzzle",
+    "puzzles/03_tspin_double.puzzle",
+    "sounds/game_over.wav",
+    "sounds/level_up.wav",
+    "sounds/line_clear.wav",
+    "sounds/lock.wav",
+    "sounds/move.wav",
+    "sounds/music.wav",
+    "sounds/rotate.wav",
+    "sounds/tetris.wav",
+    "themes/classic/TetrisFont2.ttf",
+    "themes/classic/background2.png",
+    "themes/classic/cube_block2.png",
+    "themes/classic/empty_block2.png",
+    "themes/classic/theme.cfg",
+    "themes/neon/background.png",
+    "themes/neon/block_i.png",
+    "themes/neon/block_j.png",
+    "themes/neon/block_l.png",
+    "themes/neon/block_o.png",
+    "themes/neon/block_s.png",
+    "themes/neon/block_t.png",
+    "themes/neon/block_z.png",
+    "themes/neon/empty_block.png",
+    "themes/neon/theme.cfg",
+];
+
+static ASSETS: OnceLock<AssetManager> = OnceLock::new();
+
+/// The one place that knows where the assets live.
+#[derive(Clone, Debug)]
+pub struct AssetManager {
+    root: PathBuf,
+}
+
+impl AssetManager {
+    /// Uses `root` after checking it has every file in its manifest.
+    pub fn open(root: &Path) -> Result<AssetManager, Box<dyn error::Error>> {
+        if !root.is_dir() {
+            return Err(format!("asset directory {} does not exist", root.display()).into());
+        }
+
+        let manifest = KeyValueFile::load(&root.join(MANIFEST_FILE))?;
+        let missing: Vec<&str> = manifest
+            .get_all("files.file")
+            .into_iter()
+            .filter(|file| !root.join(file).is_file())
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("assets missing from {}: {}", root.display(), missing.join(", ")).into());
+        }
+
+        Ok(AssetManager { root: root.to_path_buf() })
+    }
+
+    /// Picks the `--assets` path, then `TETRIS_ASSETS`, then an `assets`
+    /// folder near the working directory. Without any of them the copy built
+    /// into the binary is unpacked and used.
+    pub fn locate(path: Option<&Path>) -> Result<AssetManager, Box<dyn error::Error>> {
+        if let Some(path) = path {
+            return AssetManager::open(path).map_err(|error| format!("--assets: {}", error).into());
+        }
+
+        if let Some(path) = env::var_os(ASSETS_VARIABLE) {
+            return AssetManager::open(Path::new(&path))
+                .map_err(|error| format!("{}: {}", ASSETS_VARIABLE, error).into());
+        }
+
+        match find_folder::Search::ParentsThenKids(PARENT_DEPTH, KIDS_DEPTH).for_folder(ASSET_DIRECTORY) {
+            Ok(found) => AssetManager::open(&found),
+            Err(_) => AssetManager::embedded(),
+        }
+    }
+
+    fn embedded() -> Result<AssetManager, Box<dyn error::Error>> {
+        let root = data_directory()?
+            .join(EMBEDDED_DIRECTORY)
+            .join(env!("CARGO_PKG_VERSION"));
+        unpack(&root)?;
+        AssetManager::open(&root)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+fn unpack(root: &Path) -> Result<(), Box<dyn error::Error>> {
+    for (file, contents) in EMBEDDED {
+        let path = root.join(file);
+        if fs::read(&path).is_ok_and(|existing| existing == *contents) {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents).map_err(|error| format!("{}: {}", path.display(), error))?;
+    }
+    Ok(())
+}
+
+/// Settles where the assets are for the rest of the run. An empty `path`
+/// means no override was given.
+pub fn init_assets(path: &str) -> Result<&'static AssetManager, Box<dyn error::Error>> {
+    if let Some(assets) = ASSETS.get() {
+        return Ok(assets);
+    }
+
+    let path = Some(Path::new(path)).filter(|path| !path.as_os_str().is_empty());
+    let assets = AssetManager::locate(path)?;
+    Ok(ASSETS.get_or_init(|| assets))
+}
+
+pub fn asset_directory() -> Result<PathBuf, Box<dyn error::Error>> {
+    Ok(init_assets("")?.root().to_path_buf())
+}
+
+/// Reports files that change under a directory while the game runs.
+pub struct AssetWatcher {
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+}
+
+impl AssetWatcher {
+    pub fn new(root: &Path) -> Result<AssetWatcher, Box<dyn error::Error>> {
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .map_err(|error| format!("{}: {}", root.display(), error))?;
+        Ok(AssetWatcher { _watcher: watcher, events })
+    }
+
+    /// Files written, created or removed since the last call. Never blocks.
+    pub fn changed(&self) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = vec![];
+        for event in self.events.try_iter().flatten() {
+            if event.kind.is_access() {
+                continue;
+            }
+            for path in event.paths {
+                if !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("tetris-assets-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    #[test]
+    fn test_embedded_assets() {
+        let source = Path::new(env!("CARGO_MANIFEST_DIR")).join(ASSET_DIRECTORY);
+        let manifest = KeyValueFile::load(&source.join(MANIFEST_FILE)).unwrap();
+        let mut listed = manifest.get_all("files.file");
+        listed.push(MANIFEST_FILE);
+        listed.sort();
+
+        let mut embedded: Vec<&str> = EMBEDDED.iter().map(|(file, _)| *file).collect();
+        embedded.sort();
+        assert_eq!(listed, embedded);
+        AssetManager::open(&source).unwrap();
+    }
+
+    #[test]
+    fn test_missing_assets() {
+        let root = scratch("missing");
+        unpack(&root).unwrap();
+        AssetManager::open(&root).unwrap();
+
+        fs::remove_file(root.join("sounds/lock.wav")).unwrap();
+        fs::remove_file(root.join("themes/neon/theme.cfg")).unwrap();
+        let error = AssetManager::open(&root).unwrap_err().to_string();
+        assert!(error.contains("sounds/lock.wav"), "{}", error);
+        assert!(error.contains("themes/neon/theme.cfg"), "{}", error);
+
+        let error = AssetManager::locate(Some(&root.join("nowhere"))).unwrap_err().to_string();
+        assert!(error.starts_with("--assets:"), "{}", error);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_asset_watcher() {
+        let root = scratch("watcher");
+        fs::create_dir_all(root.join("themes")).unwrap();
+        let watcher = AssetWatcher::new(&root).unwrap();
+        assert!(watcher.changed().is_empty());
+
+        let file = root.join("themes").join("theme.cfg");
+        fs::write(&file, "name = Test").unwrap();
+        let mut changed = vec![];
+        for _ in 0..50 {
+            changed.extend(watcher.changed());
+            if changed.contains(&file) {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        assert!(changed.contains(&file), "{:?}", changed);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,336 @@
+use crate::assets::asset_directory;
+use crate::events::*;
+use crate::game_data::GameData;
+use crate::hud::Notice;
+use crate::settings::{self, MAX_VOLUME};
+use std::cell::RefCell;
+use std::error;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+const SOUND_DIRECTORY: &str = "sounds";
+pub const MUSIC_FILE: &str = "music.wav";
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Sound {
+    Move,
+    Rotate,
+    Lock,
+    LineClear,
+    Tetris,
+    LevelUp,
+    GameOver,
+}
+
+pub const SOUNDS: [Sound; 7] = [
+    Sound::Move,
+    Sound::Rotate,
+    Sound::Lock,
+    Sound::LineClear,
+    Sound::Tetris,
+    Sound::LevelUp,
+    Sound::GameOver,
+];
+
+impl Sound {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Sound::Move => "move.wav",
+            Sound::Rotate => "rotate.wav",
+            Sound::Lock => "lock.wav",
+            Sound::LineClear => "line_clear.wav",
+            Sound::Tetris => "tetris.wav",
+            Sound::LevelUp => "level_up.wav",
+            Sound::GameOver => "game_over.wav",
+        }
+    }
+}
+
+/// Something that can make noise. Volumes are in `[0, 1]`.
+pub trait AudioBackend {
+    fn play_sound(&mut self, sound: Sound, volume: f32);
+    /// Starts the background music, looping until stopped.
+    fn play_music(&mut self, volume: f32);
+    fn set_music_volume(&mut self, volume: f32);
+    fn stop_music(&mut self);
+}
+
+pub struct NullBackend;
+
+impl AudioBackend for NullBackend {
+    fn play_sound(&mut self, _sound: Sound, _volume: f32) {}
+    fn play_music(&mut self, _volume: f32) {}
+    fn set_music_volume(&mut self, _volume: f32) {}
+    fn stop_music(&mut self) {}
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum AudioEvent {
+    Sound(Sound, f32),
+    Music(f32),
+    MusicVolume(f32),
+    StopMusic,
+}
+
+/// Remembers everything it was asked to play. Clones share the log, so a
+/// test can keep one while the player owns the other.
+#[derive(Clone, Default)]
+pub struct RecordingBackend {
+    events: Rc<RefCell<Vec<AudioEvent>>>,
+}
+
+impl RecordingBackend {
+    pub fn new() -> RecordingBackend {
+        RecordingBackend::default()
+    }
+
+    pub fn events(&self) -> Vec<AudioEvent> {
+        self.events.borrow().clone()
+    }
+}
+
+impl AudioBackend for RecordingBackend {
+    fn play_sound(&mut self, sound: Sound, volume: f32) {
+        self.events.borrow_mut().push(AudioEvent::Sound(sound, volume));
+    }
+
+    fn play_music(&mut self, volume: f32) {
+        self.events.borrow_mut().push(AudioEvent::Music(volume));
+    }
+
+    fn set_music_volume(&mut self, volume: f32) {
+        self.events.borrow_mut().push(AudioEvent::MusicVolume(volume));
+    }
+
+    fn stop_music(&mut self) {
+        self.events.borrow_mut().push(AudioEvent::StopMusic);
+    }
+}
+
+#[cfg(feature = "sound")]
+mod rodio_backend {
+    use super::*;
+    use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+    use std::fs;
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    /// Plays through the default output device. Files are read once and
+    /// decoded again for every play.
+    pub struct RodioBackend {
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+        sounds: Vec<(Sound, Arc<[u8]>)>,
+        music: Option<Arc<[u8]>>,
+        music_sink: Option<Sink>,
+    }
+
+    impl RodioBackend {
+        pub fn new(directory: &Path, notice: &mut Notice) -> Result<RodioBackend, Box<dyn error::Error>> {
+            let (stream, handle) = OutputStream::try_default()?;
+            let mut sounds = vec![];
+            for sound in SOUNDS.iter() {
+                match fs::read(directory.join(sound.file_name())) {
+                    Ok(bytes) => sounds.push((*sound, Arc::from(bytes))),
+                    Err(error) => notice.show(format!("{}: {}", sound.file_name(), error)),
+                }
+            }
+            let music = fs::read(directory.join(MUSIC_FILE)).ok().map(Arc::from);
+
+            Ok(RodioBackend {
+                _stream: stream,
+                handle,
+                sounds,
+                music,
+                music_sink: None,
+            })
+        }
+
+        fn decode(bytes: &Arc<[u8]>) -> Option<Decoder<Cursor<Arc<[u8]>>>> {
+            Decoder::new(Cursor::new(bytes.clone())).ok()
+        }
+    }
+
+    impl AudioBackend for RodioBackend {
+        fn play_sound(&mut self, sound: Sound, volume: f32) {
+            let bytes = match self.sounds.iter().find(|(loaded, _)| *loaded == sound) {
+                Some((_, bytes)) => bytes,
+                None => return,
+            };
+            if let (Some(source), Ok(sink)) = (RodioBackend::decode(bytes), Sink::try_new(&self.handle)) {
+                sink.set_volume(volume);
+                sink.append(source);
+                sink.detach();
+            }
+        }
+
+        fn play_music(&mut self, volume: f32) {
+            self.stop_music();
+            let source = match self.music.as_ref().and_then(RodioBackend::decode) {
+                Some(source) => source,
+                None => return,
+            };
+            if let Ok(sink) = Sink::try_new(&self.handle) {
+                sink.set_volume(volume);
+                sink.append(source.repeat_infinite());
+                self.music_sink = Some(sink);
+            }
+        }
+
+        fn set_music_volume(&mut self, volume: f32) {
+            if let Some(sink) = &self.music_sink {
+                sink.set_volume(volume);
+            }
+        }
+
+        fn stop_music(&mut self) {
+            if let Some(sink) = self.music_sink.take() {
+                sink.stop();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sound")]
+fn open_backend(directory: &Path, notice: &mut Notice) -> Result<Box<dyn AudioBackend>, Box<dyn error::Error>> {
+    Ok(Box::new(rodio_backend::RodioBackend::new(directory, notice)?))
+}
+
+#[cfg(not(feature = "sound"))]
+fn open_backend(_directory: &Path, _notice: &mut Notice) -> Result<Box<dyn AudioBackend>, Box<dyn error::Error>> {
+    Ok(Box::new(NullBackend))
+}
+
+fn sound_directory() -> Result<PathBuf, Box<dyn error::Error>> {
+    Ok(asset_directory()?.join(SOUND_DIRECTORY))
+}
+
+fn volume(parts: &[u32]) -> f32 {
+    parts
+        .iter()
+        .map(|part| (*part).min(MAX_VOLUME) as f32 / MAX_VOLUME as f32)
+        .product()
+}
+
+/// Applies the volume settings and keeps track of the music.
+pub struct AudioPlayer {
+    backend: Box<dyn AudioBackend>,
+    music_playing: bool,
+}
+
+impl AudioPlayer {
+    pub fn new(backend: Box<dyn AudioBackend>) -> AudioPlayer {
+        AudioPlayer {
+            backend,
+            music_playing: false,
+        }
+    }
+
+    pub fn silent() -> AudioPlayer {
+        AudioPlayer::new(Box::new(NullBackend))
+    }
+
+    /// The real output when built with the `sound` feature, silence when
+    /// not or when no device can be opened. Problems go to `notice`.
+    pub fn open(notice: &mut Notice) -> AudioPlayer {
+        match sound_directory().and_then(|directory| open_backend(&directory, notice)) {
+            Ok(backend) => AudioPlayer::new(backend),
+            Err(error) => {
+                notice.show(format!("audio disabled: {}", error));
+                AudioPlayer::silent()
+            }
+        }
+    }
+
+    pub fn play(&mut self, sound: Sound, settings: &settings::Audio) {
+        let volume = volume(&[settings.master_volume, settings.effects_volume]);
+        if volume > 0.0 {
+            self.backend.play_sound(sound, volume);
+        }
+    }
+
+    pub fn start_music(&mut self, settings: &settings::Audio) {
+        if !self.music_playing {
+            self.music_playing = true;
+            self.backend.play_music(volume(&[settings.master_volume, settings.music_volume]));
+        }
+    }
+
+    pub fn stop_music(&mut self) {
+        if self.music_playing {
+            self.music_playing = false;
+            self.backend.stop_music();
+        }
+    }
+
+    /// Call after the volume settings changed.
+    pub fn update_volume(&mut self, settings: &settings::Audio) {
+        if self.music_playing {
+            self.backend.set_music_volume(volume(&[settings.master_volume, settings.music_volume]));
+        }
+    }
+}
+
+pub struct AudioListener;
+
+impl EventListener for AudioListener {
+    fn on_event(&mut self, event: &GameEvent, data: &mut GameData) {
+        let sound = match event {
+            GameEvent::PieceMoved { .. } => Sound::Move,
+            GameEvent::PieceRotated { .. } => Sound::Rotate,
+            GameEvent::PieceLocked { .. } => Sound::Lock,
+            GameEvent::LinesCleared { lines: 4, .. } => Sound::Tetris,
+            GameEvent::LinesCleared { .. } => Sound::LineClear,
+            GameEvent::LevelUp { .. } => Sound::LevelUp,
+            GameEvent::GameOver { .. } => Sound::GameOver,
+            _ => return,
+        };
+        data.play_sound(sound);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_player() {
+        let recording = RecordingBackend::new();
+        let mut player = AudioPlayer::new(Box::new(recording.clone()));
+        let mut settings = settings::Audio {
+            master_volume: 50,
+            music_volume: 50,
+            effects_volume: 100,
+        };
+
+        player.play(Sound::Tetris, &settings);
+        player.update_volume(&settings);
+        player.start_music(&settings);
+        player.start_music(&settings);
+        settings.music_volume = 100;
+        player.update_volume(&settings);
+        settings.effects_volume = 0;
+        player.play(Sound::Move, &settings);
+        player.stop_music();
+        player.stop_music();
+
+        assert_eq!(
+            vec![
+                AudioEvent::Sound(Sound::Tetris, 0.5),
+                AudioEvent::Music(0.25),
+                AudioEvent::MusicVolume(0.5),
+                AudioEvent::StopMusic,
+            ],
+            recording.events()
+        );
+    }
+
+    #[test]
+    fn test_sound_files_exist() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join(SOUND_DIRECTORY);
+        for sound in SOUNDS.iter() {
+            assert!(directory.join(sound.file_name()).is_file(), "{}", sound.file_name());
+        }
+        assert!(directory.join(MUSIC_FILE).is_file());
+    }
+}
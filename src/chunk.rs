@@ -247,10 +247,16 @@ mod tests {
 }
 
 pub fn find_chunks(game_field: &mut GameField, begin: usize) -> Vec<Chunk> {
+    // Flooding empties the cells, so remember what they held first.
+    let original = *game_field;
     let chunks_data = flood_field(game_field, begin);
     let mut result = Vec::new();
     for data in chunks_data {
-        result.push(Chunk::new(data));
+        let kinds = data
+            .iter()
+            .map(|point| original[point.x as usize + point.y as usize * WIDTH])
+            .collect();
+        result.push(Chunk::new(data, kinds));
     }
     result
 }
@@ -258,13 +264,15 @@ pub fn find_chunks(game_field: &mut GameField, begin: usize) -> Vec<Chunk> {
 pub struct Chunk {
     pub position: Point,
     pub elements: Vec<Point>,
+    pub kinds: Vec<TetrominoType>,
 }
 
 impl Chunk {
-    pub fn new(data: Vec<Point>) -> Self {
+    pub fn new(data: Vec<Point>, kinds: Vec<TetrominoType>) -> Self {
         Chunk {
             position: Point { x: 0, y: 0 },
             elements: data,
+            kinds,
         }
     }
 
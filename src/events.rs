@@ -0,0 +1,102 @@
+use crate::game_data::GameData;
+use crate::input::Action;
+
+/// Something that happened in a game. States only report these, anything
+/// that reacts to them (statistics, sound, the HUD) subscribes a listener.
+#[derive(Clone, PartialEq, Debug)]
+pub enum GameEvent {
+    /// One simulation step of `dt` seconds of play.
+    Tick { dt: f64 },
+    /// A key or button the falling piece reacted to.
+    KeyPressed { action: Action },
+    PieceSpawned { piece: usize },
+    PieceMoved { direction: i32 },
+    PieceRotated { rotation: usize },
+    PieceLocked { piece: usize, tspin: bool, lines: usize },
+    /// `chain` counts the cascades since the piece locked, 0 for the lines
+    /// the piece completed itself.
+    LinesCleared { lines: usize, chain: u32 },
+    /// Loose chunks landed after a clear.
+    ChainStep { chain: u32 },
+    LevelUp { level: u32 },
+    GameOver { score: u32 },
+}
+
+pub trait EventListener {
+    fn on_event(&mut self, event: &GameEvent, data: &mut GameData);
+}
+
+/// Delivers the events queued in `GameData` to every listener, in the order
+/// they were emitted and subscribed.
+#[derive(Default)]
+pub struct EventBus {
+    listeners: Vec<Box<dyn EventListener>>,
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        EventBus::default()
+    }
+
+    pub fn subscribe(&mut self, listener: Box<dyn EventListener>) {
+        self.listeners.push(listener);
+    }
+
+    /// Events emitted by listeners are delivered in the same call.
+    pub fn dispatch(&mut self, data: &mut GameData) {
+        while !data.events.is_empty() {
+            let events: Vec<GameEvent> = data.events.drain(..).collect();
+            for event in &events {
+                for listener in &mut self.listeners {
+                    listener.on_event(event, data);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Recorder {
+        name: &'static str,
+        log: Rc<RefCell<Vec<(&'static str, GameEvent)>>>,
+    }
+
+    impl EventListener for Recorder {
+        fn on_event(&mut self, event: &GameEvent, data: &mut GameData) {
+            self.log.borrow_mut().push((self.name, event.clone()));
+            if let GameEvent::PieceLocked { lines, .. } = event {
+                data.emit(GameEvent::LinesCleared { lines: *lines, chain: 0 });
+            }
+        }
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let mut data = GameData::new(Settings::default()).unwrap();
+        let log = Rc::new(RefCell::new(vec![]));
+        let mut bus = EventBus::new();
+        bus.subscribe(Box::new(Recorder { name: "first", log: log.clone() }));
+
+        let locked = GameEvent::PieceLocked { piece: 0, tspin: false, lines: 2 };
+        let cleared = GameEvent::LinesCleared { lines: 2, chain: 0 };
+        data.emit(locked.clone());
+        bus.dispatch(&mut data);
+        assert!(data.events.is_empty());
+        assert_eq!(vec![("first", locked.clone()), ("first", cleared.clone())], *log.borrow());
+
+        log.borrow_mut().clear();
+        bus.subscribe(Box::new(Recorder { name: "second", log: log.clone() }));
+        data.emit(GameEvent::LevelUp { level: 1 });
+        bus.dispatch(&mut data);
+        assert_eq!(
+            vec![("first", GameEvent::LevelUp { level: 1 }), ("second", GameEvent::LevelUp { level: 1 })],
+            *log.borrow()
+        );
+    }
+}
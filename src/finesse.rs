@@ -0,0 +1,170 @@
+use crate::ai::move_generator::{generate_moves, placement_cells};
+use crate::game_data::GameField;
+use crate::input::Action;
+use crate::tetramino::*;
+
+/// Inputs that move a piece into place. Soft drops and holds are not part
+/// of the minimum, so they are not counted either.
+pub fn is_finesse_input(action: Action) -> bool {
+    matches!(
+        action,
+        Action::MoveLeft
+            | Action::MoveRight
+            | Action::HardDrop
+            | Action::RotateClockwise
+            | Action::RotateCounterClockwise
+            | Action::Rotate180
+    )
+}
+
+#[derive(Copy, Clone)]
+pub struct FinesseRecord {
+    pub piece: usize,
+    pub inputs: u32,
+    pub minimal: u32,
+}
+
+impl FinesseRecord {
+    pub fn errors(&self) -> u32 {
+        self.inputs.saturating_sub(self.minimal)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Finesse {
+    /// Inputs spent on the piece that is still falling.
+    pub inputs: u32,
+    pub records: Vec<FinesseRecord>,
+}
+
+impl Finesse {
+    pub fn new() -> Finesse {
+        Finesse::default()
+    }
+
+    pub fn record_input(&mut self) {
+        self.inputs += 1;
+    }
+
+    pub fn record_piece(&mut self, piece: usize, minimal: Option<u32>) {
+        if let Some(minimal) = minimal {
+            self.records.push(FinesseRecord {
+                piece,
+                inputs: self.inputs,
+                minimal,
+            });
+        }
+        self.inputs = 0;
+    }
+
+    pub fn errors_by_piece(&self) -> [u32; TETRAMINOS_COUNT] {
+        let mut errors = [0; TETRAMINOS_COUNT];
+        for record in &self.records {
+            errors[record.piece] += record.errors();
+        }
+        errors
+    }
+
+    pub fn total_errors(&self) -> u32 {
+        self.records.iter().map(FinesseRecord::errors).sum()
+    }
+
+    pub fn percentage(&self) -> f64 {
+        if self.records.is_empty() {
+            return 100.0;
+        }
+
+        let clean = self.records.iter().filter(|record| record.errors() == 0).count();
+        clean as f64 * 100.0 / self.records.len() as f64
+    }
+}
+
+/// Returns the fewest inputs needed to bring a freshly spawned figure of the
+/// same type into the placement `figure` is about to lock in. Holding a key
+/// until the figure stops counts as one input, the same as when playing.
+pub fn minimal_inputs(
+    figure: &Tetramino,
+    tetraminoes_data: &TetraminoesData,
+    collision_table: &RotationCollisionTable,
+    play_table: &GameField,
+) -> Option<u32> {
+    let cells = placement_cells(
+        figure.get_type(),
+        figure.get_position(),
+        figure.get_rotation(),
+        tetraminoes_data,
+    );
+    let spawn = Tetramino::new(figure.get_type());
+    generate_moves(&spawn, tetraminoes_data, collision_table, play_table)
+        .iter()
+        .find(|placement| placement.cells == cells)
+        .map(|placement| placement.inputs.len() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_data::{HEIGHT, WIDTH};
+
+    #[test]
+    fn test_minimal_inputs() {
+        let play_table: GameField = [TetrominoType::E; WIDTH * HEIGHT];
+        let data = initialize_tetraminoes_data();
+        let table = RotationCollisionTable::new();
+
+        let mut figure = Tetramino::new(TetrominoType::O as usize);
+        figure.set_position(Point { x: 5, y: HEIGHT as i32 - 2 });
+        assert_eq!(Some(1), minimal_inputs(&figure, &data, &table, &play_table));
+
+        figure.set_position(Point { x: 3, y: HEIGHT as i32 - 2 });
+        assert_eq!(Some(3), minimal_inputs(&figure, &data, &table, &play_table));
+    }
+
+    #[test]
+    fn test_minimal_inputs_to_wall() {
+        let play_table: GameField = [TetrominoType::E; WIDTH * HEIGHT];
+        let data = initialize_tetraminoes_data();
+        let table = RotationCollisionTable::new();
+
+        // Holding left then dropping, however many columns the wall is away.
+        let mut figure = Tetramino::new(TetrominoType::O as usize);
+        let spawn = *figure.get_position();
+        let wall = generate_moves(&figure, &data, &table, &play_table)
+            .into_iter()
+            .map(|placement| placement.position)
+            .min_by_key(|position| position.x)
+            .unwrap();
+        assert!(spawn.x - wall.x > 2);
+        figure.set_position(wall);
+        let minimal = minimal_inputs(&figure, &data, &table, &play_table);
+        assert_eq!(Some(2), minimal);
+
+        let mut finesse = Finesse::new();
+        finesse.record_input();
+        finesse.record_input();
+        finesse.record_piece(figure.get_type(), minimal);
+        assert_eq!(0, finesse.total_errors());
+
+        // Tapping all the way there is one error per extra tap.
+        for _ in 0..spawn.x - wall.x + 1 {
+            finesse.record_input();
+        }
+        finesse.record_piece(figure.get_type(), minimal);
+        assert_eq!((spawn.x - wall.x - 1) as u32, finesse.total_errors());
+    }
+
+    #[test]
+    fn test_finesse_percentage() {
+        let mut finesse = Finesse::new();
+        finesse.record_input();
+        finesse.record_piece(TetrominoType::O as usize, Some(1));
+        for _ in 0..4 {
+            finesse.record_input();
+        }
+        finesse.record_piece(TetrominoType::T as usize, Some(2));
+
+        assert_eq!(2, finesse.total_errors());
+        assert_eq!(2, finesse.errors_by_piece()[TetrominoType::T as usize]);
+        assert_eq!(50.0, finesse.percentage());
+    }
+}
@@ -1,6 +1,16 @@
 use crate::tetramino::Point;
 use crate::tetramino::*;
+use crate::finesse::Finesse;
+use crate::statistics::GameStatistics;
+use crate::settings::Settings;
+use crate::audio::*;
+use crate::events::GameEvent;
+use crate::hud::{Hud, Notice};
+use crate::timestep::Interpolation;
+use crate::puzzle::*;
 use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::collections::VecDeque;
 use std::error;
 
 pub const WIDTH: usize = 10;
@@ -8,6 +18,45 @@ pub const HEIGHT: usize = 20;
 
 pub type GameField = [TetrominoType; WIDTH * HEIGHT];
 
+/// Seeded piece generator. The seed and the number of draws are enough to
+/// put it back into the same state when a saved game is resumed.
+pub struct Randomizer {
+    seed: u64,
+    draws: u64,
+    rng: StdRng,
+}
+
+impl Randomizer {
+    pub fn new() -> Randomizer {
+        Randomizer::restore(rand::random(), 0)
+    }
+
+    pub fn restore(seed: u64, draws: u64) -> Randomizer {
+        let mut randomizer = Randomizer {
+            seed,
+            draws: 0,
+            rng: StdRng::seed_from_u64(seed),
+        };
+        for _ in 0..draws {
+            randomizer.next_index();
+        }
+        randomizer
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn draws(&self) -> u64 {
+        self.draws
+    }
+
+    pub fn next_index(&mut self) -> usize {
+        self.draws += 1;
+        self.rng.gen_range(0, TETRAMINOS_COUNT)
+    }
+}
+
 pub struct GameData {
     pub running: bool,
     pub score: u32,
@@ -19,29 +68,56 @@ pub struct GameData {
     pub highest_level: usize,
     pub lines : usize,
     pub dificulty : u32,
+    pub spawn_count : u32,
+    pub finesse : Finesse,
+    pub total_lines : usize,
+    pub queue : VecDeque<usize>,
+    pub puzzle : Option<PuzzleProgress>,
+    pub hold : Option<usize>,
+    pub randomizer : Randomizer,
+    pub save_requested : bool,
+    pub statistics : GameStatistics,
+    pub settings : Settings,
+    pub audio : AudioPlayer,
+    pub events : Vec<GameEvent>,
+    pub hud : Hud,
+    pub notice : Notice,
+    pub interpolation : Interpolation,
 }
 
 impl GameData {
-    pub fn new() -> Result<GameData, Box<dyn error::Error>> {
+    pub fn new(settings : Settings) -> Result<GameData, Box<dyn error::Error>> {
         let play_table = [TetrominoType::E; WIDTH * HEIGHT];
+        let mut randomizer = Randomizer::new();
         Ok(GameData {
             running: true,
             score: 0,
-            next_figure: GameData::random_tetramino_index(),
-            current_figure: Tetramino::new(GameData::random_tetramino_index()),
+            next_figure: randomizer.next_index(),
+            current_figure: Tetramino::new(randomizer.next_index()),
             tetraminoes_data: initialize_tetraminoes_data(),
             play_table,
             collision_table: RotationCollisionTable::new(),
             highest_level: 0,
             lines : 0,
             dificulty : 0,
+            spawn_count : 0,
+            finesse : Finesse::new(),
+            total_lines : 0,
+            queue : VecDeque::new(),
+            puzzle : None,
+            hold : None,
+            randomizer,
+            save_requested : false,
+            statistics : GameStatistics::new(),
+            settings,
+            audio : AudioPlayer::silent(),
+            events : vec![],
+            hud : Hud::new(),
+            notice : Notice::default(),
+            interpolation : Interpolation::default(),
         })
     }
 
-    pub fn tetramino_preview_sequence(&self) -> &TetrominoSequence {
-        &self.tetraminoes_data[self.next_figure].rotations[0].sequence
-    }
-
     pub fn tetramino_preview_offset(&self) -> &Point {
         &self.tetraminoes_data[self.next_figure].offset
     }
@@ -52,10 +128,72 @@ impl GameData {
         .offset
     }
 
-    pub fn random_tetramino_index() -> usize {
-        let mut rng = rand::thread_rng();
-        let value: usize = rng.gen_range(0, TETRAMINOS_COUNT);
-        value
+    pub fn spawn_next_figure(&mut self) {
+        // A finished puzzle has nothing more to hand out, the locked piece
+        // stays where it is until the result shows.
+        if let Some(puzzle) = &mut self.puzzle {
+            puzzle.evaluate(&self.play_table, self.total_lines);
+            if puzzle.result.is_some() {
+                return;
+            }
+        }
+
+        // Once a puzzle has handed out its list, the held piece is the only
+        // one left to play.
+        let held = match &mut self.puzzle {
+            Some(puzzle) if puzzle.queued == 0 => self.hold.take(),
+            Some(puzzle) => {
+                puzzle.queued -= 1;
+                None
+            }
+            None => None,
+        };
+        match held {
+            Some(held) => self.current_figure = Tetramino::new(held),
+            None => {
+                self.current_figure = Tetramino::new(self.next_figure);
+                self.next_figure = self.queued_tetramino_index();
+            }
+        }
+        self.spawn_count += 1;
+    }
+
+    pub fn start_puzzle(&mut self, puzzle: &Puzzle) {
+        self.play_table = puzzle.play_table;
+        self.queue = puzzle.pieces.iter().cloned().collect();
+        self.current_figure = Tetramino::new(self.queued_tetramino_index());
+        self.next_figure = self.queued_tetramino_index();
+        self.hold = puzzle.hold;
+        self.puzzle = puzzle
+            .goal
+            .map(|goal| PuzzleProgress::new(&puzzle.name, goal, puzzle.pieces.len(), puzzle.hold.is_some()));
+    }
+
+    /// Holding into an empty hold needs a piece to take the current one's place.
+    pub fn can_hold(&self) -> bool {
+        self.hold.is_some() || self.has_next_figure()
+    }
+
+    pub fn hold_figure(&mut self) {
+        let current = self.current_figure.get_type();
+        match self.hold.replace(current) {
+            Some(held) => {
+                self.current_figure = Tetramino::new(held);
+                self.spawn_count += 1;
+            }
+            None => self.spawn_next_figure(),
+        }
+    }
+
+    pub fn has_next_figure(&self) -> bool {
+        self.puzzle.as_ref().is_none_or(|puzzle| puzzle.queued > 0)
+    }
+
+    pub fn queued_tetramino_index(&mut self) -> usize {
+        match self.queue.pop_front() {
+            Some(index) => index,
+            None => self.randomizer.next_index(),
+        }
     }
 
     pub fn score_multiplier(&self) -> u32 {
@@ -66,8 +204,20 @@ impl GameData {
     }
 
     pub fn add_score(&mut self, score : u32) {
+        let dificulty = self.dificulty;
         self.score += score;
         self.dificulty = ((self.score as f64 / 500 as f64).ln()).trunc() as u32;
+        if self.dificulty > dificulty {
+            self.emit(GameEvent::LevelUp { level : self.dificulty });
+        }
+    }
+
+    pub fn emit(&mut self, event : GameEvent) {
+        self.events.push(event);
+    }
+
+    pub fn play_sound(&mut self, sound : Sound) {
+        self.audio.play(sound, &self.settings.audio);
     }
 
     pub fn speed_multiplier(&self) -> f64 {
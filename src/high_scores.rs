@@ -0,0 +1,247 @@
+use crate::storage::{data_directory, KeyValueFile};
+use std::error;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HIGH_SCORES_FILE: &str = "highscores.txt";
+const FIELD_SEPARATOR: char = '|';
+pub const MAX_ENTRIES: usize = 10;
+pub const MAX_NAME_LENGTH: usize = 12;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GameMode {
+    Marathon,
+    /// Puzzles and editor test plays, which start from any board.
+    Puzzle,
+    Demo,
+}
+
+pub const RANKED_MODES: [GameMode; 1] = [GameMode::Marathon];
+
+impl GameMode {
+    pub fn key(self) -> &'static str {
+        match self {
+            GameMode::Marathon => "marathon",
+            GameMode::Puzzle => "puzzle",
+            GameMode::Demo => "demo",
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            GameMode::Marathon => "Marathon",
+            GameMode::Puzzle => "Puzzle",
+            GameMode::Demo => "Demo",
+        }
+    }
+
+    pub fn ranked(self) -> bool {
+        RANKED_MODES.contains(&self)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    pub lines: usize,
+    pub level: u32,
+    pub date: String,
+}
+
+impl HighScore {
+    fn parse(text: &str) -> Result<HighScore, Box<dyn error::Error>> {
+        let fields: Vec<&str> = text.split(FIELD_SEPARATOR).collect();
+        if fields.len() != 5 {
+            return Err(format!("invalid high score `{}`", text).into());
+        }
+
+        Ok(HighScore {
+            name: fields[0].to_string(),
+            score: fields[1].parse()?,
+            lines: fields[2].parse()?,
+            level: fields[3].parse()?,
+            date: fields[4].to_string(),
+        })
+    }
+
+    fn to_text(&self) -> String {
+        format!(
+            "{}{sep}{}{sep}{}{sep}{}{sep}{}",
+            self.name,
+            self.score,
+            self.lines,
+            self.level,
+            self.date,
+            sep = FIELD_SEPARATOR
+        )
+    }
+}
+
+/// Top scores of every ranked mode, best first.
+#[derive(Default)]
+pub struct HighScores {
+    tables: Vec<(GameMode, Vec<HighScore>)>,
+}
+
+impl HighScores {
+    pub fn path() -> Result<PathBuf, Box<dyn error::Error>> {
+        Ok(data_directory()?.join(HIGH_SCORES_FILE))
+    }
+
+    /// Loads the saved table, an empty one if nothing was saved yet.
+    pub fn load() -> Result<HighScores, Box<dyn error::Error>> {
+        let path = HighScores::path()?;
+        if !path.exists() {
+            return Ok(HighScores::default());
+        }
+        HighScores::parse(&KeyValueFile::load(&path)?)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn error::Error>> {
+        self.to_file().save(&HighScores::path()?)
+    }
+
+    pub fn parse(file: &KeyValueFile) -> Result<HighScores, Box<dyn error::Error>> {
+        let mut high_scores = HighScores::default();
+        for mode in RANKED_MODES.iter() {
+            for entry in file.get_all(mode.key()) {
+                high_scores.insert(*mode, HighScore::parse(entry)?);
+            }
+        }
+        Ok(high_scores)
+    }
+
+    pub fn to_file(&self) -> KeyValueFile {
+        let mut file = KeyValueFile::new();
+        for (mode, table) in &self.tables {
+            for entry in table {
+                file.push(mode.key(), &entry.to_text());
+            }
+        }
+        file
+    }
+
+    pub fn table(&self, mode: GameMode) -> &[HighScore] {
+        self.tables
+            .iter()
+            .find(|(table_mode, _)| *table_mode == mode)
+            .map_or(&[], |(_, table)| table.as_slice())
+    }
+
+    pub fn qualifies(&self, mode: GameMode, score: u32) -> bool {
+        if !mode.ranked() || score == 0 {
+            return false;
+        }
+
+        let table = self.table(mode);
+        table.len() < MAX_ENTRIES || table.last().is_some_and(|last| score > last.score)
+    }
+
+    /// Adds the entry and returns its zero based rank, `None` if it did not
+    /// make it into the table.
+    pub fn insert(&mut self, mode: GameMode, entry: HighScore) -> Option<usize> {
+        let table = match self.tables.iter_mut().position(|(table_mode, _)| *table_mode == mode) {
+            Some(index) => &mut self.tables[index].1,
+            None => {
+                self.tables.push((mode, vec![]));
+                &mut self.tables.last_mut().unwrap().1
+            }
+        };
+
+        let rank = table
+            .iter()
+            .position(|other| entry.score > other.score)
+            .unwrap_or(table.len());
+        if rank >= MAX_ENTRIES {
+            return None;
+        }
+
+        table.insert(rank, entry);
+        table.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+}
+
+/// Names may only hold characters that keep the file format intact.
+pub fn valid_name_character(character: char) -> bool {
+    character.is_alphanumeric() || character == ' ' || character == '-' || character == '_'
+}
+
+pub fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (year, month, day) = civil_date((seconds / 86400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Days since 1970-01-01 to a proleptic Gregorian date.
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = (if month_index < 10 { month_index + 3 } else { month_index - 9 }) as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: u32) -> HighScore {
+        HighScore {
+            name: name.to_string(),
+            score,
+            lines: 4,
+            level: 1,
+            date: String::from("2020-01-01"),
+        }
+    }
+
+    #[test]
+    fn test_high_scores_insert() {
+        let mut high_scores = HighScores::default();
+        for score in 1..=MAX_ENTRIES as u32 {
+            high_scores.insert(GameMode::Marathon, entry("a", score * 100));
+        }
+
+        assert_eq!(MAX_ENTRIES, high_scores.table(GameMode::Marathon).len());
+        assert!(!high_scores.qualifies(GameMode::Marathon, 100));
+        assert!(high_scores.qualifies(GameMode::Marathon, 150));
+        assert!(!high_scores.qualifies(GameMode::Puzzle, 5000));
+        assert!(!high_scores.qualifies(GameMode::Demo, 5000));
+
+        assert_eq!(Some(1), high_scores.insert(GameMode::Marathon, entry("b", 950)));
+        assert_eq!(None, high_scores.insert(GameMode::Marathon, entry("c", 50)));
+        let table = high_scores.table(GameMode::Marathon);
+        assert_eq!(MAX_ENTRIES, table.len());
+        assert_eq!("b", table[1].name);
+        assert_eq!(200, table.last().unwrap().score);
+    }
+
+    #[test]
+    fn test_high_scores_round_trip() {
+        let mut high_scores = HighScores::default();
+        high_scores.insert(GameMode::Marathon, entry("Ann", 300));
+        high_scores.insert(GameMode::Marathon, entry("Bob", 200));
+        high_scores.insert(GameMode::Puzzle, entry("Cid", 100));
+
+        let text = high_scores.to_file().to_string();
+        let parsed = HighScores::parse(&KeyValueFile::parse(&text).unwrap()).unwrap();
+        assert_eq!(high_scores.table(GameMode::Marathon), parsed.table(GameMode::Marathon));
+        assert!(parsed.table(GameMode::Puzzle).is_empty());
+    }
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!((1970, 1, 1), civil_date(0));
+        assert_eq!((2000, 2, 29), civil_date(11016));
+        assert_eq!((2024, 12, 31), civil_date(20088));
+    }
+}
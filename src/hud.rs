@@ -0,0 +1,120 @@
+use crate::events::*;
+use crate::game_data::GameData;
+
+const MESSAGE_DURATION: f64 = 1.5;
+const NOTICE_DURATION: f64 = 6.0;
+const CLEAR_NAMES: [&str; 4] = ["Single", "Double", "Triple", "Tetris"];
+
+/// Short messages shown over the play field after notable moves.
+#[derive(Default)]
+pub struct Hud {
+    message: Option<String>,
+    time_left: f64,
+}
+
+impl Hud {
+    pub fn new() -> Hud {
+        Hud::default()
+    }
+
+    pub fn show(&mut self, message: String) {
+        self.message = Some(message);
+        self.time_left = MESSAGE_DURATION;
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        self.time_left -= dt;
+        if self.time_left <= 0.0 {
+            self.message = None;
+        }
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+/// A problem the game got past, such as a theme or a sound that would not
+/// load. Shown over every screen for a while instead of being printed, which
+/// would garble the terminal frontend.
+#[derive(Default)]
+pub struct Notice {
+    message: Option<String>,
+    time_left: f64,
+}
+
+impl Notice {
+    pub fn show(&mut self, message: String) {
+        self.message = Some(message);
+        self.time_left = NOTICE_DURATION;
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        self.time_left -= dt;
+        if self.time_left <= 0.0 {
+            self.message = None;
+        }
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+pub struct HudListener;
+
+impl EventListener for HudListener {
+    fn on_event(&mut self, event: &GameEvent, data: &mut GameData) {
+        match event {
+            GameEvent::PieceLocked { tspin: true, lines, .. } => {
+                let name = match lines {
+                    0 => "",
+                    lines => CLEAR_NAMES[(lines - 1).min(CLEAR_NAMES.len() - 1)],
+                };
+                data.hud.show(format!("T-spin {}", name).trim_end().to_string());
+            }
+            GameEvent::LinesCleared { chain: 0, lines } if *lines == CLEAR_NAMES.len() => {
+                data.hud.show(String::from("Tetris!"));
+            }
+            GameEvent::LinesCleared { chain, .. } if *chain > 0 => {
+                data.hud.show(format!("Chain x{}", chain + 1));
+            }
+            GameEvent::LevelUp { level } => data.hud.show(format!("Level {}", level)),
+            GameEvent::Tick { dt } => data.hud.update(*dt),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+
+    #[test]
+    fn test_hud_messages() {
+        let mut data = GameData::new(Settings::default()).unwrap();
+        let mut listener = HudListener;
+        listener.on_event(&GameEvent::PieceLocked { piece: 0, tspin: true, lines: 2 }, &mut data);
+        assert_eq!(Some("T-spin Double"), data.hud.message());
+        listener.on_event(&GameEvent::LinesCleared { lines: 1, chain: 2 }, &mut data);
+        assert_eq!(Some("Chain x3"), data.hud.message());
+        listener.on_event(&GameEvent::LinesCleared { lines: 1, chain: 0 }, &mut data);
+        assert_eq!(Some("Chain x3"), data.hud.message());
+
+        data.hud.update(1.0);
+        assert!(data.hud.message().is_some());
+        data.hud.update(1.0);
+        assert_eq!(None, data.hud.message());
+    }
+
+    #[test]
+    fn test_notice() {
+        let mut notice = Notice::default();
+        notice.show(String::from("audio disabled"));
+        notice.update(NOTICE_DURATION / 2.0);
+        assert_eq!(Some("audio disabled"), notice.message());
+        notice.update(NOTICE_DURATION);
+        assert_eq!(None, notice.message());
+    }
+}
@@ -0,0 +1,471 @@
+use crate::settings::Settings;
+use piston_window::*;
+use std::collections::HashMap;
+
+pub const MAX_KEYS_PER_ACTION: usize = 3;
+
+/// What a key means to the game, independent of the key itself.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateClockwise,
+    RotateCounterClockwise,
+    Rotate180,
+    Hold,
+    Pause,
+    MenuUp,
+    MenuDown,
+    MenuLeft,
+    MenuRight,
+    Confirm,
+    Back,
+}
+
+/// Where an action is used. An input may be bound once in each, so the same
+/// key can rotate pieces in play and move up in menus.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InputContext {
+    Gameplay,
+    Menu,
+}
+
+pub const ACTIONS: [Action; 15] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::SoftDrop,
+    Action::HardDrop,
+    Action::RotateClockwise,
+    Action::RotateCounterClockwise,
+    Action::Rotate180,
+    Action::Hold,
+    Action::Pause,
+    Action::MenuUp,
+    Action::MenuDown,
+    Action::MenuLeft,
+    Action::MenuRight,
+    Action::Confirm,
+    Action::Back,
+];
+
+impl Action {
+    /// Name used in the settings file.
+    pub fn key(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::SoftDrop => "soft_drop",
+            Action::HardDrop => "hard_drop",
+            Action::RotateClockwise => "rotate_clockwise",
+            Action::RotateCounterClockwise => "rotate_counter_clockwise",
+            Action::Rotate180 => "rotate_180",
+            Action::Hold => "hold",
+            Action::Pause => "pause",
+            Action::MenuUp => "menu_up",
+            Action::MenuDown => "menu_down",
+            Action::MenuLeft => "menu_left",
+            Action::MenuRight => "menu_right",
+            Action::Confirm => "confirm",
+            Action::Back => "back",
+        }
+    }
+
+    pub fn context(self) -> InputContext {
+        match self {
+            Action::MoveLeft
+            | Action::MoveRight
+            | Action::SoftDrop
+            | Action::HardDrop
+            | Action::RotateClockwise
+            | Action::RotateCounterClockwise
+            | Action::Rotate180
+            | Action::Hold
+            | Action::Pause => InputContext::Gameplay,
+            Action::MenuUp
+            | Action::MenuDown
+            | Action::MenuLeft
+            | Action::MenuRight
+            | Action::Confirm
+            | Action::Back => InputContext::Menu,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::SoftDrop => "Soft drop",
+            Action::HardDrop => "Hard drop",
+            Action::RotateClockwise => "Rotate CW",
+            Action::RotateCounterClockwise => "Rotate CCW",
+            Action::Rotate180 => "Rotate 180",
+            Action::Hold => "Hold",
+            Action::Pause => "Pause",
+            Action::MenuUp => "Menu up",
+            Action::MenuDown => "Menu down",
+            Action::MenuLeft => "Menu left",
+            Action::MenuRight => "Menu right",
+            Action::Confirm => "Confirm",
+            Action::Back => "Back",
+        }
+    }
+
+    fn default_keys(self) -> Vec<Key> {
+        match self {
+            Action::MoveLeft => vec![Key::Left],
+            Action::MoveRight => vec![Key::Right],
+            Action::SoftDrop => vec![Key::Down],
+            Action::HardDrop => vec![Key::Space],
+            Action::RotateClockwise => vec![Key::Up, Key::X],
+            Action::RotateCounterClockwise => vec![Key::Z, Key::LCtrl],
+            Action::Rotate180 => vec![Key::A],
+            Action::Hold => vec![Key::C, Key::LShift],
+            Action::Pause => vec![Key::Escape, Key::F1],
+            Action::MenuUp => vec![Key::Up],
+            Action::MenuDown => vec![Key::Down],
+            Action::MenuLeft => vec![Key::Left],
+            Action::MenuRight => vec![Key::Right],
+            Action::Confirm => vec![Key::Return],
+            Action::Back => vec![Key::Escape],
+        }
+    }
+
+    // Button numbers follow the usual SDL joystick layout of an Xbox pad.
+    fn default_pad_inputs(self) -> Vec<PadInput> {
+        match self {
+            Action::MoveLeft => vec![PadInput::HatLeft, PadInput::AxisNegative(0)],
+            Action::MoveRight => vec![PadInput::HatRight, PadInput::AxisPositive(0)],
+            Action::SoftDrop => vec![PadInput::HatDown, PadInput::AxisPositive(1)],
+            Action::HardDrop => vec![PadInput::HatUp],
+            Action::RotateClockwise => vec![PadInput::Button(1)],
+            Action::RotateCounterClockwise => vec![PadInput::Button(0)],
+            Action::Rotate180 => vec![PadInput::Button(3)],
+            Action::Hold => vec![PadInput::Button(4), PadInput::Button(5)],
+            Action::Pause => vec![PadInput::Button(7)],
+            Action::MenuUp => vec![PadInput::HatUp, PadInput::AxisNegative(1)],
+            Action::MenuDown => vec![PadInput::HatDown, PadInput::AxisPositive(1)],
+            Action::MenuLeft => vec![PadInput::HatLeft, PadInput::AxisNegative(0)],
+            Action::MenuRight => vec![PadInput::HatRight, PadInput::AxisPositive(0)],
+            Action::Confirm => vec![PadInput::Button(0)],
+            Action::Back => vec![PadInput::Button(1), PadInput::Button(6)],
+        }
+    }
+}
+
+/// A bindable controller input. Analog axes count as two buttons, one for
+/// each direction past the deadzone.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PadInput {
+    Button(u8),
+    HatUp,
+    HatDown,
+    HatLeft,
+    HatRight,
+    AxisNegative(u8),
+    AxisPositive(u8),
+}
+
+impl PadInput {
+    /// Name used in the settings file.
+    pub fn name(self) -> String {
+        match self {
+            PadInput::Button(button) => format!("button{}", button),
+            PadInput::HatUp => String::from("hat_up"),
+            PadInput::HatDown => String::from("hat_down"),
+            PadInput::HatLeft => String::from("hat_left"),
+            PadInput::HatRight => String::from("hat_right"),
+            PadInput::AxisNegative(axis) => format!("axis{}-", axis),
+            PadInput::AxisPositive(axis) => format!("axis{}+", axis),
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<PadInput> {
+        match name {
+            "hat_up" => return Some(PadInput::HatUp),
+            "hat_down" => return Some(PadInput::HatDown),
+            "hat_left" => return Some(PadInput::HatLeft),
+            "hat_right" => return Some(PadInput::HatRight),
+            _ => {}
+        }
+
+        if let Some(button) = name.strip_prefix("button") {
+            return button.parse().ok().map(PadInput::Button);
+        }
+
+        let axis = name.strip_prefix("axis")?;
+        if let Some(axis) = axis.strip_suffix('-') {
+            axis.parse().ok().map(PadInput::AxisNegative)
+        } else if let Some(axis) = axis.strip_suffix('+') {
+            axis.parse().ok().map(PadInput::AxisPositive)
+        } else {
+            None
+        }
+    }
+}
+
+fn hat_inputs(state: HatState) -> Vec<PadInput> {
+    match state {
+        HatState::Centered => vec![],
+        HatState::Up => vec![PadInput::HatUp],
+        HatState::Down => vec![PadInput::HatDown],
+        HatState::Left => vec![PadInput::HatLeft],
+        HatState::Right => vec![PadInput::HatRight],
+        HatState::LeftUp => vec![PadInput::HatLeft, PadInput::HatUp],
+        HatState::LeftDown => vec![PadInput::HatLeft, PadInput::HatDown],
+        HatState::RightUp => vec![PadInput::HatRight, PadInput::HatUp],
+        HatState::RightDown => vec![PadInput::HatRight, PadInput::HatDown],
+    }
+}
+
+fn axis_input(axis: u8, position: f64, deadzone: f64) -> Option<PadInput> {
+    if position <= -deadzone {
+        Some(PadInput::AxisNegative(axis))
+    } else if position >= deadzone {
+        Some(PadInput::AxisPositive(axis))
+    } else {
+        None
+    }
+}
+
+/// Inputs bound to every action, keys by default. An input triggers at most
+/// one action of each `InputContext`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Bindings<T = Key> {
+    inputs: Vec<(Action, Vec<T>)>,
+}
+
+impl Default for Bindings<Key> {
+    fn default() -> Bindings<Key> {
+        Bindings {
+            inputs: ACTIONS.iter().map(|action| (*action, action.default_keys())).collect(),
+        }
+    }
+}
+
+impl Default for Bindings<PadInput> {
+    fn default() -> Bindings<PadInput> {
+        Bindings {
+            inputs: ACTIONS.iter().map(|action| (*action, action.default_pad_inputs())).collect(),
+        }
+    }
+}
+
+impl<T: Copy + PartialEq> Bindings<T> {
+    pub fn actions(&self, input: T) -> Vec<Action> {
+        self.inputs
+            .iter()
+            .filter(|(_, inputs)| inputs.contains(&input))
+            .map(|(action, _)| *action)
+            .collect()
+    }
+
+    pub fn inputs(&self, action: Action) -> &[T] {
+        self.inputs
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .map_or(&[], |(_, inputs)| inputs.as_slice())
+    }
+
+    pub fn set_inputs(&mut self, action: Action, inputs: Vec<T>) {
+        match self.inputs.iter_mut().find(|(bound, _)| *bound == action) {
+            Some((_, bound_inputs)) => *bound_inputs = inputs,
+            None => self.inputs.push((action, inputs)),
+        }
+    }
+
+    /// Adds an input to the action, dropping the oldest one past the limit.
+    /// Other actions of the same context lose the input.
+    pub fn bind(&mut self, action: Action, input: T) {
+        let mut inputs = self.inputs(action).to_vec();
+        if inputs.contains(&input) {
+            return;
+        }
+
+        for (other, bound) in self.inputs.iter_mut() {
+            if other.context() == action.context() {
+                bound.retain(|bound| *bound != input);
+            }
+        }
+
+        inputs.push(input);
+        if inputs.len() > MAX_KEYS_PER_ACTION {
+            inputs.remove(0);
+        }
+        self.set_inputs(action, inputs);
+    }
+
+    pub fn unbind_last(&mut self, action: Action) {
+        let mut inputs = self.inputs(action).to_vec();
+        inputs.pop();
+        self.set_inputs(action, inputs);
+    }
+}
+
+/// Turns raw input into actions. Controllers report hats and axes as
+/// positions rather than presses, so the last position of each is kept to
+/// find out which bound directions were entered or left.
+#[derive(Default)]
+pub struct InputMapper {
+    hats: HashMap<(i32, u8), HatState>,
+    axes: HashMap<(i32, u8), Option<PadInput>>,
+}
+
+impl InputMapper {
+    pub fn new() -> InputMapper {
+        InputMapper::default()
+    }
+
+    /// The actions of `context` the input pressed or released.
+    pub fn actions(&mut self, input: &Input, settings: &Settings, context: InputContext) -> Vec<(Action, ButtonState)> {
+        let mut actions = self.all_actions(input, settings);
+        actions.retain(|(action, _)| action.context() == context);
+        actions
+    }
+
+    // Hats and axes are tracked whatever the context, a direction entered
+    // in play may be left in a menu.
+    fn all_actions(&mut self, input: &Input, settings: &Settings) -> Vec<(Action, ButtonState)> {
+        let gamepad = &settings.gamepad;
+        match input {
+            Input::Button(ButtonArgs { state, button, .. }) => match button {
+                Button::Keyboard(key) => with_state(settings.bindings.actions(*key), *state),
+                Button::Controller(controller) => {
+                    let bindings = gamepad.bindings(controller.id);
+                    with_state(bindings.actions(PadInput::Button(controller.button)), *state)
+                }
+                Button::Hat(hat) => {
+                    let current = if *state == ButtonState::Press { hat.state } else { HatState::Centered };
+                    let previous = self.hats.insert((hat.id, hat.which), current).unwrap_or(HatState::Centered);
+                    changes(gamepad.bindings(hat.id), &hat_inputs(previous), &hat_inputs(current))
+                }
+                _ => vec![],
+            },
+
+            Input::Move(Motion::ControllerAxis(args)) => {
+                let current = axis_input(args.axis, args.position, gamepad.deadzone);
+                let previous = self.axes.insert((args.id, args.axis), current).flatten();
+                let previous: Vec<PadInput> = previous.into_iter().collect();
+                let current: Vec<PadInput> = current.into_iter().collect();
+                changes(gamepad.bindings(args.id), &previous, &current)
+            }
+            _ => vec![],
+        }
+    }
+}
+
+fn with_state(actions: Vec<Action>, state: ButtonState) -> Vec<(Action, ButtonState)> {
+    actions.into_iter().map(|action| (action, state)).collect()
+}
+
+fn changes(bindings: &Bindings<PadInput>, previous: &[PadInput], current: &[PadInput]) -> Vec<(Action, ButtonState)> {
+    let released = previous.iter().filter(|input| !current.contains(input));
+    let pressed = current.iter().filter(|input| !previous.contains(input));
+    released
+        .flat_map(|input| with_state(bindings.actions(*input), ButtonState::Release))
+        .chain(pressed.flat_map(|input| with_state(bindings.actions(*input), ButtonState::Press)))
+        .collect()
+}
+
+/// The controller input a menu waiting for a binding should take, if any.
+pub fn captured_pad_input(input: &Input, deadzone: f64) -> Option<(i32, PadInput)> {
+    match input {
+        Input::Button(ButtonArgs {
+            state: ButtonState::Press,
+            button,
+            ..
+        }) => match button {
+            Button::Controller(controller) => Some((controller.id, PadInput::Button(controller.button))),
+            Button::Hat(hat) => hat_inputs(hat.state).first().map(|input| (hat.id, *input)),
+            _ => None,
+        },
+        Input::Move(Motion::ControllerAxis(args)) => {
+            axis_input(args.axis, args.position, deadzone).map(|input| (args.id, input))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bindings() {
+        let mut bindings = Bindings::default();
+        assert_eq!(vec![Action::SoftDrop, Action::MenuDown], bindings.actions(Key::Down));
+        assert!(bindings.actions(Key::Q).is_empty());
+
+        bindings.bind(Action::HardDrop, Key::W);
+        bindings.bind(Action::HardDrop, Key::W);
+        assert_eq!(&[Key::Space, Key::W], bindings.inputs(Action::HardDrop));
+
+        bindings.bind(Action::HardDrop, Key::E);
+        bindings.bind(Action::HardDrop, Key::R);
+        assert_eq!(&[Key::W, Key::E, Key::R], bindings.inputs(Action::HardDrop));
+        assert!(bindings.actions(Key::Space).is_empty());
+
+        bindings.unbind_last(Action::HardDrop);
+        assert_eq!(&[Key::W, Key::E], bindings.inputs(Action::HardDrop));
+
+        // Binding takes the key from gameplay actions, menus keep it.
+        bindings.bind(Action::Hold, Key::Up);
+        assert_eq!(vec![Action::Hold, Action::MenuUp], bindings.actions(Key::Up));
+    }
+
+    #[test]
+    fn test_default_bindings_per_context() {
+        let keys = Bindings::<Key>::default();
+        let pad = Bindings::<PadInput>::default();
+        for context in [InputContext::Gameplay, InputContext::Menu] {
+            let in_context = |actions: Vec<Action>| actions.iter().filter(|action| action.context() == context).count();
+            for action in ACTIONS.iter().filter(|action| action.context() == context) {
+                for key in keys.inputs(*action) {
+                    assert_eq!(1, in_context(keys.actions(*key)), "{:?}", key);
+                }
+                for input in pad.inputs(*action) {
+                    assert_eq!(1, in_context(pad.actions(*input)), "{:?}", input);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_pad_input_names() {
+        for input in [PadInput::Button(12), PadInput::HatLeft, PadInput::AxisNegative(0), PadInput::AxisPositive(3)].iter() {
+            assert_eq!(Some(*input), PadInput::parse(&input.name()));
+        }
+        assert_eq!(None, PadInput::parse("axis1"));
+        assert_eq!(None, PadInput::parse("buttonX"));
+    }
+
+    #[test]
+    fn test_mapper_axis_and_hat() {
+        let settings = Settings::default();
+        let mut mapper = InputMapper::new();
+        let axis = |position| Input::Move(Motion::ControllerAxis(ControllerAxisArgs::new(0, 0, position)));
+
+        let play = InputContext::Gameplay;
+        assert!(mapper.actions(&axis(-0.1), &settings, play).is_empty());
+        assert_eq!(vec![(Action::MoveLeft, ButtonState::Press)], mapper.actions(&axis(-0.9), &settings, play));
+        assert!(mapper.actions(&axis(-0.8), &settings, play).is_empty());
+        assert_eq!(
+            vec![(Action::MenuLeft, ButtonState::Release), (Action::MenuRight, ButtonState::Press)],
+            mapper.actions(&axis(0.9), &settings, InputContext::Menu)
+        );
+
+        let hat = |state| {
+            Input::Button(ButtonArgs {
+                state: ButtonState::Press,
+                button: Button::Hat(ControllerHat::new(0, 0, state)),
+                scancode: None,
+            })
+        };
+        mapper.actions(&hat(HatState::Down), &settings, play);
+        let actions = mapper.actions(&hat(HatState::LeftDown), &settings, play);
+        assert_eq!(vec![(Action::MoveLeft, ButtonState::Press)], actions);
+        let actions = mapper.actions(&hat(HatState::Centered), &settings, play);
+        assert_eq!(2, actions.iter().filter(|(_, state)| *state == ButtonState::Release).count());
+    }
+}
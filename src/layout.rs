@@ -0,0 +1,137 @@
+use crate::abstraction::abstraction_layer::{Rect, RenderContext, Transform};
+use crate::game_data::{HEIGHT, WIDTH};
+use crate::settings::{DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_WIDTH};
+use crate::tetramino::Point;
+
+// Everything was drawn for 64 pixel cells, positions and text sizes given in
+// those pixels are scaled by `cell / REFERENCE_CELL`.
+pub const REFERENCE_CELL: f64 = 64.0;
+// The side panel is as wide as this many cells.
+const PANEL_COLUMNS: f64 = 6.0;
+const MIN_CELL: f64 = 4.0;
+const MIN_TEXT_SIZE: u32 = 8;
+
+/// Where the board and the side panel go in a window of a given size. The
+/// two are scaled together to fit and centered in the window.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Layout {
+    pub window: Rect,
+    pub cell: f64,
+    pub board: Rect,
+    pub panel: Rect,
+}
+
+impl Layout {
+    pub fn new(width: f64, height: f64) -> Layout {
+        let columns = WIDTH as f64 + PANEL_COLUMNS;
+        let rows = HEIGHT as f64;
+        // Whole pixels keep the blocks from drifting apart.
+        let cell = (width / columns).min(height / rows).floor().max(MIN_CELL);
+        let x = ((width - columns * cell) / 2.0).floor().max(0.0);
+        let y = ((height - rows * cell) / 2.0).floor().max(0.0);
+
+        let board = Rect::new(x, y, WIDTH as f64 * cell, rows * cell);
+        Layout {
+            window: Rect::new(0.0, 0.0, width, height),
+            cell,
+            board,
+            panel: Rect::new(board.x + board.width, y, PANEL_COLUMNS * cell, board.height),
+        }
+    }
+
+    pub fn of(ctx: &dyn RenderContext) -> Layout {
+        let [width, height] = ctx.size();
+        Layout::new(width, height)
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.cell / REFERENCE_CELL
+    }
+
+    pub fn text_size(&self, size: u32) -> u32 {
+        ((size as f64 * self.scale()).round() as u32).max(MIN_TEXT_SIZE)
+    }
+
+    /// Top left corner of a board cell. Rows above the board are allowed.
+    pub fn cell_position(&self, x: i32, y: i32) -> Transform {
+        self.cell_point(x as f64, y as f64)
+    }
+
+    /// Like `cell_position` for pieces drawn part way between cells.
+    pub fn cell_point(&self, x: f64, y: f64) -> Transform {
+        Transform::new(self.board.x + x * self.cell, self.board.y + y * self.cell)
+    }
+
+    pub fn cell_at(&self, x: f64, y: f64) -> Option<Point> {
+        let column = ((x - self.board.x) / self.cell).floor();
+        let row = ((y - self.board.y) / self.cell).floor();
+        if column < 0.0 || row < 0.0 || column >= WIDTH as f64 || row >= HEIGHT as f64 {
+            return None;
+        }
+        Some(Point { x: column as i32, y: row as i32 })
+    }
+
+    /// A point given in reference pixels from the board's top left corner.
+    pub fn board_point(&self, x: f64, y: f64) -> Transform {
+        Transform::new(self.board.x + x * self.scale(), self.board.y + y * self.scale())
+    }
+
+    /// A point given in reference pixels from the top left corner of the
+    /// default window, where menus are laid out. Board and panel fill that
+    /// window exactly, so it is scaled and centered along with them.
+    pub fn screen_point(&self, x: f64, y: f64) -> Transform {
+        self.board_point(x, y)
+    }
+
+    /// A point given in reference pixels from the panel's top left corner.
+    pub fn panel_point(&self, x: f64, y: f64) -> Transform {
+        Transform::new(self.panel.x + x * self.scale(), self.panel.y + y * self.scale())
+    }
+
+    /// Backgrounds are made for the default window. They are scaled to cover
+    /// the whole window and centered, cutting off what does not fit.
+    pub fn background(&self) -> (Transform, f64) {
+        let scale = (self.window.width / DEFAULT_WINDOW_WIDTH as f64).max(self.window.height / DEFAULT_WINDOW_HEIGHT as f64);
+        let x = (self.window.width - DEFAULT_WINDOW_WIDTH as f64 * scale) / 2.0;
+        let y = (self.window.height - DEFAULT_WINDOW_HEIGHT as f64 * scale) / 2.0;
+        (Transform::new(x, y), scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_window() {
+        let layout = Layout::new(DEFAULT_WINDOW_WIDTH as f64, DEFAULT_WINDOW_HEIGHT as f64);
+        assert_eq!(REFERENCE_CELL, layout.cell);
+        assert_eq!(Rect::new(0.0, 0.0, 640.0, 1280.0), layout.board);
+        assert_eq!(Rect::new(640.0, 0.0, 384.0, 1280.0), layout.panel);
+        assert_eq!(Transform::new(650.0, 32.0), layout.panel_point(10.0, 32.0));
+        assert_eq!((Transform::new(0.0, 0.0), 1.0), layout.background());
+    }
+
+    #[test]
+    fn test_wide_window() {
+        let layout = Layout::new(1920.0, 1080.0);
+        assert_eq!(54.0, layout.cell);
+        assert_eq!(Rect::new(528.0, 0.0, 540.0, 1080.0), layout.board);
+        assert_eq!(1068.0, layout.panel.x);
+        assert_eq!(27, layout.text_size(32));
+        assert_eq!(Transform::new(528.0 + 54.0, 108.0), layout.screen_point(64.0, 128.0));
+        assert_eq!(Transform::new(528.0 + 54.0, 54.0 * 2.0), layout.cell_position(1, 2));
+        assert_eq!(Some(Point { x: 1, y: 2 }), layout.cell_at(528.0 + 60.0, 110.0));
+        assert_eq!(None, layout.cell_at(500.0, 110.0));
+        assert_eq!(None, layout.cell_at(1070.0, 110.0));
+    }
+
+    #[test]
+    fn test_narrow_window() {
+        let layout = Layout::new(400.0, 1280.0);
+        assert_eq!(25.0, layout.cell);
+        assert_eq!(0.0, layout.board.x);
+        assert_eq!(390.0, layout.board.y);
+        assert_eq!(MIN_TEXT_SIZE, layout.text_size(16));
+    }
+}
@@ -5,8 +5,25 @@ mod tetris;
 
 pub use crate::tetris::Tetris;
 mod resources;
-mod abstraction;
+mod assets;
+pub mod abstraction;
 mod states;
+mod ai;
+mod finesse;
+mod storage;
+mod puzzle;
+mod save_game;
+mod high_scores;
+mod statistics;
+mod settings;
+mod input;
+pub mod audio;
+mod events;
+mod hud;
+mod theme;
+mod layout;
+mod widgets;
+mod timestep;
 
 pub use crate::resources::Resources;
 extern crate rand;
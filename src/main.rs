@@ -1,13 +1,29 @@
 use libtetrisgame::Tetris;
 
 fn main() {
-    let result = Tetris::new("");
+    let arguments: Vec<String> = std::env::args().collect();
+    let assets = match arguments.iter().position(|argument| argument == "--assets") {
+        Some(index) => match arguments.get(index + 1) {
+            Some(path) if !path.starts_with("--") => path.as_str(),
+            _ => {
+                eprintln!("--assets needs a directory");
+                std::process::exit(2);
+            }
+        },
+        None => "",
+    };
+
+    let result = if arguments.iter().any(|argument| argument == "--terminal") {
+        Tetris::terminal(assets)
+    } else {
+        Tetris::new(assets)
+    };
     match result {
         Ok(mut game) => {
             game.run();
         },
         Err(error) => {
-            println!("{}", error);
+            eprintln!("{}", error);
         }
     }
 
@@ -0,0 +1,380 @@
+use crate::assets::asset_directory;
+use crate::storage::{data_directory, KeyValueFile};
+use crate::game_data::*;
+use crate::tetramino::*;
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const PUZZLE_DIRECTORY: &str = "puzzles";
+const PUZZLE_EXTENSION: &str = "puzzle";
+const EMPTY_CELL: char = '.';
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Goal {
+    Lines(usize),
+    PerfectClear,
+    TSpinDouble,
+}
+
+impl Goal {
+    pub fn parse(text: &str) -> Result<Goal, Box<dyn error::Error>> {
+        let mut words = text.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("lines"), Some(count)) => Ok(Goal::Lines(count.parse()?)),
+            (Some("perfect_clear"), None) => Ok(Goal::PerfectClear),
+            (Some("tspin_double"), None) => Ok(Goal::TSpinDouble),
+            _ => Err(format!("unknown goal `{}`", text).into()),
+        }
+    }
+
+    pub fn to_text(self) -> String {
+        match self {
+            Goal::Lines(count) => format!("lines {}", count),
+            Goal::PerfectClear => String::from("perfect_clear"),
+            Goal::TSpinDouble => String::from("tspin_double"),
+        }
+    }
+
+    pub fn description(self) -> String {
+        match self {
+            Goal::Lines(count) => format!("Clear {} lines", count),
+            Goal::PerfectClear => String::from("Perfect clear"),
+            Goal::TSpinDouble => String::from("T-spin double"),
+        }
+    }
+}
+
+/// A starting position. Without a goal the position is played like a normal
+/// game, continuing with random figures once the listed pieces run out.
+#[derive(Clone)]
+pub struct Puzzle {
+    pub name: String,
+    pub goal: Option<Goal>,
+    pub pieces: Vec<usize>,
+    pub hold: Option<usize>,
+    pub play_table: GameField,
+}
+
+impl Puzzle {
+    pub fn parse(text: &str) -> Result<Puzzle, Box<dyn error::Error>> {
+        let file = KeyValueFile::parse(text)?;
+        let name = file.get("name").ok_or("missing `name`")?.to_string();
+        let goal = match file.get("goal") {
+            Some(goal) => Some(Goal::parse(goal)?),
+            None => None,
+        };
+
+        let pieces = file
+            .get("pieces")
+            .unwrap_or("")
+            .split_whitespace()
+            .map(parse_piece)
+            .collect::<Result<Vec<usize>, Box<dyn error::Error>>>()?;
+        if goal.is_some() && pieces.is_empty() {
+            return Err("a puzzle with a goal needs `pieces`".into());
+        }
+
+        let hold = match file.get("hold") {
+            Some(hold) => Some(parse_piece(hold)?),
+            None => None,
+        };
+
+        let rows = file.get_all("row");
+        if rows.len() > HEIGHT {
+            return Err(format!("more than {} rows", HEIGHT).into());
+        }
+
+        // Rows are listed top to bottom and aligned to the floor of the field.
+        let mut play_table = [TetrominoType::E; WIDTH * HEIGHT];
+        let first_row = HEIGHT - rows.len();
+        for (row, text) in rows.iter().enumerate() {
+            let cells: Vec<char> = text.chars().collect();
+            if cells.len() != WIDTH {
+                return Err(format!("row `{}` is not {} cells wide", text, WIDTH).into());
+            }
+
+            for (column, cell) in cells.iter().enumerate() {
+                play_table[(first_row + row) * WIDTH + column] = parse_cell(*cell)?;
+            }
+        }
+
+        Ok(Puzzle {
+            name,
+            goal,
+            pieces,
+            hold,
+            play_table,
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut file = KeyValueFile::new();
+        file.push("name", &self.name);
+        if let Some(goal) = &self.goal {
+            file.push("goal", &goal.to_text());
+        }
+        if !self.pieces.is_empty() {
+            let pieces: Vec<&str> = self.pieces.iter().map(|piece| TETRAMINO_NAMES[*piece]).collect();
+            file.push("pieces", &pieces.join(" "));
+        }
+        if let Some(hold) = self.hold {
+            file.push("hold", TETRAMINO_NAMES[hold]);
+        }
+
+        let first_row = (0..HEIGHT)
+            .find(|row| {
+                self.play_table[row * WIDTH..(row + 1) * WIDTH]
+                    .iter()
+                    .any(|cell| !matches!(cell, TetrominoType::E))
+            })
+            .unwrap_or(HEIGHT);
+        for row in first_row..HEIGHT {
+            let cells: String = self.play_table[row * WIDTH..(row + 1) * WIDTH]
+                .iter()
+                .map(|cell| cell_symbol(*cell))
+                .collect();
+            file.push("row", &cells);
+        }
+
+        file.to_string()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn error::Error>> {
+        KeyValueFile::parse(&self.to_text())?.save(path)
+    }
+
+    pub fn load(path: &Path) -> Result<Puzzle, Box<dyn error::Error>> {
+        let file = KeyValueFile::load(path)?;
+        Puzzle::parse(&file.to_string()).map_err(|error| format!("{}: {}", path.display(), error).into())
+    }
+}
+
+/// Where puzzles made in the editor are kept, the assets may be read-only.
+pub fn user_puzzle_directory() -> Result<PathBuf, Box<dyn error::Error>> {
+    Ok(data_directory()?.join(PUZZLE_DIRECTORY))
+}
+
+/// The puzzles that come with the game followed by the user's own.
+pub fn all_puzzles() -> Result<Vec<Puzzle>, Box<dyn error::Error>> {
+    let mut puzzles = load_puzzles(&asset_directory()?.join(PUZZLE_DIRECTORY))?;
+    let user_directory = user_puzzle_directory()?;
+    if user_directory.is_dir() {
+        puzzles.extend(load_puzzles(&user_directory)?);
+    }
+    Ok(puzzles)
+}
+
+pub fn load_puzzles(directory: &Path) -> Result<Vec<Puzzle>, Box<dyn error::Error>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == PUZZLE_EXTENSION) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    paths.iter().map(|path| Puzzle::load(path)).collect()
+}
+
+pub fn parse_piece(text: &str) -> Result<usize, Box<dyn error::Error>> {
+    TETRAMINO_NAMES
+        .iter()
+        .position(|name| *name == text)
+        .ok_or_else(|| format!("unknown piece `{}`", text).into())
+}
+
+pub fn cell_symbol(cell: TetrominoType) -> char {
+    match cell {
+        TetrominoType::E => EMPTY_CELL,
+        _ => TETRAMINO_NAMES[cell as usize].chars().next().unwrap(),
+    }
+}
+
+pub fn parse_cell(cell: char) -> Result<TetrominoType, Box<dyn error::Error>> {
+    match cell {
+        EMPTY_CELL => Ok(TetrominoType::E),
+        'I' => Ok(TetrominoType::I),
+        'O' | 'X' => Ok(TetrominoType::O),
+        'T' => Ok(TetrominoType::T),
+        'S' => Ok(TetrominoType::S),
+        'Z' => Ok(TetrominoType::Z),
+        'J' => Ok(TetrominoType::J),
+        'L' => Ok(TetrominoType::L),
+        _ => Err(format!("unknown cell `{}`", cell).into()),
+    }
+}
+
+pub struct PuzzleProgress {
+    pub name: String,
+    pub goal: Goal,
+    /// Pieces still to be locked, wherever they are: falling, held or queued.
+    pub pieces_left: usize,
+    /// Listed pieces that have not spawned yet, the next one included.
+    pub queued: usize,
+    pub tspin_doubles: u32,
+    pub result: Option<bool>,
+}
+
+impl PuzzleProgress {
+    /// A preset hold piece is played like the listed ones.
+    pub fn new(name: &str, goal: Goal, pieces: usize, hold: bool) -> PuzzleProgress {
+        PuzzleProgress {
+            name: name.to_string(),
+            goal,
+            pieces_left: pieces + hold as usize,
+            queued: pieces.saturating_sub(1),
+            tspin_doubles: 0,
+            result: None,
+        }
+    }
+
+    pub fn record_lock(&mut self, tspin: bool, lines: usize) {
+        self.pieces_left = self.pieces_left.saturating_sub(1);
+        if tspin && lines == 2 {
+            self.tspin_doubles += 1;
+        }
+    }
+
+    /// Called once the field has settled after a lock.
+    pub fn evaluate(&mut self, play_table: &GameField, total_lines: usize) {
+        let complete = match self.goal {
+            Goal::Lines(count) => total_lines >= count,
+            Goal::PerfectClear => play_table.iter().all(|cell| matches!(cell, TetrominoType::E)),
+            Goal::TSpinDouble => self.tspin_doubles > 0,
+        };
+
+        if complete {
+            self.result = Some(true);
+        } else if self.pieces_left == 0 {
+            self.result = Some(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+    use crate::ai::move_generator::generate_moves;
+    use crate::states::play::{fill_field, find_filled_lines, is_tspin};
+
+    const TSPIN_DOUBLE: &str = "
+name = T-spin double
+goal = tspin_double
+pieces = T
+row = XX........
+row = X...XXXXXX
+row = XX.XXXXXXX
+";
+
+    #[test]
+    fn test_puzzle_parse() {
+        let puzzle = Puzzle::parse(TSPIN_DOUBLE).unwrap();
+        assert_eq!("T-spin double", puzzle.name);
+        assert_eq!(Some(Goal::TSpinDouble), puzzle.goal);
+        assert_eq!(vec![TetrominoType::T as usize], puzzle.pieces);
+        assert!(matches!(puzzle.play_table[(HEIGHT - 3) * WIDTH + 1], TetrominoType::O));
+        assert!(matches!(puzzle.play_table[(HEIGHT - 1) * WIDTH + 2], TetrominoType::E));
+
+        assert!(Puzzle::parse("name = a\ngoal = lines 2\npieces = Q").is_err());
+        assert!(Puzzle::parse("name = a\ngoal = lines 2\npieces = T\nrow = ...").is_err());
+        assert!(Puzzle::parse("name = a\ngoal = lines 2").is_err());
+        assert!(Puzzle::parse("name = a").unwrap().goal.is_none());
+    }
+
+    #[test]
+    fn test_puzzle_round_trip() {
+        let mut puzzle = Puzzle::parse(TSPIN_DOUBLE).unwrap();
+        puzzle.hold = Some(TetrominoType::I as usize);
+        puzzle.play_table[(HEIGHT - 1) * WIDTH] = TetrominoType::S;
+
+        let parsed = Puzzle::parse(&puzzle.to_text()).unwrap();
+        assert_eq!(puzzle.name, parsed.name);
+        assert_eq!(puzzle.goal, parsed.goal);
+        assert_eq!(puzzle.pieces, parsed.pieces);
+        assert_eq!(puzzle.hold, parsed.hold);
+        assert!(matches!(parsed.play_table[(HEIGHT - 1) * WIDTH], TetrominoType::S));
+        assert!(matches!(parsed.play_table[(HEIGHT - 1) * WIDTH + 2], TetrominoType::E));
+    }
+
+    #[test]
+    fn test_tspin_double_reachable() {
+        let puzzle = Puzzle::parse(TSPIN_DOUBLE).unwrap();
+        let data = initialize_tetraminoes_data();
+        let table = RotationCollisionTable::new();
+        let figure = Tetramino::new(TetrominoType::T as usize);
+
+        let solved = generate_moves(&figure, &data, &table, &puzzle.play_table)
+            .iter()
+            .any(|placement| {
+                let mut placed = Tetramino::new(TetrominoType::T as usize);
+                placed.set_position(placement.position);
+                placed.set_rotation(placement.rotation);
+                let tspin = is_tspin(&placed, &puzzle.play_table);
+
+                let mut field = puzzle.play_table;
+                let rotation = &data[placed.get_type()].rotations[placed.get_rotation()];
+                fill_field(&placement.position, rotation, &mut field, TetrominoType::T);
+                tspin && find_filled_lines(&field).len() == 2
+            });
+        assert!(solved);
+    }
+
+    #[test]
+    fn test_puzzle_progress() {
+        let puzzle = Puzzle::parse("name = a\ngoal = lines 2\npieces = I O").unwrap();
+        let mut progress = PuzzleProgress::new(&puzzle.name, Goal::Lines(2), puzzle.pieces.len(), false);
+        progress.record_lock(false, 1);
+        progress.evaluate(&puzzle.play_table, 1);
+        assert_eq!(None, progress.result);
+        progress.record_lock(false, 0);
+        progress.evaluate(&puzzle.play_table, 1);
+        assert_eq!(Some(false), progress.result);
+    }
+
+    #[test]
+    fn test_puzzle_hold() {
+        let puzzle = Puzzle::parse("name = a\ngoal = lines 4\npieces = I O").unwrap();
+        let mut data = GameData::new(Settings::default()).unwrap();
+        data.start_puzzle(&puzzle);
+        assert!(data.has_next_figure());
+
+        // Holding the first piece brings the last one forward.
+        data.hold_figure();
+        assert_eq!(TetrominoType::O as usize, data.current_figure.get_type());
+        assert!(!data.has_next_figure());
+        assert!(data.can_hold());
+
+        // After it locks, the held piece comes out instead of a random one.
+        data.puzzle.as_mut().unwrap().record_lock(false, 0);
+        data.spawn_next_figure();
+        assert_eq!(TetrominoType::I as usize, data.current_figure.get_type());
+        assert_eq!(None, data.hold);
+        assert_eq!(1, data.puzzle.as_ref().unwrap().pieces_left);
+        assert!(!data.can_hold());
+    }
+
+    #[test]
+    fn test_puzzle_preset_hold() {
+        let mut puzzle = Puzzle::parse("name = a\ngoal = lines 4\npieces = I").unwrap();
+        puzzle.hold = Some(TetrominoType::O as usize);
+        let mut data = GameData::new(Settings::default()).unwrap();
+        data.start_puzzle(&puzzle);
+
+        // The swapped in hold piece does not use up the listed one.
+        data.hold_figure();
+        assert_eq!(TetrominoType::O as usize, data.current_figure.get_type());
+        data.puzzle.as_mut().unwrap().record_lock(false, 0);
+        data.spawn_next_figure();
+        assert_eq!(None, data.puzzle.as_ref().unwrap().result);
+        assert_eq!(TetrominoType::I as usize, data.current_figure.get_type());
+
+        // With nothing left it fails instead of spawning a random piece.
+        data.puzzle.as_mut().unwrap().record_lock(false, 0);
+        data.spawn_next_figure();
+        assert_eq!(Some(false), data.puzzle.as_ref().unwrap().result);
+        assert_eq!(TetrominoType::I as usize, data.current_figure.get_type());
+    }
+}
@@ -1,66 +1,56 @@
-extern crate find_folder;
-use piston_window::*;
 use std::error;
-use crate::tetris::Abstraction;
-use crate::abstraction::piston_abstraction::PistonAbstraction;
-use crate::abstraction::abstraction_layer::{TextureHandle, AbstractionLayer};
-
-// type R1 = Abstraction::Ctx;
-type R1 = <crate::abstraction::piston_abstraction::PistonAbstraction as crate::abstraction::abstraction_layer::AbstractionLayer>::Ctx;
-
-const ASSET_DIRECTORY : &str = "assets";
-const GAME_FONT : &str = "TetrisFont2.ttf";
-const PARENT_DEPTH : find_folder::ParentsDepth = 3;
-const KIDS_DEPTH : find_folder::KidsDepth = 3;
+use crate::abstraction::abstraction_layer::{FontHandle, ResourceLoader, TextureHandle};
+use crate::tetramino::{TetrominoType, TETRAMINOS_COUNT};
+use crate::theme::{Theme, ThemeColors};
+use crate::widgets::HudLayout;
 
+#[derive(Clone)]
 pub struct Resources {
-    pub background : G2dTexture,
-    pub empty_block : G2dTexture,
-    pub cube_block : G2dTexture,
-    pub font : Glyphs,
-    pub texture : TextureHandle<R1>
+    pub background : TextureHandle,
+    pub empty_block : TextureHandle,
+    pub blocks : [TextureHandle; TETRAMINOS_COUNT],
+    pub font : FontHandle,
+    pub colors : ThemeColors,
+    pub hud : HudLayout,
 }
 
 impl Resources {
-    pub fn new(_path : &str,  window : &mut PistonWindow, resource_loader : &mut Abstraction) -> Result<Resources, Box<dyn error::Error>> {
-
-        let assets = find_folder::Search::ParentsThenKids(PARENT_DEPTH, KIDS_DEPTH)
-            .for_folder(ASSET_DIRECTORY)?;
-
-        let _tex = window.create_texture_context();
-
-        let background = Texture::from_path(
-            &mut window.create_texture_context(),
-            assets.join(String::from("background2.png")),
-            Flip::None,
-            &TextureSettings::new()
-        )?;
-
-        let empty_block = Texture::from_path(
-            &mut window.create_texture_context(),
-            assets.join(String::from("empty_block2.png")),
-            Flip::None,
-            &TextureSettings::new()
-        )?;
-
-        let cube_block = Texture::from_path(
-            &mut window.create_texture_context(),
-            assets.join(String::from("cube_block2.png")),
-            Flip::None,
-            &TextureSettings::new()
-        )?;
-
-        let ref font = assets.join(GAME_FONT);
-        let font = window.load_font(font)?;
+    pub fn new(theme : &Theme, loader : &mut dyn ResourceLoader) -> Result<Resources, Box<dyn error::Error>> {
+        let mut blocks = [TextureHandle(0); TETRAMINOS_COUNT];
+        for (block, path) in blocks.iter_mut().zip(theme.blocks.iter()) {
+            *block = loader.load_texture(path)?;
+        }
 
         let result = Resources {
-            background : background,
-            empty_block : empty_block,
-            cube_block : cube_block,
-            font : font,
-            texture : resource_loader.load_texture("background2.png")
+            background : loader.load_texture(&theme.background)?,
+            empty_block : loader.load_texture(&theme.empty_block)?,
+            blocks,
+            font : loader.load_font(&theme.font)?,
+            colors : theme.colors,
+            hud : theme.hud.clone(),
         };
 
         Ok(result)
     }
+
+    /// Loads `theme` into the handles already in use, so nothing holding a
+    /// copy of these resources has to be told.
+    pub fn reload(&mut self, theme : &Theme, loader : &mut dyn ResourceLoader) -> Result<(), Box<dyn error::Error>> {
+        loader.reload_texture(self.background, &theme.background)?;
+        loader.reload_texture(self.empty_block, &theme.empty_block)?;
+        for (block, path) in self.blocks.iter().zip(theme.blocks.iter()) {
+            loader.reload_texture(*block, path)?;
+        }
+        loader.reload_font(self.font, &theme.font)?;
+        self.colors = theme.colors;
+        self.hud = theme.hud.clone();
+        Ok(())
+    }
+
+    pub fn block(&self, kind : TetrominoType) -> TextureHandle {
+        match kind {
+            TetrominoType::E => self.empty_block,
+            _ => self.blocks[kind as usize],
+        }
+    }
 }
@@ -0,0 +1,203 @@
+use crate::storage::{data_directory, KeyValueFile};
+use crate::finesse::FinesseRecord;
+use crate::statistics::GameStatistics;
+use crate::game_data::*;
+use crate::puzzle::{cell_symbol, parse_cell, parse_piece};
+use crate::tetramino::*;
+use std::error;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const SAVE_FILE: &str = "savegame.txt";
+const NO_PIECE: &str = "-";
+
+pub fn save_path() -> Result<PathBuf, Box<dyn error::Error>> {
+    Ok(data_directory()?.join(SAVE_FILE))
+}
+
+pub fn has_saved_game() -> bool {
+    save_path().is_ok_and(|path| path.exists())
+}
+
+pub fn delete_saved_game() -> Result<(), Box<dyn error::Error>> {
+    fs::remove_file(save_path()?)?;
+    Ok(())
+}
+
+/// Writes the session together with the saved lines of the play state stack.
+pub fn write_game(data: &GameData, states: &[String]) -> KeyValueFile {
+    let mut file = KeyValueFile::new();
+    file.push("seed", &data.randomizer.seed().to_string());
+    file.push("draws", &data.randomizer.draws().to_string());
+    file.push("score", &data.score.to_string());
+    file.push("dificulty", &data.dificulty.to_string());
+    file.push("lines", &data.lines.to_string());
+    file.push("total_lines", &data.total_lines.to_string());
+    file.push("highest_level", &data.highest_level.to_string());
+    file.push("spawn_count", &data.spawn_count.to_string());
+
+    let current = &data.current_figure;
+    file.push(
+        "current",
+        &format!(
+            "{} {} {} {}",
+            TETRAMINO_NAMES[current.get_type()],
+            current.get_position().x,
+            current.get_position().y,
+            current.get_rotation()
+        ),
+    );
+    file.push("next", TETRAMINO_NAMES[data.next_figure]);
+    file.push("hold", data.hold.map_or(NO_PIECE, |hold| TETRAMINO_NAMES[hold]));
+    let queue: Vec<&str> = data.queue.iter().map(|piece| TETRAMINO_NAMES[*piece]).collect();
+    file.push("queue", &queue.join(" "));
+
+    let records: Vec<String> = data
+        .finesse
+        .records
+        .iter()
+        .map(|record| format!("{}:{}:{}", record.piece, record.inputs, record.minimal))
+        .collect();
+    file.push("finesse", &records.join(" "));
+    file.push("finesse_inputs", &data.finesse.inputs.to_string());
+    data.statistics.write(&mut file);
+
+    for row in data.play_table.chunks(WIDTH) {
+        let cells: String = row.iter().map(|cell| cell_symbol(*cell)).collect();
+        file.push("row", &cells);
+    }
+
+    for state in states {
+        file.push("state", state);
+    }
+
+    file
+}
+
+/// Restores the session into `data` and returns the saved state lines.
+pub fn read_game(file: &KeyValueFile, data: &mut GameData) -> Result<Vec<String>, Box<dyn error::Error>> {
+    data.randomizer = Randomizer::restore(value(file, "seed")?, value(file, "draws")?);
+    data.score = value(file, "score")?;
+    data.dificulty = value(file, "dificulty")?;
+    data.lines = value(file, "lines")?;
+    data.total_lines = value(file, "total_lines")?;
+    data.highest_level = value(file, "highest_level")?;
+    data.spawn_count = value(file, "spawn_count")?;
+
+    let current: Vec<&str> = required(file, "current")?.split_whitespace().collect();
+    if current.len() != 4 {
+        return Err("`current` needs a piece, a position and a rotation".into());
+    }
+    let mut figure = Tetramino::new(parse_piece(current[0])?);
+    figure.set_position(Point {
+        x: current[1].parse()?,
+        y: current[2].parse()?,
+    });
+    figure.set_rotation(current[3].parse()?);
+    data.current_figure = figure;
+
+    data.next_figure = parse_piece(required(file, "next")?)?;
+    data.hold = match required(file, "hold")? {
+        NO_PIECE => None,
+        hold => Some(parse_piece(hold)?),
+    };
+    data.queue = file
+        .get("queue")
+        .unwrap_or("")
+        .split_whitespace()
+        .map(parse_piece)
+        .collect::<Result<_, _>>()?;
+
+    data.finesse = Default::default();
+    for record in file.get("finesse").unwrap_or("").split_whitespace() {
+        let fields = record
+            .split(':')
+            .map(|field| field.parse())
+            .collect::<Result<Vec<u32>, _>>()?;
+        if fields.len() != 3 || fields[0] as usize >= TETRAMINOS_COUNT {
+            return Err(format!("invalid finesse record `{}`", record).into());
+        }
+        data.finesse.records.push(FinesseRecord {
+            piece: fields[0] as usize,
+            inputs: fields[1],
+            minimal: fields[2],
+        });
+    }
+
+    data.finesse.inputs = match file.get("finesse_inputs") {
+        Some(_) => value(file, "finesse_inputs")?,
+        None => 0,
+    };
+
+    data.statistics = GameStatistics::read(file)?;
+
+    let rows = file.get_all("row");
+    if rows.len() != HEIGHT {
+        return Err(format!("expected {} rows", HEIGHT).into());
+    }
+    for (row, text) in rows.iter().enumerate() {
+        let cells: Vec<char> = text.chars().collect();
+        if cells.len() != WIDTH {
+            return Err(format!("row `{}` is not {} cells wide", text, WIDTH).into());
+        }
+        for (column, cell) in cells.iter().enumerate() {
+            data.play_table[row * WIDTH + column] = parse_cell(*cell)?;
+        }
+    }
+
+    data.puzzle = None;
+    Ok(file.get_all("state").iter().map(|state| state.to_string()).collect())
+}
+
+fn required<'a>(file: &'a KeyValueFile, key: &str) -> Result<&'a str, Box<dyn error::Error>> {
+    file.get(key).ok_or_else(|| format!("missing `{}`", key).into())
+}
+
+fn value<T: FromStr>(file: &KeyValueFile, key: &str) -> Result<T, Box<dyn error::Error>> {
+    required(file, key)?
+        .parse()
+        .map_err(|_| format!("invalid `{}`", key).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+
+    #[test]
+    fn test_game_round_trip() {
+        let mut data = GameData::new(Settings::default()).unwrap();
+        data.score = 1234;
+        data.hold = Some(TetrominoType::T as usize);
+        data.play_table[(HEIGHT - 1) * WIDTH + 3] = TetrominoType::L;
+        data.current_figure.set_position(Point { x: 4, y: 7 });
+        data.current_figure.rotate_right();
+        data.finesse.record_piece(TetrominoType::I as usize, Some(2));
+        data.finesse.record_input();
+        data.statistics.record_lock(TetrominoType::I as usize, false, 2);
+        for _ in 0..5 {
+            data.queued_tetramino_index();
+        }
+        data.queue.push_back(TetrominoType::S as usize);
+        let states = vec![String::from("falling 0.1 -"), String::from("fast_fall 0")];
+
+        let text = write_game(&data, &states).to_string();
+        let mut restored = GameData::new(Settings::default()).unwrap();
+        let restored_states = read_game(&KeyValueFile::parse(&text).unwrap(), &mut restored).unwrap();
+
+        assert_eq!(states, restored_states);
+        assert_eq!(1234, restored.score);
+        assert_eq!(data.hold, restored.hold);
+        assert_eq!(data.queue, restored.queue);
+        assert_eq!(data.current_figure.get_position(), restored.current_figure.get_position());
+        assert_eq!(1, restored.current_figure.get_rotation());
+        assert_eq!(1, restored.finesse.records.len());
+        assert_eq!(1, restored.finesse.inputs);
+        assert_eq!(data.statistics, restored.statistics);
+        assert!(matches!(restored.play_table[(HEIGHT - 1) * WIDTH + 3], TetrominoType::L));
+        for _ in 0..10 {
+            assert_eq!(data.randomizer.next_index(), restored.randomizer.next_index());
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use crate::storage::{config_directory, KeyValueFile};
+use crate::input::{Bindings, PadInput, ACTIONS};
+use crate::theme::DEFAULT_THEME;
+use piston_window::Key;
+use std::error;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const SETTINGS_FILE: &str = "settings.cfg";
+
+pub const DEFAULT_GRAVITY_INTERVAL: f64 = 0.33;
+pub const DEFAULT_CONTROL_INTERVAL: f64 = 0.1;
+pub const DEFAULT_WINDOW_WIDTH: u32 = 1024;
+pub const DEFAULT_WINDOW_HEIGHT: u32 = 1280;
+pub const MAX_VOLUME: u32 = 100;
+pub const DEFAULT_DEADZONE: f64 = 0.35;
+pub const DEFAULT_TRANSITION_TIME: f64 = 0.3;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Handling {
+    pub gravity_interval: f64,
+    pub control_interval: f64,
+}
+
+impl Default for Handling {
+    fn default() -> Handling {
+        Handling {
+            gravity_interval: DEFAULT_GRAVITY_INTERVAL,
+            control_interval: DEFAULT_CONTROL_INTERVAL,
+        }
+    }
+}
+
+/// Controllers without bindings of their own use the default ones.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Gamepad {
+    pub deadzone: f64,
+    pub default: Bindings<PadInput>,
+    pub controllers: Vec<(i32, Bindings<PadInput>)>,
+}
+
+impl Default for Gamepad {
+    fn default() -> Gamepad {
+        Gamepad {
+            deadzone: DEFAULT_DEADZONE,
+            default: Bindings::default(),
+            controllers: vec![],
+        }
+    }
+}
+
+impl Gamepad {
+    pub fn bindings(&self, controller: i32) -> &Bindings<PadInput> {
+        self.controllers
+            .iter()
+            .find(|(id, _)| *id == controller)
+            .map_or(&self.default, |(_, bindings)| bindings)
+    }
+
+    /// Gives the controller its own bindings, starting from the defaults.
+    pub fn bindings_mut(&mut self, controller: i32) -> &mut Bindings<PadInput> {
+        let index = match self.controllers.iter().position(|(id, _)| *id == controller) {
+            Some(index) => index,
+            None => {
+                self.controllers.push((controller, self.default.clone()));
+                self.controllers.len() - 1
+            }
+        };
+        &mut self.controllers[index].1
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Visuals {
+    pub window_width: u32,
+    pub window_height: u32,
+    pub theme: String,
+    /// Seconds a screen change is animated for, 0 switches at once.
+    pub transition_time: f64,
+}
+
+impl Default for Visuals {
+    fn default() -> Visuals {
+        Visuals {
+            window_width: DEFAULT_WINDOW_WIDTH,
+            window_height: DEFAULT_WINDOW_HEIGHT,
+            theme: DEFAULT_THEME.to_string(),
+            transition_time: DEFAULT_TRANSITION_TIME,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Audio {
+    pub master_volume: u32,
+    pub music_volume: u32,
+    pub effects_volume: u32,
+}
+
+impl Default for Audio {
+    fn default() -> Audio {
+        Audio {
+            master_volume: MAX_VOLUME,
+            music_volume: MAX_VOLUME / 2,
+            effects_volume: MAX_VOLUME,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Gameplay {
+    pub hold_enabled: bool,
+    pub preview_enabled: bool,
+}
+
+impl Default for Gameplay {
+    fn default() -> Gameplay {
+        Gameplay {
+            hold_enabled: true,
+            preview_enabled: true,
+        }
+    }
+}
+
+/// User configuration, stored as a `KeyValueFile` in the config directory.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Settings {
+    pub bindings: Bindings,
+    pub gamepad: Gamepad,
+    pub handling: Handling,
+    pub visuals: Visuals,
+    pub audio: Audio,
+    pub gameplay: Gameplay,
+}
+
+impl Settings {
+    pub fn path() -> Result<PathBuf, Box<dyn error::Error>> {
+        Ok(config_directory()?.join(SETTINGS_FILE))
+    }
+
+    /// Loads the saved settings, the defaults if nothing was saved yet.
+    pub fn load() -> Result<Settings, Box<dyn error::Error>> {
+        let path = Settings::path()?;
+        if !path.exists() {
+            return Ok(Settings::default());
+        }
+
+        let file = KeyValueFile::load(&path)?;
+        Settings::read(&file).map_err(|error| format!("{}: {}", path.display(), error).into())
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn error::Error>> {
+        self.write().save(&Settings::path()?)
+    }
+
+    /// Missing entries keep their default value.
+    pub fn read(file: &KeyValueFile) -> Result<Settings, Box<dyn error::Error>> {
+        let mut settings = Settings::default();
+
+        read_bindings(file, "controls", parse_key, &mut settings.bindings)?;
+
+        let gamepad = &mut settings.gamepad;
+        read_value(file, "gamepad.deadzone", &mut gamepad.deadzone)?;
+        read_bindings(file, "gamepad.default", PadInput::parse, &mut gamepad.default)?;
+        for id in file.get("gamepad.controllers").unwrap_or("").split_whitespace() {
+            let id: i32 = id.parse().map_err(|_| format!("invalid controller `{}`", id))?;
+            let mut bindings = gamepad.default.clone();
+            read_bindings(file, &format!("gamepad.controller{}", id), PadInput::parse, &mut bindings)?;
+            gamepad.controllers.push((id, bindings));
+        }
+
+        let handling = &mut settings.handling;
+        read_value(file, "handling.gravity_interval", &mut handling.gravity_interval)?;
+        read_value(file, "handling.control_interval", &mut handling.control_interval)?;
+
+        let visuals = &mut settings.visuals;
+        read_value(file, "visuals.window_width", &mut visuals.window_width)?;
+        read_value(file, "visuals.window_height", &mut visuals.window_height)?;
+        read_value(file, "visuals.theme", &mut visuals.theme)?;
+        read_value(file, "visuals.transition_time", &mut visuals.transition_time)?;
+
+        let audio = &mut settings.audio;
+        read_value(file, "audio.master_volume", &mut audio.master_volume)?;
+        read_value(file, "audio.music_volume", &mut audio.music_volume)?;
+        read_value(file, "audio.effects_volume", &mut audio.effects_volume)?;
+
+        let gameplay = &mut settings.gameplay;
+        read_value(file, "gameplay.hold_enabled", &mut gameplay.hold_enabled)?;
+        read_value(file, "gameplay.preview_enabled", &mut gameplay.preview_enabled)?;
+
+        Ok(settings)
+    }
+
+    pub fn write(&self) -> KeyValueFile {
+        let mut file = KeyValueFile::new();
+
+        write_bindings(&mut file, "controls", |key| key_name(*key), &self.bindings);
+
+        let gamepad = &self.gamepad;
+        file.push("gamepad.deadzone", &gamepad.deadzone.to_string());
+        write_bindings(&mut file, "gamepad.default", |input| input.name(), &gamepad.default);
+        let ids: Vec<String> = gamepad.controllers.iter().map(|(id, _)| id.to_string()).collect();
+        file.push("gamepad.controllers", &ids.join(" "));
+        for (id, bindings) in &gamepad.controllers {
+            write_bindings(&mut file, &format!("gamepad.controller{}", id), |input| input.name(), bindings);
+        }
+
+        let handling = &self.handling;
+        file.push("handling.gravity_interval", &handling.gravity_interval.to_string());
+        file.push("handling.control_interval", &handling.control_interval.to_string());
+
+        let visuals = &self.visuals;
+        file.push("visuals.window_width", &visuals.window_width.to_string());
+        file.push("visuals.window_height", &visuals.window_height.to_string());
+        file.push("visuals.theme", &visuals.theme);
+        file.push("visuals.transition_time", &visuals.transition_time.to_string());
+
+        let audio = &self.audio;
+        file.push("audio.master_volume", &audio.master_volume.to_string());
+        file.push("audio.music_volume", &audio.music_volume.to_string());
+        file.push("audio.effects_volume", &audio.effects_volume.to_string());
+
+        let gameplay = &self.gameplay;
+        file.push("gameplay.hold_enabled", &gameplay.hold_enabled.to_string());
+        file.push("gameplay.preview_enabled", &gameplay.preview_enabled.to_string());
+
+        file
+    }
+}
+
+fn read_value<T: FromStr>(file: &KeyValueFile, key: &str, value: &mut T) -> Result<(), Box<dyn error::Error>> {
+    if let Some(text) = file.get(key) {
+        *value = text.parse().map_err(|_| format!("invalid `{}`", key))?;
+    }
+    Ok(())
+}
+
+fn read_bindings<T: Copy + PartialEq>(
+    file: &KeyValueFile,
+    prefix: &str,
+    parse: fn(&str) -> Option<T>,
+    bindings: &mut Bindings<T>,
+) -> Result<(), Box<dyn error::Error>> {
+    for action in ACTIONS.iter() {
+        let key = format!("{}.{}", prefix, action.key());
+        if let Some(names) = file.get(&key) {
+            let inputs = names
+                .split_whitespace()
+                .map(|name| parse(name).ok_or_else(|| format!("unknown input `{}` for `{}`", name, key)))
+                .collect::<Result<Vec<T>, String>>()?;
+            bindings.set_inputs(*action, inputs);
+        }
+    }
+    Ok(())
+}
+
+fn write_bindings<T: Copy + PartialEq>(
+    file: &mut KeyValueFile,
+    prefix: &str,
+    name: fn(&T) -> String,
+    bindings: &Bindings<T>,
+) {
+    for action in ACTIONS.iter() {
+        let names: Vec<String> = bindings.inputs(*action).iter().map(name).collect();
+        file.push(&format!("{}.{}", prefix, action.key()), &names.join(" "));
+    }
+}
+
+pub fn key_name(key: Key) -> String {
+    format!("{:?}", key)
+}
+
+/// Looks a key up by the name `key_name` gives it.
+pub fn parse_key(name: &str) -> Option<Key> {
+    // Printable keys use their character code, the rest follow SDL scancodes.
+    (0x01..0x80)
+        .chain(0x4000_0039..0x4000_011B)
+        .map(Key::from)
+        .find(|key| *key != Key::Unknown && key_name(*key) == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Action;
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(Some(Key::Left), parse_key("Left"));
+        assert_eq!(Some(Key::Space), parse_key("Space"));
+        assert_eq!(Some(Key::D1), parse_key("D1"));
+        assert_eq!(Some(Key::F5), parse_key("F5"));
+        assert_eq!(None, parse_key("Nothing"));
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        let mut settings = Settings::default();
+        settings.bindings.bind(Action::HardDrop, Key::X);
+        settings.bindings.set_inputs(Action::Rotate180, vec![]);
+        settings.gamepad.bindings_mut(2).bind(Action::Hold, PadInput::Button(9));
+        settings.gamepad.deadzone = 0.5;
+        settings.handling.gravity_interval = 0.2;
+        settings.audio.music_volume = 10;
+        settings.gameplay.hold_enabled = false;
+
+        let text = settings.write().to_string();
+        assert!(text.contains("[controls]"));
+        let parsed = Settings::read(&KeyValueFile::parse(&text).unwrap()).unwrap();
+        assert_eq!(settings, parsed);
+        assert_eq!(&[PadInput::Button(4), PadInput::Button(5), PadInput::Button(9)], parsed.gamepad.bindings(2).inputs(Action::Hold));
+        assert_eq!(parsed.gamepad.default, parsed.gamepad.bindings(0).clone());
+
+        let partial = KeyValueFile::parse("[visuals]\nwindow_width = 800").unwrap();
+        let parsed = Settings::read(&partial).unwrap();
+        assert_eq!(800, parsed.visuals.window_width);
+        assert_eq!(DEFAULT_WINDOW_HEIGHT, parsed.visuals.window_height);
+        assert!(Settings::read(&KeyValueFile::parse("[controls]\nhard_drop = Nope").unwrap()).is_err());
+        assert!(Settings::read(&KeyValueFile::parse("[gamepad]\ndefault.hold = hat_middle").unwrap()).is_err());
+    }
+}
@@ -2,9 +2,12 @@ use crate::states::line_clearing::*;
 use crate::states::state_machine::*;
 use crate::states::play::*;
 use crate::game_data::*;
+use crate::events::GameEvent;
 use crate::tetramino::*;
 use crate::Resources;
+use crate::abstraction::abstraction_layer::*;
 use crate::chunk::*;
+use crate::layout::Layout;
 use piston_window::*;
 use std::error;
 
@@ -15,17 +18,81 @@ pub struct ChunkFall {
     iteration : i32,
     fall_time: f64,
     begin : usize,
+    chain : u32,
 }
 
 impl ChunkFall {
-    pub fn new(begin : usize) -> Result<Box<dyn State>, Box<dyn error::Error>> {
+    pub fn new(begin : usize, chain : u32) -> Result<Box<dyn State>, Box<dyn error::Error>> {
         Ok(Box::new(ChunkFall {
             chunks : vec![],
             iteration : 0,
             fall_time : 0.0,
             begin,
+            chain,
         }))
     }
+
+    pub fn restore(arguments: &[&str]) -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        let chunks = arguments
+            .iter()
+            .skip(3)
+            .map(|chunk| parse_chunk(chunk))
+            .collect::<Result<Vec<Chunk>, Box<dyn error::Error>>>()?;
+
+        Ok(Box::new(ChunkFall {
+            chunks,
+            iteration : state_argument(arguments, 1)?,
+            fall_time : state_argument(arguments, 2)?,
+            begin : state_argument(arguments, 0)?,
+            // The chain length only feeds the HUD, it is not worth saving.
+            chain : 0,
+        }))
+    }
+}
+
+// Chunks are saved as `x,y,kind;x,y,kind;...` with the cells at their original
+// position. Saves without the kind fall back to plain blocks.
+fn chunk_text(chunk : &Chunk) -> String {
+    let cells : Vec<String> = chunk
+        .elements
+        .iter()
+        .zip(&chunk.kinds)
+        .map(|(cell, kind)| format!("{},{},{}", cell.x, cell.y, kind_name(*kind)))
+        .collect();
+    cells.join(";")
+}
+
+fn kind_name(kind : TetrominoType) -> &'static str {
+    match kind {
+        TetrominoType::E => "E",
+        _ => TETRAMINO_NAMES[kind as usize],
+    }
+}
+
+fn parse_kind(name : Option<&&str>) -> Result<TetrominoType, Box<dyn error::Error>> {
+    let name = match name {
+        Some(name) => *name,
+        None => return Ok(TetrominoType::O),
+    };
+    TETRAMINO_NAMES
+        .iter()
+        .position(|candidate| *candidate == name)
+        .map(|index| TETRAMINOES[index])
+        .ok_or_else(|| format!("unknown block `{}`", name).into())
+}
+
+fn parse_chunk(text : &str) -> Result<Chunk, Box<dyn error::Error>> {
+    let mut elements = vec![];
+    let mut kinds = vec![];
+    for cell in text.split(';') {
+        let coordinates : Vec<&str> = cell.split(',').collect();
+        elements.push(Point {
+            x : state_argument(&coordinates, 0)?,
+            y : state_argument(&coordinates, 1)?,
+        });
+        kinds.push(parse_kind(coordinates.get(2))?);
+    }
+    Ok(Chunk::new(elements, kinds))
 }
 
 impl State for ChunkFall {
@@ -36,7 +103,9 @@ impl State for ChunkFall {
         _event: Event,
     ) -> StateTransition {
         if self.chunks.is_empty() {
-            return StateTransition::Transition(LineClearing::new().unwrap());
+            let chain = self.chain + 1;
+            data.emit(GameEvent::ChainStep { chain });
+            return StateTransition::Transition(LineClearing::chained(chain).unwrap());
         }
 
         self.fall_time += update_args.dt;
@@ -50,7 +119,7 @@ impl State for ChunkFall {
                 let position = Point{x : chunk.position.x, y : chunk.position.y + iteration + 1};
                 if check_for_collision(&position, chunk.into_iter(), play_table) {
                     let position = Point{x : chunk.position.x, y : chunk.position.y + iteration};
-                    fill_field(&position, chunk.into_iter(), play_table);
+                    fill_chunk(&position, chunk, play_table);
                     return false;
                 }
                 true
@@ -64,24 +133,18 @@ impl State for ChunkFall {
     fn handle_input(&mut self, _input: Input, _time: Option<TimeStamp>, _data: &mut GameData) {
     }
 
-    fn render(
-        &mut self,
-        c: Context,
-        g: &mut G2d,
-        _arguments: &RenderArgs,
-        _device: &mut gfx_device_gl::Device,
-        resources: &mut Resources,
-        _data: &GameData,
-    ) {
-        let full_block = &resources.cube_block;
+    fn overlay(&self) -> Option<Overlay> {
+        Some(Overlay::CLEAR)
+    }
+
+    fn render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, _data: &GameData) {
         let iteration = self.iteration;
+        let layout = Layout::of(ctx);
 
         self.chunks.iter().for_each(|chunk : &Chunk| {
             let position = Point{x : chunk.position.x, y : chunk.position.y + iteration};
-            chunk.elements.iter().for_each(|offset: &Point| {
-                let x = (position.x * BLOCK_SIZE as i32) as f64 + (offset.x * BLOCK_SIZE as i32) as f64;
-                let y = (position.y * BLOCK_SIZE as i32) as f64 + (offset.y * BLOCK_SIZE as i32) as f64;
-                image(full_block, c.transform.trans(x as f64, y as f64), g);
+            chunk.elements.iter().zip(&chunk.kinds).for_each(|(offset, kind)| {
+                draw_cell(ctx, &layout, resources.block(*kind), position.x + offset.x, position.y + offset.y);
             });
         });
     }
@@ -90,4 +153,13 @@ impl State for ChunkFall {
         let play_table = &mut data.play_table;
         self.chunks = find_chunks(play_table, HEIGHT - self.begin);
     }
+
+    fn save(&self) -> Option<String> {
+        let mut text = format!("chunk_fall {} {} {}", self.begin, self.iteration, self.fall_time);
+        for chunk in &self.chunks {
+            text.push(' ');
+            text.push_str(&chunk_text(chunk));
+        }
+        Some(text)
+    }
 }
@@ -0,0 +1,287 @@
+use crate::states::state_machine::*;
+use crate::states::main_menu::MainMenu;
+use crate::states::play::{draw_cell, PlayState};
+use crate::puzzle::*;
+use crate::game_data::*;
+use crate::input::Action;
+use crate::tetramino::*;
+use crate::Resources;
+use crate::abstraction::abstraction_layer::*;
+use crate::layout::Layout;
+use crate::settings::{DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_WIDTH};
+use piston_window::*;
+use std::error;
+use std::path::PathBuf;
+
+const EDITOR_FILE: &str = "editor.puzzle";
+const INFO_TEXT_SIZE: u32 = 32;
+const HELP_TEXT_SIZE: u32 = 20;
+// Reference pixels, the info from the panel's corner and the piece names
+// from their cell's.
+const INFO_POSITION_X: f64 = 10.0;
+const NAME_POSITION_X: f64 = 20.0;
+const NAME_POSITION_Y: f64 = 44.0;
+const CURSOR_WIDTH: f64 = 4.0;
+const BRUSH_POSITION_Y: f64 = 40.0;
+const QUEUE_POSITION_Y: f64 = 90.0;
+const HOLD_POSITION_Y: f64 = 140.0;
+const GOAL_POSITION_Y: f64 = 190.0;
+const STATUS_POSITION_Y: f64 = 240.0;
+const HELP_POSITION_Y: f64 = 320.0;
+const HELP_LINE_SPACING: f64 = 28.0;
+const HELP: [&str; 9] = [
+    "Arrows / mouse: move cursor",
+    "Space / left click: paint",
+    "Delete / right click: erase",
+    "1-7: brush I O T S Z J L",
+    "Q / W: push / pop queue",
+    "H: toggle hold, G: goal",
+    "C: clear board",
+    "S / L: save / load",
+    "Enter: play, Escape: menu",
+];
+const GOALS: [Option<Goal>; 6] = [
+    None,
+    Some(Goal::Lines(1)),
+    Some(Goal::Lines(2)),
+    Some(Goal::Lines(4)),
+    Some(Goal::PerfectClear),
+    Some(Goal::TSpinDouble),
+];
+
+enum EditorRequest {
+    None,
+    Play,
+    Back,
+}
+
+pub struct Editor {
+    puzzle: Puzzle,
+    cursor: Point,
+    brush: usize,
+    painting: Option<bool>,
+    status: String,
+    request: EditorRequest,
+    // From the last frame, to find the cell under the mouse.
+    layout: Layout,
+}
+
+impl Editor {
+    pub fn new() -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        Ok(Box::new(Editor {
+            puzzle: Puzzle {
+                name: String::from("Editor"),
+                goal: None,
+                pieces: vec![],
+                hold: None,
+                play_table: [TetrominoType::E; WIDTH * HEIGHT],
+            },
+            cursor: Point { x: 0, y: HEIGHT as i32 - 1 },
+            brush: TetrominoType::I as usize,
+            painting: None,
+            status: String::new(),
+            request: EditorRequest::None,
+            layout: Layout::new(DEFAULT_WINDOW_WIDTH as f64, DEFAULT_WINDOW_HEIGHT as f64),
+        }))
+    }
+
+    fn editor_file() -> Result<PathBuf, Box<dyn error::Error>> {
+        Ok(user_puzzle_directory()?.join(EDITOR_FILE))
+    }
+
+    fn set_cell(&mut self, paint: bool) {
+        let index = self.cursor.y as usize * WIDTH + self.cursor.x as usize;
+        self.puzzle.play_table[index] = if paint {
+            TETRAMINOES[self.brush]
+        } else {
+            TetrominoType::E
+        };
+    }
+
+    fn move_cursor(&mut self, x: i32, y: i32) {
+        self.cursor.x = (self.cursor.x + x).clamp(0, WIDTH as i32 - 1);
+        self.cursor.y = (self.cursor.y + y).clamp(0, HEIGHT as i32 - 1);
+    }
+
+    fn cycle_goal(&mut self) {
+        let current = GOALS.iter().position(|goal| *goal == self.puzzle.goal).unwrap_or(0);
+        self.puzzle.goal = GOALS[(current + 1) % GOALS.len()];
+    }
+
+    fn save(&mut self) {
+        if self.puzzle.goal.is_some() && self.puzzle.pieces.is_empty() {
+            self.status = String::from("A goal needs queued pieces");
+            return;
+        }
+
+        self.status = match Editor::editor_file().and_then(|path| self.puzzle.save(&path)) {
+            Ok(()) => String::from("Saved"),
+            Err(error) => error.to_string(),
+        };
+    }
+
+    fn load(&mut self) {
+        match Editor::editor_file().and_then(|path| Puzzle::load(&path)) {
+            Ok(puzzle) => {
+                self.puzzle = puzzle;
+                self.status = String::from("Loaded");
+            }
+            Err(error) => self.status = error.to_string(),
+        }
+    }
+
+    fn handle_key(&mut self, key: Key) {
+        match key {
+            Key::Space => self.set_cell(true),
+            Key::Delete | Key::Backspace => self.set_cell(false),
+            Key::D1 | Key::D2 | Key::D3 | Key::D4 | Key::D5 | Key::D6 | Key::D7 => {
+                self.brush = key as usize - Key::D1 as usize;
+            }
+            Key::Q => self.puzzle.pieces.push(self.brush),
+            Key::W => {
+                self.puzzle.pieces.pop();
+            }
+            Key::H => {
+                self.puzzle.hold = match self.puzzle.hold {
+                    Some(hold) if hold == self.brush => None,
+                    _ => Some(self.brush),
+                };
+            }
+            Key::G => self.cycle_goal(),
+            Key::C => self.puzzle.play_table = [TetrominoType::E; WIDTH * HEIGHT],
+            Key::S => self.save(),
+            Key::L => self.load(),
+            _ => {}
+        }
+    }
+
+    fn handle_menu_action(&mut self, action: Action) {
+        match action {
+            Action::MenuLeft => self.move_cursor(-1, 0),
+            Action::MenuRight => self.move_cursor(1, 0),
+            Action::MenuUp => self.move_cursor(0, -1),
+            Action::MenuDown => self.move_cursor(0, 1),
+            Action::Confirm => {
+                if self.puzzle.goal.is_some() && self.puzzle.pieces.is_empty() {
+                    self.status = String::from("A goal needs queued pieces");
+                } else {
+                    self.request = EditorRequest::Play;
+                }
+            }
+            Action::Back => self.request = EditorRequest::Back,
+            _ => {}
+        }
+    }
+}
+
+fn piece_names(pieces: &[usize]) -> String {
+    let names: Vec<&str> = pieces.iter().map(|piece| TETRAMINO_NAMES[*piece]).collect();
+    names.join(" ")
+}
+
+impl State for Editor {
+    fn update(
+        &mut self,
+        _data: &mut GameData,
+        _update_args: &UpdateArgs,
+        _event: Event,
+    ) -> StateTransition {
+        match std::mem::replace(&mut self.request, EditorRequest::None) {
+            EditorRequest::Play => {
+                StateTransition::Transition(PlayState::puzzle(self.puzzle.clone()).unwrap())
+            }
+            EditorRequest::Back => StateTransition::Transition(MainMenu::new().unwrap()),
+            EditorRequest::None => StateTransition::Hold,
+        }
+    }
+
+    fn handle_input(&mut self, input: Input, _time: Option<TimeStamp>, _data: &mut GameData) {
+        match input {
+            Input::Button(buttons) => match buttons.button {
+                Button::Keyboard(key) if buttons.state == ButtonState::Press => {
+                    self.handle_key(key);
+                }
+
+                Button::Mouse(button) => {
+                    if buttons.state == ButtonState::Release {
+                        self.painting = None;
+                        return;
+                    }
+
+                    self.painting = match button {
+                        MouseButton::Left => Some(true),
+                        MouseButton::Right => Some(false),
+                        _ => None,
+                    };
+                    if let Some(paint) = self.painting {
+                        self.set_cell(paint);
+                    }
+                }
+                _ => {}
+            },
+
+            Input::Move(Motion::MouseCursor([x, y])) => {
+                if let Some(cell) = self.layout.cell_at(x, y) {
+                    self.cursor = cell;
+                    if let Some(paint) = self.painting {
+                        self.set_cell(paint);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_action(&mut self, action: Action, state: ButtonState, _data: &mut GameData) {
+        if state == ButtonState::Press {
+            self.handle_menu_action(action);
+        }
+    }
+
+    fn render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, _data: &GameData) {
+        let font = resources.font;
+        let layout = Layout::of(ctx);
+        self.layout = layout;
+        let scale = layout.scale();
+        let info_size = layout.text_size(INFO_TEXT_SIZE);
+
+        render_background(ctx, resources);
+
+        for (index, cell) in self.puzzle.play_table.iter().enumerate() {
+            let (x, y) = ((index % WIDTH) as i32, (index / WIDTH) as i32);
+            draw_cell(ctx, &layout, resources.block(*cell), x, y);
+            if !matches!(cell, TetrominoType::E) {
+                let name = TETRAMINO_NAMES[*cell as usize];
+                let corner = layout.cell_position(x, y);
+                let transform = Transform::new(corner.x + NAME_POSITION_X * scale, corner.y + NAME_POSITION_Y * scale);
+                ctx.draw_text(name, font, info_size, &transform, &Color::BLACK);
+            }
+        }
+
+        let corner = layout.cell_position(self.cursor.x, self.cursor.y);
+        let cursor = Rect::new(corner.x, corner.y, layout.cell, layout.cell);
+        ctx.draw_rect_border(&cursor, &resources.colors.text, CURSOR_WIDTH * scale);
+
+        let goal = match &self.puzzle.goal {
+            Some(goal) => goal.description(),
+            None => String::from("Free play"),
+        };
+        let hold = self.puzzle.hold.map_or("-", |hold| TETRAMINO_NAMES[hold]);
+        let info = [
+            (format!("Brush : {}", TETRAMINO_NAMES[self.brush]), BRUSH_POSITION_Y),
+            (format!("Queue : {}", piece_names(&self.puzzle.pieces)), QUEUE_POSITION_Y),
+            (format!("Hold : {}", hold), HOLD_POSITION_Y),
+            (format!("Goal : {}", goal), GOAL_POSITION_Y),
+            (self.status.clone(), STATUS_POSITION_Y),
+        ];
+        for (line, y) in info.iter() {
+            ctx.draw_text(line, font, info_size, &layout.panel_point(INFO_POSITION_X, *y), &resources.colors.text);
+        }
+
+        for (index, line) in HELP.iter().enumerate() {
+            let y = HELP_POSITION_Y + index as f64 * HELP_LINE_SPACING;
+            let size = layout.text_size(HELP_TEXT_SIZE);
+            ctx.draw_text(line, font, size, &layout.panel_point(INFO_POSITION_X, y), &resources.colors.title);
+        }
+    }
+}
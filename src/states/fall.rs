@@ -5,13 +5,16 @@ use crate::states::fast_fall::*;
 use crate::states::play::*;
 use crate::tetramino::*;
 use crate::resources::*;
+use crate::abstraction::abstraction_layer::RenderContext;
 use crate::game_data::*;
+use crate::finesse::minimal_inputs;
+use crate::input::Action;
+use crate::events::GameEvent;
 use piston_window::*;
 use std::error;
 
-const TIME_INTERVAL: f64 = 0.33;
-const CONTROL_TIME_INTERVAL: f64 = 0.1;
 const MOVEMENT_SPEED: i32 = 1;
+const SOFT_DROP_FACTOR: f64 = 20.0;
 
 pub struct FallingState {
     fall_time: f64,
@@ -23,13 +26,38 @@ pub struct FallingState {
     right_pressed: bool, // piston bug
     rotate_left: bool,
     rotate_right: bool,
-    down_pressed: bool,
+    rotate_half: bool,
+    soft_drop: bool,
+    // Kept until the next update, the terminal releases keys right away.
+    soft_drop_stroke: bool,
+    hard_drop: bool,
+    last_rotated: bool,
+    hold_pressed: bool,
+    hold_spawn: Option<u32>,
 
 }
 
 impl FallingState {
     pub fn new() -> Result<Box<dyn State>, Box<dyn error::Error>> {
-        Ok(Box::new(FallingState {
+        Ok(Box::new(FallingState::initial()))
+    }
+
+    pub fn restore(arguments: &[&str]) -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        let mut state = FallingState::initial();
+        state.fall_time = state_argument(arguments, 0)?;
+        state.hold_spawn = match arguments.get(1) {
+            Some(&"-") => None,
+            _ => Some(state_argument(arguments, 1)?),
+        };
+        // Saves from before T-spins were kept have no third argument.
+        if arguments.len() > 2 {
+            state.last_rotated = state_argument(arguments, 2)?;
+        }
+        Ok(Box::new(state))
+    }
+
+    fn initial() -> FallingState {
+        FallingState {
             fall_time: 0.0,
             horizontal_time: 0.0,
             horizontal_movement: 0,
@@ -39,14 +67,23 @@ impl FallingState {
             right_pressed: false,
             rotate_left: false,
             rotate_right: false,
-            down_pressed: false,
-
-        }))
+            rotate_half: false,
+            soft_drop: false,
+            soft_drop_stroke: false,
+            hard_drop: false,
+            last_rotated: false,
+            hold_pressed: false,
+            hold_spawn: None,
+        }
     }
 
     fn handle_fall(&mut self, dt: f64, data: &mut GameData) -> StateTransition {
         self.fall_time += dt;
-        let time_interval = TIME_INTERVAL / data.speed_multiplier();
+        let mut time_interval = data.settings.handling.gravity_interval / data.speed_multiplier();
+        if self.soft_drop || self.soft_drop_stroke {
+            time_interval /= SOFT_DROP_FACTOR;
+        }
+        self.soft_drop_stroke = false;
         if self.fall_time >= time_interval {
             self.fall_time -= time_interval;
             let current = &data.current_figure;
@@ -60,16 +97,34 @@ impl FallingState {
                     return Pop;
                 }
 
+                let minimal = minimal_inputs(
+                    current,
+                    &data.tetraminoes_data,
+                    &data.collision_table,
+                    &data.play_table,
+                );
+                data.finesse.record_piece(current.get_type(), minimal);
+
                 let position = current.get_position().add(data.tetramino_preview_offset());
+                let tspin = self.last_rotated && is_tspin(current, &data.play_table);
+                let piece = current.get_type();
                 let game_field = &mut data.play_table;
-                fill_field(&position, rotation.into_iter(), game_field);
+                fill_field(&position, rotation, game_field, TETRAMINOES[piece]);
+                let lines = find_filled_lines(game_field).len();
+                data.emit(GameEvent::PieceLocked { piece, tspin, lines });
+                if let Some(puzzle) = &mut data.puzzle {
+                    puzzle.record_lock(tspin, lines);
+                }
                 return Push(LineClearing::new().unwrap());
             } else {
                 let current = &mut data.current_figure;
                 current.set_position(new_position);
+                self.last_rotated = false;
             }
         }
 
+        data.interpolation.fall = self.fall_time / time_interval;
+        data.interpolation.fall_speed = 1.0 / time_interval;
         Hold
     }
 
@@ -89,36 +144,36 @@ impl FallingState {
             next_rotation_index = current.peek_right_rotation();
         }
 
-        let rotation = &data.tetraminoes_data[current.get_type()].rotations[next_rotation_index];
-        let sequence = data.collision_table.collision_sequence(
-            rotation_index,
-            next_rotation_index,
-            current.get_type(),
-        );
-
-        let mut collision = true;
-        let mut free_position = Point { x: 0, y: 0 };
-        for point in sequence {
-            let new_position = current.get_position().add(point);
-            if !check_for_collision(&new_position, rotation, game_field) {
-                collision = false;
-                free_position = *point;
-                break;
-            }
+        if self.rotate_half {
+            self.rotate_half = false;
+            next_rotation_index = current.peek_half_rotation();
         }
 
-        if collision {
+        if next_rotation_index == rotation_index {
             return;
         }
 
+        let free_position = match rotation_kick(
+            current,
+            next_rotation_index,
+            &data.tetraminoes_data,
+            &data.collision_table,
+            game_field,
+        ) {
+            Some(point) => point,
+            None => return,
+        };
+
         let current = &mut data.current_figure;
         current.move_it(&free_position);
         current.set_rotation(next_rotation_index);
+        self.last_rotated = true;
+        data.emit(GameEvent::PieceRotated { rotation: next_rotation_index });
     }
 
     fn handle_horizontal_movement(&mut self, dt: f64, data: &mut GameData) {
         self.horizontal_time += dt;
-        let time_interval = CONTROL_TIME_INTERVAL;// / data.speed_multiplier();
+        let time_interval = data.settings.handling.control_interval;
         if self.horizontal_time >= time_interval {
             self.horizontal_time -= time_interval;
             let current = &data.current_figure;
@@ -141,8 +196,13 @@ impl FallingState {
             self.left_stroke = false;
             self.right_stroke = false;
             let game_field = &data.play_table;
-            if !check_for_collision(&new_position, rotation, game_field) {
+            if new_position.x != current.get_position().x
+                && !check_for_collision(&new_position, rotation, game_field)
+            {
+                let direction = new_position.x - current.get_position().x;
                 data.current_figure.set_position(new_position);
+                self.last_rotated = false;
+                data.emit(GameEvent::PieceMoved { direction });
             }
         }
     }
@@ -163,8 +223,21 @@ impl State for FallingState {
             }
         }
 
-        if self.down_pressed {
-            self.down_pressed = false;
+        if self.hold_pressed {
+            self.hold_pressed = false;
+            if data.settings.gameplay.hold_enabled
+                && data.can_hold()
+                && self.hold_spawn != Some(data.spawn_count)
+            {
+                data.hold_figure();
+                data.emit(GameEvent::PieceSpawned { piece: data.current_figure.get_type() });
+                self.hold_spawn = Some(data.spawn_count);
+                self.last_rotated = false;
+            }
+        }
+
+        if self.hard_drop {
+            self.hard_drop = false;
             return StateTransition::Push(FastFallingState::new().unwrap());
         }
 
@@ -173,89 +246,110 @@ impl State for FallingState {
         StateTransition::Hold
     }
 
-    fn handle_input(&mut self, input: Input, _time: Option<TimeStamp>, _data: &mut GameData) {
-        match input {
-            Input::Button(buttons) => match buttons.button {
-                Button::Keyboard(key) => match key {
-                    Key::Left => {
-                        if buttons.state == ButtonState::Press {
-                            if !self.left_pressed {
-                                self.horizontal_movement -= MOVEMENT_SPEED;
-                                self.left_stroke = true;
-                                self.left_pressed = true;
-                            }
-                        } else {
-                            self.horizontal_movement = 0;
-                            self.left_pressed = false;
-                        }
-                    }
-                    Key::Right => {
-                        if buttons.state == ButtonState::Press {
-                            if !self.right_pressed {
-                                self.horizontal_movement += MOVEMENT_SPEED;
-                                self.right_pressed = true;
-                                self.right_stroke = true;
-                            }
-                        } else {
-                            self.horizontal_movement = 0;
-                            self.right_pressed = false;
-                        }
+    fn handle_action(&mut self, action: Action, state: ButtonState, data: &mut GameData) {
+        let pressed = state == ButtonState::Press;
+        match action {
+            Action::MoveLeft => {
+                if pressed {
+                    if !self.left_pressed {
+                        data.emit(GameEvent::KeyPressed { action });
+                        self.horizontal_movement -= MOVEMENT_SPEED;
+                        self.left_stroke = true;
+                        self.left_pressed = true;
                     }
-                    Key::Up => {
-                        if buttons.state == ButtonState::Press {
-                            self.rotate_left = true;
-                        }
-                    }
-
-                    Key::Down => {
-                        if buttons.state == ButtonState::Press {
-                            self.rotate_right = true;
-                        }
-                    }
-
-                    Key::Space => {
-                        if buttons.state == ButtonState::Press {
-                            self.down_pressed = true;
-                        }
+                } else {
+                    self.horizontal_movement = 0;
+                    self.left_pressed = false;
+                }
+            }
+            Action::MoveRight => {
+                if pressed {
+                    if !self.right_pressed {
+                        data.emit(GameEvent::KeyPressed { action });
+                        self.horizontal_movement += MOVEMENT_SPEED;
+                        self.right_pressed = true;
+                        self.right_stroke = true;
                     }
-                    _ => {}
-                },
-                _ => {}
-            },
+                } else {
+                    self.horizontal_movement = 0;
+                    self.right_pressed = false;
+                }
+            }
+            Action::SoftDrop => {
+                if pressed && !self.soft_drop {
+                    data.emit(GameEvent::KeyPressed { action });
+                }
+                self.soft_drop = pressed;
+                self.soft_drop_stroke |= pressed;
+            }
+            Action::HardDrop if pressed => {
+                data.emit(GameEvent::KeyPressed { action });
+                self.hard_drop = true;
+            }
+            Action::RotateCounterClockwise if pressed => {
+                data.emit(GameEvent::KeyPressed { action });
+                self.rotate_left = true;
+            }
+            Action::RotateClockwise if pressed => {
+                data.emit(GameEvent::KeyPressed { action });
+                self.rotate_right = true;
+            }
+            Action::Rotate180 if pressed => {
+                data.emit(GameEvent::KeyPressed { action });
+                self.rotate_half = true;
+            }
+            Action::Hold if pressed => {
+                data.emit(GameEvent::KeyPressed { action });
+                self.hold_pressed = true;
+            }
             _ => {}
         }
-
     }
 
-    fn background_render(
-        &mut self,
-        _c: Context,
-        _g: &mut G2d,
-        _arguments: &RenderArgs,
-        _device: &mut gfx_device_gl::Device,
-        _resources: &mut Resources,
-        _data: &GameData,
-    ) {
+    fn render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, data: &GameData) {
+        draw_current(ctx, resources, data);
     }
 
-    fn render(
-        &mut self,
-        c: Context,
-        g: &mut G2d,
-        arguments: &RenderArgs,
-        device: &mut gfx_device_gl::Device,
-        resources: &mut Resources,
-        data: &GameData,
-    ) {
-        draw_current(&c, g, arguments, device, resources, data);
-    }
+    // Whatever is on top started with the piece locked into the field.
+    fn background_render(&mut self, _ctx: &mut dyn RenderContext, _resources: &Resources, _data: &GameData) {}
 
     fn enter(&mut self, _data: &mut GameData) {}
     fn exit(&mut self, data: &mut GameData) {}
 
     fn resume(&mut self, _data: &mut GameData) {
+        self.last_rotated = false;
+        self.soft_drop = false;
+        self.soft_drop_stroke = false;
         self.horizontal_movement = 0;
         self.right_stroke = false;
         self.left_stroke = false;
     }
+
+    fn save(&self) -> Option<String> {
+        let hold_spawn = self.hold_spawn.map_or(String::from("-"), |spawn| spawn.to_string());
+        Some(format!("falling {} {} {}", self.fall_time, hold_spawn, self.last_rotated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+
+    #[test]
+    fn test_soft_drop_tap() {
+        let mut data = GameData::new(Settings::default()).unwrap();
+        let mut state = FallingState::initial();
+        let start = data.current_figure.get_position().y;
+        let args = UpdateArgs { dt: data.settings.handling.gravity_interval / 4.0 };
+
+        // A press released before the update still drops the piece once.
+        state.handle_action(Action::SoftDrop, ButtonState::Press, &mut data);
+        state.handle_action(Action::SoftDrop, ButtonState::Release, &mut data);
+        state.update(&mut data, &args, Event::Loop(Loop::Update(args)));
+        assert_eq!(start + 1, data.current_figure.get_position().y);
+
+        state.update(&mut data, &args, Event::Loop(Loop::Update(args)));
+        assert_eq!(start + 1, data.current_figure.get_position().y);
+    }
 }
@@ -3,6 +3,7 @@ use crate::states::state_machine::*;
 use crate::states::play::*;
 use crate::game_data::*;
 use crate::resources::*;
+use crate::abstraction::abstraction_layer::RenderContext;
 use piston_window::*;
 use std::error;
 
@@ -19,6 +20,12 @@ impl FastFallingState {
         }))
     }
 
+    pub fn restore(arguments: &[&str]) -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        Ok(Box::new(FastFallingState {
+            fall_time: state_argument(arguments, 0)?,
+        }))
+    }
+
     fn handle_fall(&mut self, dt: f64, data: &mut GameData) -> StateTransition {
         self.fall_time += dt;
         let time_interval = TIME_INTERVAL / data.speed_multiplier();
@@ -53,31 +60,16 @@ impl State for FastFallingState {
 
     fn handle_input(&mut self, _input: Input, _time: Option<TimeStamp>, _data: &mut GameData) {}
 
-    fn background_render(
-        &mut self,
-        _c: Context,
-        _g: &mut G2d,
-        _arguments: &RenderArgs,
-        _device: &mut gfx_device_gl::Device,
-        _resources: &mut Resources,
-        _data: &GameData,
-    ) {
-    }
-
-    fn render(
-        &mut self,
-        c: Context,
-        g: &mut G2d,
-        arguments: &RenderArgs,
-        device: &mut gfx_device_gl::Device,
-        resources: &mut Resources,
-        data: &GameData,
-    ) {
-        draw_current(&c, g, arguments, device, resources, data);
+    fn render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, data: &GameData) {
+        draw_current(ctx, resources, data);
     }
 
     fn enter(&mut self, _data: &mut GameData) {}
 
     fn exit(&mut self, _data: &mut GameData) {}
+
+    fn save(&self) -> Option<String> {
+        Some(format!("fast_fall {}", self.fall_time))
+    }
 }
 
@@ -0,0 +1,106 @@
+use crate::states::state_machine::*;
+use crate::states::main_menu::MainMenu;
+use crate::high_scores::*;
+use crate::game_data::*;
+use crate::input::Action;
+use crate::Resources;
+use crate::abstraction::abstraction_layer::*;
+use crate::layout::Layout;
+use piston_window::*;
+use std::error;
+
+const TITLE_TEXT_SIZE: u32 = 48;
+const ROW_TEXT_SIZE: u32 = 28;
+const TITLE_POSITION_Y: f64 = 96.0;
+const HEADER_POSITION_Y: f64 = 192.0;
+const ROW_SPACING: f64 = 56.0;
+const COLUMNS: [f64; 6] = [0.0, 64.0, 384.0, 576.0, 704.0, 800.0];
+
+pub struct HighScoreTable {
+    high_scores: HighScores,
+    error: Option<String>,
+    mode: usize,
+    back: bool,
+}
+
+impl HighScoreTable {
+    pub fn new() -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        let (high_scores, error) = match HighScores::load() {
+            Ok(high_scores) => (high_scores, None),
+            Err(error) => (HighScores::default(), Some(error.to_string())),
+        };
+
+        Ok(Box::new(HighScoreTable {
+            high_scores,
+            error,
+            mode: 0,
+            back: false,
+        }))
+    }
+}
+
+fn draw_row(ctx: &mut dyn RenderContext, layout: &Layout, cells: [&str; 6], y: f64, color: &Color, font: FontHandle) {
+    let size = layout.text_size(ROW_TEXT_SIZE);
+    for (cell, x) in cells.iter().zip(COLUMNS.iter()) {
+        ctx.draw_text(cell, font, size, &layout.screen_point(*x, y), color);
+    }
+}
+
+impl State for HighScoreTable {
+    fn update(
+        &mut self,
+        _data: &mut GameData,
+        _update_args: &UpdateArgs,
+        _event: Event,
+    ) -> StateTransition {
+        if self.back {
+            self.back = false;
+            return StateTransition::Transition(MainMenu::new().unwrap());
+        }
+        StateTransition::Hold
+    }
+
+    fn handle_action(&mut self, action: Action, state: ButtonState, _data: &mut GameData) {
+        if state != ButtonState::Press {
+            return;
+        }
+
+        let count = RANKED_MODES.len();
+        match action {
+            Action::MenuLeft => self.mode = (self.mode + count - 1) % count,
+            Action::MenuRight => self.mode = (self.mode + 1) % count,
+            Action::Confirm | Action::Back => self.back = true,
+            _ => {}
+        }
+    }
+    fn render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, _data: &GameData) {
+        let font = resources.font;
+        let layout = Layout::of(ctx);
+
+        render_background(ctx, resources);
+
+        let mode = RANKED_MODES[self.mode];
+        let title = format!("< {} >", mode.title());
+        let transform = layout.screen_point(0.0, TITLE_POSITION_Y);
+        render_sized_text(&title, layout.text_size(TITLE_TEXT_SIZE), resources, &transform, ctx, true);
+
+        if let Some(error) = &self.error {
+            let transform = layout.screen_point(0.0, HEADER_POSITION_Y);
+            ctx.draw_text(error, font, layout.text_size(ROW_TEXT_SIZE), &transform, &resources.colors.error);
+            return;
+        }
+
+        let header = ["#", "Name", "Score", "Lines", "Level", "Date"];
+        draw_row(ctx, &layout, header, HEADER_POSITION_Y, &resources.colors.title, font);
+
+        for (index, entry) in self.high_scores.table(mode).iter().enumerate() {
+            let rank = (index + 1).to_string();
+            let score = entry.score.to_string();
+            let lines = entry.lines.to_string();
+            let level = entry.level.to_string();
+            let cells = [rank.as_str(), &entry.name, &score, &lines, &level, &entry.date];
+            let y = HEADER_POSITION_Y + (index + 1) as f64 * ROW_SPACING;
+            draw_row(ctx, &layout, cells, y, &resources.colors.value, font);
+        }
+    }
+}
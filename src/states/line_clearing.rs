@@ -1,9 +1,11 @@
 use crate::states::chunk_falling::*;
 use crate::states::state_machine::*;
 use crate::states::play::*;
-use crate::tetramino::*;
 use crate::game_data::*;
+use crate::events::GameEvent;
 use crate::Resources;
+use crate::abstraction::abstraction_layer::*;
+use crate::layout::Layout;
 use piston_window::*;
 use std::error;
 
@@ -17,18 +19,49 @@ pub struct LineClearing {
     iterations : u32,
     time_passed : f64,
     lines : Vec<usize>,
+    chain : u32,
 }
 
 impl LineClearing {
     pub fn new() -> Result<Box<dyn State>, Box<dyn error::Error>> {
-        Ok(Box::new(LineClearing {
+        Ok(Box::new(LineClearing::initial()))
+    }
+
+    /// Clears the lines left complete by the `chain`th cascade of chunks.
+    pub fn chained(chain : u32) -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        let mut state = LineClearing::initial();
+        state.chain = chain;
+        Ok(Box::new(state))
+    }
+
+    pub fn restore(arguments: &[&str], data: &GameData) -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        let mut state = LineClearing::initial();
+        state.iterations = state_argument(arguments, 0)?;
+        state.time_passed = state_argument(arguments, 1)?;
+        state.find_lines(&data.play_table);
+        Ok(Box::new(state))
+    }
+
+    fn initial() -> LineClearing {
+        LineClearing {
             min_line : 0,
             max_line : 0,
             line_count : 0,
             iterations : 0,
             time_passed : 0.0,
             lines : vec![],
-        }))
+            chain : 0,
+        }
+    }
+
+    fn find_lines(&mut self, play_table : &GameField) {
+        self.lines = find_filled_lines(play_table);
+        let count = self.lines.len();
+        if count != 0 {
+            self.max_line = *self.lines.first().unwrap();
+            self.min_line = *self.lines.last().unwrap();
+            self.line_count = count;
+        }
     }
 }
 
@@ -44,8 +77,8 @@ impl State for LineClearing {
             let score_multiplier = data.score_multiplier();
             data.add_score((((overall_lines + 1) * overall_lines) * score_multiplier ) as u32);
             data.lines = 0;
-            data.current_figure = Tetramino::new(data.next_figure);
-            data.next_figure = GameData::random_tetramino_index();
+            data.spawn_next_figure();
+            data.emit(GameEvent::PieceSpawned { piece : data.current_figure.get_type() });
             return StateTransition::Pop;
         }
 
@@ -54,7 +87,7 @@ impl State for LineClearing {
             self.time_passed -= TIME_INTERVAL;
             self.iterations += 1;
             if self.iterations >= MAX_ITERATIONS {
-                return StateTransition::Transition(ChunkFall::new(self.min_line).unwrap());
+                return StateTransition::Transition(ChunkFall::new(self.min_line, self.chain).unwrap());
             }
         }
         StateTransition::Hold
@@ -63,40 +96,26 @@ impl State for LineClearing {
     fn handle_input(&mut self, _input: Input, _time: Option<TimeStamp>, _data: &mut GameData) {
     }
 
-    fn render(
-        &mut self,
-        c: Context,
-        g: &mut G2d,
-        _arguments: &RenderArgs,
-        _device: &mut gfx_device_gl::Device,
-        resources: &mut Resources,
-        data: &GameData,
-    ) {
-        let empty_block = &resources.empty_block;
-        let a = self.max_line*WIDTH;
-        let b = (self.min_line + 1)*WIDTH;
-        let blocks = &data.play_table[a..b];
-        let mut position_index: usize = a*BLOCK_SIZE;
-        blocks.iter().for_each(|_block: &TetrominoType| {
-            let x = position_index % GAME_FIELD_WIDTH;
-            let y = (position_index / GAME_FIELD_WIDTH) * BLOCK_SIZE;
-            position_index += BLOCK_SIZE;
-            if self.iterations%2 == 0 {
-                image(empty_block, c.transform.trans(x as f64, y as f64), g);
-            }
+    fn overlay(&self) -> Option<Overlay> {
+        Some(Overlay::CLEAR)
+    }
 
-        });
+    fn render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, _data: &GameData) {
+        if !self.iterations.is_multiple_of(2) {
+            return;
+        }
+
+        let layout = Layout::of(ctx);
+        for index in self.max_line * WIDTH..(self.min_line + 1) * WIDTH {
+            draw_cell(ctx, &layout, resources.empty_block, (index % WIDTH) as i32, (index / WIDTH) as i32);
+        }
     }
 
     fn enter(&mut self, data: &mut GameData) {
-        let play_table = &data.play_table;
-        self.lines = find_filled_lines(play_table);
         data.lines += self.line_count;
-        let count = self.lines.len();
-        if count != 0 {
-            self.max_line = self.lines.first().unwrap().clone();
-            self.min_line = self.lines.last().unwrap().clone();
-            self.line_count = count;
+        self.find_lines(&data.play_table);
+        if self.line_count != 0 {
+            data.emit(GameEvent::LinesCleared { lines : self.line_count, chain : self.chain });
         }
     }
 
@@ -106,9 +125,14 @@ impl State for LineClearing {
         if lines_count != 0 {
             let play_table = &mut data.play_table;
             clear_play_table(play_table, self.lines.clone());
+            data.total_lines += lines_count;
             data.add_score((1 << (self.line_count - 1)) * score_multiplier);
         }
 
         data.add_score((lines_count*(lines_count + 1)) as u32 * score_multiplier);
     }
+
+    fn save(&self) -> Option<String> {
+        Some(format!("line_clearing {} {}", self.iterations, self.time_passed))
+    }
 }
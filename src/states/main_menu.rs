@@ -1,15 +1,33 @@
 use crate::states::state_machine::*;
+use crate::states::transition::Effect;
 use crate::states::play::PlayState;
+use crate::states::puzzle_select::PuzzleSelect;
+use crate::states::editor::Editor;
+use crate::states::high_score_table::HighScoreTable;
+use crate::states::settings_menu::SettingsMenu;
+use crate::states::menu_mouse::*;
 use crate::game_data::*;
+use crate::input::Action;
 use crate::Resources;
+use crate::abstraction::abstraction_layer::*;
+use crate::layout::Layout;
 use piston_window::*;
 use std::error;
 
-const ELEMENTS_COUNT: i32 = 2;
+const ELEMENTS_COUNT: i32 = 8;
+const ELEMENTS: [&str; ELEMENTS_COUNT as usize] =
+    ["Start game", "Continue", "Puzzles", "Editor", "High scores", "Settings", "Demo", "Quit"];
+const ELEMENT_TEXT_SIZE: u32 = 128;
+const FIRST_ELEMENT_POSITION_Y: f64 = 128.0;
+const ELEMENT_SPACING: f64 = 144.0;
+const ERROR_TEXT_SIZE: u32 = 24;
+const ERROR_POSITION_Y: f64 = 1240.0;
 
 pub struct MainMenu {
     selection: i32,
     interact: bool,
+    error: Option<String>,
+    mouse: MenuMouse,
 }
 
 impl MainMenu {
@@ -17,6 +35,8 @@ impl MainMenu {
         Ok(Box::new(MainMenu {
             selection: 0,
             interact: false,
+            error: None,
+            mouse: MenuMouse::new(),
         }))
     }
 }
@@ -24,7 +44,7 @@ impl MainMenu {
 impl State for MainMenu {
     fn update(
         &mut self,
-        _data: &mut GameData,
+        data: &mut GameData,
         _update_args: &UpdateArgs,
         _event: Event,
     ) -> StateTransition {
@@ -35,7 +55,36 @@ impl State for MainMenu {
                     return StateTransition::Transition(PlayState::new().unwrap());
                 }
 
-                1 => {
+                1 => match PlayState::resume(data) {
+                    Ok(state) => {
+                        return StateTransition::Transition(state);
+                    }
+                    Err(error) => {
+                        self.error = Some(error.to_string());
+                    }
+                },
+
+                2 => {
+                    return StateTransition::Transition(PuzzleSelect::new().unwrap());
+                }
+
+                3 => {
+                    return StateTransition::Transition(Editor::new().unwrap());
+                }
+
+                4 => {
+                    return StateTransition::Transition(HighScoreTable::new().unwrap());
+                }
+
+                5 => {
+                    return StateTransition::Transition(SettingsMenu::new(true).unwrap());
+                }
+
+                6 => {
+                    return StateTransition::Transition(PlayState::demo().unwrap());
+                }
+
+                7 => {
                     return StateTransition::Pop;
                 }
 
@@ -48,67 +97,58 @@ impl State for MainMenu {
     }
 
     fn handle_input(&mut self, input: Input, _time: Option<TimeStamp>, _data: &mut GameData) {
-        match input {
-            Input::Button(buttons) => {
-                if buttons.state == ButtonState::Press {
-                    match buttons.button {
-                        Button::Keyboard(key) => match key {
-                            Key::Up => {
-                                self.selection =
-                                    (self.selection + (ELEMENTS_COUNT - 1)) % ELEMENTS_COUNT;
-                            }
-                            Key::Down => {
-                                self.selection = (self.selection + 1) % ELEMENTS_COUNT;
-                            }
-                            Key::Return => {
-                                self.interact = true;
-                            }
-
-                            Key::Escape => {
-                                self.selection = 1;
-                                self.interact = true;
-                            }
-                            _ => {}
-                        },
-                        _ => {}
-                    }
-                }
+        match self.mouse.handle_input(&input) {
+            Some(MouseEvent::Hover(index)) => self.selection = index as i32,
+            Some(MouseEvent::Click(index)) => {
+                self.selection = index as i32;
+                self.interact = true;
+            }
+            None => {}
+        }
+    }
+
+    fn handle_action(&mut self, action: Action, state: ButtonState, _data: &mut GameData) {
+        if state != ButtonState::Press {
+            return;
+        }
+
+        match action {
+            Action::MenuUp => {
+                self.selection = (self.selection + (ELEMENTS_COUNT - 1)) % ELEMENTS_COUNT;
+            }
+            Action::MenuDown => {
+                self.selection = (self.selection + 1) % ELEMENTS_COUNT;
+            }
+            Action::Confirm => {
+                self.interact = true;
+            }
+            Action::Back => {
+                self.selection = ELEMENTS_COUNT - 1;
+                self.interact = true;
             }
             _ => {}
         }
     }
+    fn transition(&self) -> Option<Effect> {
+        Some(Effect::Fade)
+    }
 
-    fn render(
-        &mut self,
-        _c: Context,
-        _g: &mut G2d,
-        _arguments: &RenderArgs,
-        _device: &mut gfx_device_gl::Device,
-        _resources: &mut Resources,
-        _data: &GameData,
-    ) {
-        let texture = &_resources.background;
-        let _font = &mut _resources.font;
-
-        clear([1.0; 4], _g);
-        image(texture, _c.transform, _g);
-
-        render_text(
-            "Start game",
-            _font,
-            _c.transform.trans(0 as f64, 128 as f64),
-            &_c.draw_state,
-            _g,
-            self.selection == 0,
-        );
-        render_text(
-            "Quit",
-            _font,
-            _c.transform.trans(0 as f64, 512 as f64),
-            &_c.draw_state,
-            _g,
-            self.selection == 1,
-        );
-        _font.factory.encoder.flush(_device);
+    fn render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, _data: &GameData) {
+        let font = resources.font;
+        let layout = Layout::of(ctx);
+
+        render_background(ctx, resources);
+
+        let size = layout.text_size(ELEMENT_TEXT_SIZE);
+        for (index, element) in ELEMENTS.iter().enumerate() {
+            let transform = layout.screen_point(0.0, FIRST_ELEMENT_POSITION_Y + index as f64 * ELEMENT_SPACING);
+            render_sized_text(element, size, resources, &transform, ctx, self.selection == index as i32);
+            self.mouse.set_item(index, text_bounds(ctx, element, size, font, transform.x, transform.y));
+        }
+
+        if let Some(error) = &self.error {
+            let transform = layout.screen_point(0.0, ERROR_POSITION_Y);
+            ctx.draw_text(error, font, layout.text_size(ERROR_TEXT_SIZE), &transform, &resources.colors.error);
+        }
     }
 }
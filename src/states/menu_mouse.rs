@@ -0,0 +1,109 @@
+use crate::abstraction::abstraction_layer::{FontHandle, RenderContext};
+use piston_window::*;
+
+pub enum MouseEvent {
+    Hover(usize),
+    Click(usize),
+}
+
+/// Window areas `[x, y, width, height]` of the items of a menu. They are
+/// measured while rendering, where the font is at hand, so a menu only
+/// reacts to the mouse once it has been drawn.
+#[derive(Default)]
+pub struct MenuMouse {
+    items: Vec<[f64; 4]>,
+    cursor: Option<[f64; 2]>,
+    hovered: Option<usize>,
+}
+
+impl MenuMouse {
+    pub fn new() -> MenuMouse {
+        MenuMouse::default()
+    }
+
+    pub fn set_item(&mut self, index: usize, bounds: [f64; 4]) {
+        if self.items.len() <= index {
+            self.items.resize(index + 1, [0.0; 4]);
+        }
+        self.items[index] = bounds;
+    }
+
+    /// Items past `count` are no longer drawn.
+    pub fn truncate(&mut self, count: usize) {
+        self.items.truncate(count);
+    }
+
+    pub fn hovered(&self) -> Option<usize> {
+        self.hovered
+    }
+
+    fn item_at(&self, position: [f64; 2]) -> Option<usize> {
+        self.items.iter().position(|[x, y, width, height]| {
+            position[0] >= *x && position[0] < x + width && position[1] >= *y && position[1] < y + height
+        })
+    }
+
+    /// Hover is only reported when the cursor enters an item, so the mouse
+    /// resting on one does not fight keyboard navigation.
+    pub fn handle_input(&mut self, input: &Input) -> Option<MouseEvent> {
+        match input {
+            Input::Move(Motion::MouseCursor(position)) => {
+                self.cursor = Some(*position);
+                let hovered = self.item_at(*position);
+                let entered = hovered.filter(|item| self.hovered != Some(*item));
+                self.hovered = hovered;
+                entered.map(MouseEvent::Hover)
+            }
+
+            Input::Button(ButtonArgs {
+                state: ButtonState::Press,
+                button: Button::Mouse(MouseButton::Left),
+                ..
+            }) => self.cursor.and_then(|position| self.item_at(position)).map(MouseEvent::Click),
+            _ => None,
+        }
+    }
+}
+
+/// Area covered by text drawn with its baseline at `[x, y]`.
+pub fn text_bounds(ctx: &mut dyn RenderContext, text: &str, size: u32, font: FontHandle, x: f64, y: f64) -> [f64; 4] {
+    let width = ctx.text_width(text, font, size);
+    [x, y - size as f64, width, size as f64]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(x: f64, y: f64) -> Input {
+        Input::Move(Motion::MouseCursor([x, y]))
+    }
+
+    fn click() -> Input {
+        Input::Button(ButtonArgs {
+            state: ButtonState::Press,
+            button: Button::Mouse(MouseButton::Left),
+            scancode: None,
+        })
+    }
+
+    #[test]
+    fn test_menu_mouse() {
+        let mut mouse = MenuMouse::new();
+        mouse.set_item(1, [0.0, 200.0, 300.0, 100.0]);
+        mouse.set_item(0, [0.0, 50.0, 300.0, 100.0]);
+
+        assert!(mouse.handle_input(&click()).is_none());
+        assert!(matches!(mouse.handle_input(&cursor(10.0, 220.0)), Some(MouseEvent::Hover(1))));
+        assert!(mouse.handle_input(&cursor(20.0, 230.0)).is_none());
+        assert_eq!(Some(1), mouse.hovered());
+        assert!(matches!(mouse.handle_input(&click()), Some(MouseEvent::Click(1))));
+        assert!(mouse.handle_input(&cursor(400.0, 60.0)).is_none());
+        assert!(mouse.handle_input(&click()).is_none());
+        assert!(matches!(mouse.handle_input(&cursor(10.0, 60.0)), Some(MouseEvent::Hover(0))));
+
+        mouse.truncate(1);
+        mouse.handle_input(&cursor(10.0, 220.0));
+        assert!(mouse.handle_input(&click()).is_none());
+    }
+}
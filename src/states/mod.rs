@@ -6,4 +6,10 @@ pub mod main_menu;
 pub mod fast_fall;
 pub mod pause;
 pub mod play;
-pub mod fall;
\ No newline at end of file
+pub mod fall;
+pub mod puzzle_select;
+pub mod puzzle_result;
+pub mod editor;
+pub mod high_score_table;
+pub mod settings_menu;
+pub mod menu_mouse;pub mod transition;
@@ -1,79 +1,147 @@
 use crate::states::state_machine::*;
+use crate::states::transition::Effect;
+use crate::states::settings_menu::SettingsMenu;
+use crate::states::menu_mouse::*;
 use crate::game_data::*;
+use crate::input::Action;
 use crate::Resources;
+use crate::abstraction::abstraction_layer::*;
+use crate::layout::Layout;
 use piston_window::*;
 use std::error;
 
+const RESUME_ITEM: usize = 0;
+const SETTINGS_ITEM: usize = 1;
+const SAVE_ITEM: usize = 2;
+const ITEMS: [&str; 3] = ["Resume", "Settings", "Save and quit"];
+const TITLE_TEXT_SIZE: u32 = 32;
+const TITLE_POSITION_Y: f64 = 128.0;
+const ITEM_TEXT_SIZE: u32 = 128;
+const FIRST_ITEM_POSITION_Y: f64 = 320.0;
+const ITEM_SPACING: f64 = 192.0;
+const MESSAGE_TEXT_SIZE: u32 = 24;
+const MESSAGE_POSITION_Y: f64 = 832.0;
+// The game stays in sight behind the menu.
+const OVERLAY: Overlay = Overlay { dim: 0.6, blur: 4 };
+
 pub struct Pause {
     interact: bool,
+    selection: usize,
+    can_save: bool,
+    message: Option<String>,
+    mouse: MenuMouse,
 }
 
 impl Pause {
-    pub fn new() -> Result<Box<dyn State>, Box<dyn error::Error>> {
+    pub fn new(can_save: bool) -> Result<Box<dyn State>, Box<dyn error::Error>> {
         Ok(Box::new(Pause {
             interact: false,
+            selection: RESUME_ITEM,
+            can_save,
+            message: None,
+            mouse: MenuMouse::new(),
         }))
     }
+
+    pub fn with_message(can_save: bool, message: &str) -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        Ok(Box::new(Pause {
+            interact: false,
+            selection: RESUME_ITEM,
+            can_save,
+            message: Some(message.to_string()),
+            mouse: MenuMouse::new(),
+        }))
+    }
+
+    fn items_count(&self) -> usize {
+        if self.can_save {
+            3
+        } else {
+            2
+        }
+    }
 }
 
 impl State for Pause {
     fn update(
         &mut self,
-        _data: &mut GameData,
+        data: &mut GameData,
         _update_args: &UpdateArgs,
         _event: Event,
     ) -> StateTransition {
         if self.interact {
             self.interact = false;
+            if self.selection == SETTINGS_ITEM {
+                return StateTransition::Push(SettingsMenu::new(false).unwrap());
+            }
+            data.save_requested = self.selection == SAVE_ITEM;
             return StateTransition::Pop;
         }
         StateTransition::Hold
     }
 
     fn handle_input(&mut self, input: Input, _time: Option<TimeStamp>, _data: &mut GameData) {
-        match input {
-            Input::Button(buttons) => {
-                if buttons.state == ButtonState::Press {
-                    match buttons.button {
-                        Button::Keyboard(key) => match key {
-                            Key::Escape => {
-                                self.interact = true;
-                            }
-                            _ => {}
-                        },
-                        _ => {}
-                    }
-                }
+        match self.mouse.handle_input(&input) {
+            Some(MouseEvent::Hover(index)) => self.selection = index,
+            Some(MouseEvent::Click(index)) => {
+                self.selection = index;
+                self.interact = true;
+            }
+            None => {}
+        }
+    }
+
+    fn handle_action(&mut self, action: Action, state: ButtonState, _data: &mut GameData) {
+        if state != ButtonState::Press {
+            return;
+        }
+
+        let count = self.items_count();
+        match action {
+            Action::MenuUp => {
+                self.selection = (self.selection + count - 1) % count;
+            }
+            Action::MenuDown => {
+                self.selection = (self.selection + 1) % count;
+            }
+            Action::Confirm => {
+                self.interact = true;
+            }
+            Action::Back => {
+                self.selection = RESUME_ITEM;
+                self.interact = true;
             }
             _ => {}
         }
     }
 
-    fn render(
-        &mut self,
-        c: Context,
-        g: &mut G2d,
-        _arguments: &RenderArgs,
-        device: &mut gfx_device_gl::Device,
-        resources: &mut Resources,
-        _data: &GameData,
-    ) {
-        let texture = &resources.background;
-        let font = &mut resources.font;
+    fn overlay(&self) -> Option<Overlay> {
+        Some(OVERLAY)
+    }
 
-        clear([1.0; 4], g);
-        image(texture, c.transform, g);
-        text::Text::new_color([0.2, 0.8, 0.3, 1.0], 32)
-            .draw(
-                "Pause",
-                font,
-                &c.draw_state,
-                c.transform
-                    .trans(0 as f64, 128 as f64),
-                g,
-            )
-            .unwrap();
+    fn transition(&self) -> Option<Effect> {
+        Some(Effect::Wipe)
+    }
+
+    fn render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, _data: &GameData) {
+        let font = resources.font;
+        let layout = Layout::of(ctx);
+
+        let transform = layout.screen_point(0.0, TITLE_POSITION_Y);
+        ctx.draw_text("Pause", font, layout.text_size(TITLE_TEXT_SIZE), &transform, &resources.colors.title);
 
-        font.factory.encoder.flush(device);
+        let count = self.items_count();
+        let size = layout.text_size(ITEM_TEXT_SIZE);
+        for (index, item) in ITEMS.iter().take(count).enumerate() {
+            let transform = layout.screen_point(0.0, FIRST_ITEM_POSITION_Y + index as f64 * ITEM_SPACING);
+            render_sized_text(item, size, resources, &transform, ctx, self.selection == index);
+            self.mouse.set_item(index, text_bounds(ctx, item, size, font, transform.x, transform.y));
+        }
+        self.mouse.truncate(count);
+
+        if let Some(message) = &self.message {
+            let transform = layout.screen_point(0.0, MESSAGE_POSITION_Y);
+            ctx.draw_text(message, font, layout.text_size(MESSAGE_TEXT_SIZE), &transform, &resources.colors.error);
+        }
     }
 }
@@ -1,31 +1,49 @@
 use crate::states::state_machine::*;
+use crate::states::transition::Effect;
 use crate::states::score_screen::*;
+use crate::states::main_menu::*;
+use crate::states::puzzle_result::*;
 use crate::states::pause::*;
 use crate::states::fall::*;
+use crate::states::fast_fall::FastFallingState;
+use crate::states::line_clearing::LineClearing;
+use crate::states::chunk_falling::ChunkFall;
+use crate::save_game::*;
+use crate::high_scores::GameMode;
+use crate::storage::KeyValueFile;
+use crate::ai::evaluator::Weights;
+use crate::ai::bot::Bot;
+use crate::finesse::Finesse;
+use crate::statistics::{GameStatistics, LifetimeStatistics, StatisticsListener};
+use crate::puzzle::Puzzle;
+use crate::input::{Action, InputContext};
+use crate::audio::AudioListener;
+use crate::events::*;
+use crate::hud::{Hud, HudListener};
 use crate::game_data::*;
 use crate::tetramino::*;
 use crate::Resources;
+use crate::abstraction::abstraction_layer::*;
 use crate::chunk::*;
+use crate::layout::Layout;
+use crate::widgets::draw_panel;
 use piston_window::*;
 use std::error;
 
-pub const BLOCK_SIZE: usize = 64;
-pub const GAME_FIELD_WIDTH: usize = BLOCK_SIZE * crate::game_data::WIDTH;
-const SCORE_TEXT_SIZE: u32 = 32;
-const SCORE_POSITION_X: f64 = 780.0;
-const SCORE_POSITION_Y: f64 = 24.0;
-const SCORE_TEXT_POSITION_X: f64 = 650.0;
-const SCORE_TEXT_POSITION_Y: f64 = SCORE_TEXT_SIZE as f64;
-const LEVEL_POSITION_X: f64 = 780.0;
-const LEVEL_POSITION_Y: f64 = 74.0;
-const LEVEL_TEXT_POSITION_X: f64 = 650.0;
-const LEVEL_TEXT_POSITION_Y: f64 = SCORE_TEXT_SIZE as f64 + 50.0;
-const PREVIEW_DEFAULT_POSITION_X: f64 = 780.0;
-const PREVIEW_DEFAULT_POSITION_Y: f64 = 240.0;
+// The HUD message is placed in reference pixels from the board's top left
+// corner.
+const HUD_MESSAGE_TEXT_SIZE: u32 = 48;
+const HUD_MESSAGE_POSITION_X: f64 = 64.0;
+const HUD_MESSAGE_POSITION_Y: f64 = 320.0;
 
 pub struct PlayState {
     logic: StateMachine,
     pause_event : bool,
+    quit_event : bool,
+    bot : Option<Bot>,
+    puzzle : Option<Puzzle>,
+    resumed : bool,
+    events : EventBus,
 }
 
 pub fn land_flying_chunks(play_table: &mut GameField, begin : usize) {
@@ -38,7 +56,7 @@ pub fn land_flying_chunks(play_table: &mut GameField, begin : usize) {
             let position = Point{x : chunk.position.x, y : chunk.position.y + iteration + 1};
             if check_for_collision(&position, chunk.into_iter(), play_table) {
                 let position = Point{x : chunk.position.x, y : chunk.position.y + iteration};
-                fill_field(&position, chunk.into_iter(), play_table);
+                fill_chunk(&position, chunk, play_table);
                 return false;
             }
          true
@@ -73,6 +91,7 @@ pub fn find_filled_lines(play_table: &GameField) -> Vec<usize> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::settings::Settings;
 
     #[test]
     fn test_find_filled_lines_1() {
@@ -132,6 +151,79 @@ mod tests {
         let result = find_filled_lines(&mut gamefield);
         assert_eq!(3, result.len());
     }
+
+    #[test]
+    fn test_restore_state() {
+        let data = GameData::new(Settings::default()).unwrap();
+        for saved in ["falling 0.25 - false", "falling 0 3 true", "fast_fall 0.01", "chunk_fall 19 2 0 1,2,T;1,3,T 5,5,O"] {
+            let state = restore_state(saved, &data).unwrap();
+            assert_eq!(Some(saved.to_string()), state.save());
+        }
+
+        // Saves from before the last rotation was kept count it as a move.
+        let old = restore_state("falling 0.25 -", &data).unwrap();
+        assert_eq!(Some("falling 0.25 - false".to_string()), old.save());
+
+        // Chunks saved before blocks kept their piece come back as plain blocks.
+        let old = restore_state("chunk_fall 19 2 0 1,2;1,3", &data).unwrap();
+        assert_eq!(Some("chunk_fall 19 2 0 1,2,O;1,3,O".to_string()), old.save());
+
+        assert!(restore_state("falling", &data).is_err());
+        assert!(restore_state("chunk_fall 19 2 0 1,2,X", &data).is_err());
+        assert!(restore_state("chunk_fall 19 2 0 1;2", &data).is_err());
+        assert!(restore_state("spinning 1", &data).is_err());
+    }
+
+    #[test]
+    fn test_half_rotation_at_wall() {
+        let data = initialize_tetraminoes_data();
+        let table = RotationCollisionTable::new();
+        let play_table : GameField = [TetrominoType::E; WIDTH * HEIGHT];
+        for piece in 0..TETRAMINOS_COUNT {
+            for rotation in 0..4 {
+                let cells = &data[piece].rotations[rotation];
+                let left = cells.into_iter().map(|cell| cell.x).min().unwrap();
+                let right = cells.into_iter().map(|cell| cell.x).max().unwrap();
+                for x in [-left, WIDTH as i32 - 1 - right] {
+                    let mut figure = Tetramino::new(piece);
+                    figure.set_rotation(rotation);
+                    figure.set_position(Point { x, y : HEIGHT as i32 / 2 });
+                    assert!(!check_for_collision(figure.get_position(), cells, &play_table));
+
+                    // Against a wall a half turn moves the piece a cell at most.
+                    let half = figure.peek_half_rotation();
+                    let kick = rotation_kick(&figure, half, &data, &table, &play_table).unwrap();
+                    assert!(kick.x.abs() <= 1 && (-1..=0).contains(&kick.y), "{} {} {:?}", piece, rotation, kick);
+                    let position = figure.get_position().add(&kick);
+                    assert!(!check_for_collision(&position, &data[piece].rotations[half], &play_table));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_restore_state_stack() {
+        let mut data = GameData::new(Settings::default()).unwrap();
+        for cell in data.play_table[(HEIGHT - 1) * WIDTH..].iter_mut() {
+            *cell = TetrominoType::I;
+        }
+        data.play_table[(HEIGHT - 2) * WIDTH] = TetrominoType::T;
+
+        let stacks = [
+            vec!["falling 0.5 2 true", "line_clearing 3 0.05"],
+            vec!["falling 0 - false", "line_clearing 5 0", "chunk_fall 19 1 0.01 0,18,T"],
+        ];
+        for stack in stacks {
+            let states = stack
+                .iter()
+                .map(|saved| restore_state(saved, &data))
+                .collect::<Result<Vec<Box<dyn State>>, Box<dyn error::Error>>>()
+                .unwrap();
+            let logic = StateMachine::from_stack(states).unwrap();
+            let saved : Vec<String> = stack.iter().map(|saved| saved.to_string()).collect();
+            assert_eq!(Some(saved), logic.save());
+        }
+    }
 }
 
 pub fn clear_play_table(play_table: &mut GameField, lines: Vec<usize>) {
@@ -165,20 +257,44 @@ pub fn score(data: &mut GameData) {
     data.add_score((lines_count*(lines_count + 1)) as u32 * score_multiplier);
 }
 
+pub fn clear_lines(play_table: &mut GameField) -> usize {
+    let mut lines_count = 0;
+    loop {
+        let lines = find_filled_lines(play_table);
+        if lines.is_empty() {
+            break;
+        }
+
+        lines_count += lines.len();
+        let chunk_begin : usize = *lines.last().unwrap();
+        clear_play_table(play_table, lines);
+        land_flying_chunks(play_table, chunk_begin);
+    }
+
+    lines_count
+}
+
 pub fn fill_field(
     position: &Point,
     sequence: impl IntoIterator<Item = Point>,
     game_field: &mut GameField,
+    kind: TetrominoType,
 ) {
     for element in sequence {
         let element_position = position.add(&element);
         if element_position.y >= 0 {
             let index = element_position.x as usize + (element_position.y as usize) * WIDTH;
-            game_field[index] = TetrominoType::O;
+            game_field[index] = kind;
         }
     }
 }
 
+pub fn fill_chunk(position: &Point, chunk: &Chunk, game_field: &mut GameField) {
+    for (element, kind) in chunk.elements.iter().zip(&chunk.kinds) {
+        fill_field(position, Some(*element), game_field, *kind);
+    }
+}
+
 pub fn check_for_collision(
     position: &Point,
     sequence: impl IntoIterator<Item = Point>,
@@ -211,131 +327,92 @@ pub fn check_for_collision(
     false
 }
 
-fn draw_play_field(
-    c: &Context,
-    g: &mut G2d,
-    _arguments: &RenderArgs,
-    _device: &mut gfx_device_gl::Device,
-    resources: &mut Resources,
-    data: &GameData,
-) {
-    let empty_block = &resources.empty_block;
-    let full_block = &resources.cube_block;
-    let blocks = &data.play_table;
-    let mut position_index: usize = 0;
-    blocks.iter().for_each(|block: &TetrominoType| {
-        let x = position_index % GAME_FIELD_WIDTH;
-        let y = (position_index / GAME_FIELD_WIDTH) * BLOCK_SIZE;
-        position_index += BLOCK_SIZE;
-        match block {
-            TetrominoType::E => {
-                image(empty_block, c.transform.trans(x as f64, y as f64), g);
-            }
-
-            _ => {
-                image(full_block, c.transform.trans(x as f64, y as f64), g);
-            }
+pub fn rotation_kick(
+    figure: &Tetramino,
+    next_rotation: usize,
+    tetraminoes_data: &TetraminoesData,
+    collision_table: &RotationCollisionTable,
+    game_field: &GameField,
+) -> Option<Point> {
+    let rotation = &tetraminoes_data[figure.get_type()].rotations[next_rotation];
+    let sequence = collision_table.collision_sequence(
+        figure.get_rotation(),
+        next_rotation,
+        figure.get_type(),
+    );
+
+    for point in sequence {
+        let new_position = figure.get_position().add(point);
+        if !check_for_collision(&new_position, rotation, game_field) {
+            return Some(*point);
         }
-    });
+    }
+
+    None
 }
 
-fn draw_score(
-    c: &Context,
-    g: &mut G2d,
-    _arguments: &RenderArgs,
-    device: &mut gfx_device_gl::Device,
-    resources: &mut Resources,
-    data: &GameData,
-) {
-    let score = data.score;
-    let level = data.dificulty;
-
-    text::Text::new_color([1.0, 1.0, 0.0, 1.0], 32)
-        .draw(
-            "Score : ",
-            &mut resources.font,
-            &c.draw_state,
-            c.transform
-                .trans(SCORE_TEXT_POSITION_X as f64, SCORE_TEXT_POSITION_Y as f64),
-            g,
-        )
-        .unwrap();
-
-    text::Text::new_color([1.0, 1.0, 0.0, 1.0], 16)
-        .draw(
-            &score.to_string(),
-            &mut resources.font,
-            &c.draw_state,
-            c.transform
-                .trans(SCORE_POSITION_X as f64, SCORE_POSITION_Y as f64),
-            g,
-        )
-        .unwrap();
-
-    text::Text::new_color([1.0, 1.0, 0.0, 1.0], 32)
-        .draw(
-            "Level : ",
-            &mut resources.font,
-            &c.draw_state,
-            c.transform
-                .trans(LEVEL_TEXT_POSITION_X as f64, LEVEL_TEXT_POSITION_Y as f64),
-            g,
-        )
-        .unwrap();
-
-    text::Text::new_color([1.0, 1.0, 0.0, 1.0], 16)
-        .draw(
-            &level.to_string(),
-            &mut resources.font,
-            &c.draw_state,
-            c.transform
-                .trans(LEVEL_POSITION_X as f64, LEVEL_POSITION_Y as f64),
-            g,
-        )
-        .unwrap();
-
-    resources.font.factory.encoder.flush(device);
+/// Three corner rule: a T figure that got into place by rotating is a T-spin
+/// when at least three of the cells diagonal to its center are blocked.
+pub fn is_tspin(figure: &Tetramino, game_field: &GameField) -> bool {
+    if figure.get_type() != TetrominoType::T as usize {
+        return false;
+    }
+
+    let center = figure.get_position();
+    let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+    let blocked = corners
+        .iter()
+        .filter(|(x, y)| {
+            let corner = Point { x: center.x + x, y: center.y + y };
+            corner.x < 0
+                || corner.x >= WIDTH as i32
+                || corner.y >= HEIGHT as i32
+                || (corner.y >= 0
+                    && !matches!(game_field[corner.y as usize * WIDTH + corner.x as usize], TetrominoType::E))
+        })
+        .count();
+
+    blocked >= 3
 }
 
-fn draw_preview(
-    c: &Context,
-    g: &mut G2d,
-    _arguments: &RenderArgs,
-    _device: &mut gfx_device_gl::Device,
-    resources: &mut Resources,
-    data: &GameData,
-) {
-    let full_block = &resources.cube_block;
-    let sequence = data.tetramino_preview_sequence();
-    let offset = data.tetramino_preview_offset();
-    sequence.iter().for_each(|position: &Point| {
-        let x =
-            PREVIEW_DEFAULT_POSITION_X + (position.x * BLOCK_SIZE as i32) as f64 + offset.x as f64;
-        let y =
-            PREVIEW_DEFAULT_POSITION_Y + (position.y * BLOCK_SIZE as i32) as f64 + offset.y as f64;
-        image(full_block, c.transform.trans(x as f64, y as f64), g);
-    });
+/// Draws `block` over the board cell at `x`, `y`.
+pub fn draw_cell(ctx: &mut dyn RenderContext, layout: &Layout, block: TextureHandle, x: i32, y: i32) {
+    ctx.draw_image_scaled(block, &layout.cell_position(x, y), layout.scale());
 }
 
-pub fn draw_current(
-    c: &Context,
-    g: &mut G2d,
-    _arguments: &RenderArgs,
-    _device: &mut gfx_device_gl::Device,
-    resources: &mut Resources,
-    data: &GameData,
-) {
+fn draw_play_field(ctx: &mut dyn RenderContext, resources: &Resources, data: &GameData) {
+    let layout = Layout::of(ctx);
+    for (index, block) in data.play_table.iter().enumerate() {
+        draw_cell(ctx, &layout, resources.block(*block), (index % WIDTH) as i32, (index / WIDTH) as i32);
+    }
+}
+
+fn draw_hud_message(ctx: &mut dyn RenderContext, resources: &Resources, data: &GameData) {
+    let message = match data.hud.message() {
+        Some(message) => message,
+        None => return,
+    };
+
+    let layout = Layout::of(ctx);
+    let transform = layout.board_point(HUD_MESSAGE_POSITION_X, HUD_MESSAGE_POSITION_Y);
+    let size = layout.text_size(HUD_MESSAGE_TEXT_SIZE);
+    ctx.draw_text(message, resources.font, size, &transform, &resources.colors.message);
+}
+
+pub fn draw_current(ctx: &mut dyn RenderContext, resources: &Resources, data: &GameData) {
+    let layout = Layout::of(ctx);
     let current = &data.current_figure;
     let type_index = current.get_type();
     let rotation_index = current.get_rotation();
-    let position = current.get_position();
     let rotation = &data.tetraminoes_data[type_index].rotations[rotation_index];
-    let full_block = &resources.cube_block;
+    let below = current.get_position().add(&Point { x: 0, y: 1 });
+    let can_fall = !check_for_collision(&below, rotation, &data.play_table);
+    let (x, y) = data.interpolation.position(current, can_fall);
+    let full_block = resources.blocks[type_index];
 
     rotation.into_iter().for_each(|offset: Point| {
-        let x = (position.x * BLOCK_SIZE as i32) as f64 + (offset.x * BLOCK_SIZE as i32) as f64;
-        let y = (position.y * BLOCK_SIZE as i32) as f64 + (offset.y * BLOCK_SIZE as i32) as f64;
-        image(full_block, c.transform.trans(x as f64, y as f64), g);
+        let transform = layout.cell_point(x + offset.x as f64, y + offset.y as f64);
+        ctx.draw_image_scaled(full_block, &transform, layout.scale());
     });
 }
 
@@ -344,10 +421,163 @@ impl PlayState {
         Ok(Box::new(PlayState {
             logic: StateMachine::new(FallingState::new()?)?,
             pause_event : false,
+            quit_event : false,
+            bot : None,
+            puzzle : None,
+            resumed : false,
+            events : game_events(),
+        }))
+    }
+
+    /// Continues the saved game, restoring `data` and the falling states.
+    pub fn resume(data : &mut GameData) -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        if !has_saved_game() {
+            return Err("no saved game".into());
+        }
+
+        let file = KeyValueFile::load(&save_path()?)?;
+        let states = read_game(&file, data)?
+            .iter()
+            .map(|state| restore_state(state, data))
+            .collect::<Result<Vec<Box<dyn State>>, Box<dyn error::Error>>>()?;
+        delete_saved_game()?;
+
+        Ok(Box::new(PlayState {
+            logic: StateMachine::from_stack(states)?,
+            pause_event : false,
+            quit_event : false,
+            bot : None,
+            puzzle : None,
+            resumed : true,
+            events : game_events(),
+        }))
+    }
+
+    fn mode(&self) -> GameMode {
+        if self.bot.is_some() {
+            GameMode::Demo
+        } else if self.puzzle.is_some() {
+            GameMode::Puzzle
+        } else {
+            GameMode::Marathon
+        }
+    }
+
+    fn save(&self, data : &GameData) -> Result<(), Box<dyn error::Error>> {
+        let states = self.logic.save().ok_or("the game cannot be saved right now")?;
+        write_game(data, &states).save(&save_path()?)
+    }
+
+    fn step(&mut self, data : &mut GameData, update_args : &UpdateArgs, event : Event) -> StateTransition {
+        data.emit(GameEvent::Tick { dt : update_args.dt });
+        if let Some(bot) = &mut self.bot {
+            for (action, state) in bot.update(update_args.dt, data) {
+                self.logic.handle_action(action, state, data);
+            }
+            // The keys count towards the piece before it can lock.
+            self.events.dispatch(data);
+        }
+
+        if let Some(puzzle) = &data.puzzle {
+            if let Some(solved) = puzzle.result {
+                let transition = StateTransition::Transition(PuzzleResult::new(&puzzle.name, solved).unwrap());
+                data.emit(GameEvent::GameOver { score : data.score });
+                return transition;
+            }
+        }
+
+        if data.save_requested {
+            data.save_requested = false;
+            return match self.save(data) {
+                Ok(()) => StateTransition::Transition(MainMenu::new().unwrap()),
+                Err(error) => StateTransition::Push(Pause::with_message(true, &error.to_string()).unwrap()),
+            };
+        }
+
+        if self.quit_event {
+            self.quit_event = false;
+            data.emit(GameEvent::GameOver { score : data.score });
+            StateTransition::Transition(MainMenu::new().unwrap())
+        } else if self.pause_event {
+            self.pause_event = false;
+            let can_save = self.bot.is_none() && data.puzzle.is_none();
+            StateTransition::Push(Pause::new(can_save).unwrap())
+        } else if self.logic.update(data, update_args, event) {
+            StateTransition::Hold
+        } else if let Some(puzzle) = &data.puzzle {
+            let transition = StateTransition::Transition(PuzzleResult::new(&puzzle.name, false).unwrap());
+            data.emit(GameEvent::GameOver { score : data.score });
+            transition
+        } else {
+            data.emit(GameEvent::GameOver { score : data.score });
+            // The statistics are final once everything that happened is in.
+            self.events.dispatch(data);
+            let lifetime = if self.mode().ranked() {
+                LifetimeStatistics::record(&data.statistics).map(Some)
+            } else {
+                Ok(None)
+            };
+            StateTransition::Transition(
+                ScoreScreen::new(
+                    self.mode(),
+                    data.score,
+                    data.total_lines,
+                    data.dificulty,
+                    data.finesse.clone(),
+                    data.statistics.clone(),
+                    lifetime,
+                )
+                .unwrap(),
+            )
+        }
+    }
+
+    pub fn demo() -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        Ok(Box::new(PlayState {
+            logic: StateMachine::new(FallingState::new()?)?,
+            pause_event : false,
+            quit_event : false,
+            bot : Some(Bot::new(Weights::default(), true)),
+            puzzle : None,
+            resumed : false,
+            events : game_events(),
+        }))
+    }
+
+    pub fn puzzle(puzzle : Puzzle) -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        Ok(Box::new(PlayState {
+            logic: StateMachine::new(FallingState::new()?)?,
+            pause_event : false,
+            quit_event : false,
+            bot : None,
+            puzzle : Some(puzzle),
+            resumed : false,
+            events : game_events(),
         }))
     }
 }
 
+fn game_events() -> EventBus {
+    let mut events = EventBus::new();
+    events.subscribe(Box::new(StatisticsListener));
+    events.subscribe(Box::new(AudioListener));
+    events.subscribe(Box::new(HudListener));
+    events
+}
+
+fn restore_state(text : &str, data : &GameData) -> Result<Box<dyn State>, Box<dyn error::Error>> {
+    let mut words = text.split_whitespace();
+    let name = words.next().ok_or("empty state")?;
+    let arguments : Vec<&str> = words.collect();
+    match name {
+        "falling" => FallingState::restore(&arguments),
+        "fast_fall" => FastFallingState::restore(&arguments),
+        "line_clearing" => LineClearing::restore(&arguments, data),
+        "chunk_fall" => ChunkFall::restore(&arguments),
+        _ => Err(format!("unknown state `{}`", name).into()),
+    }
+}
+
 impl State for PlayState {
     fn update(
         &mut self,
@@ -355,57 +585,83 @@ impl State for PlayState {
         update_args: &UpdateArgs,
         event: Event,
     ) -> StateTransition {
-        if self.pause_event {
-            self.pause_event = false;
-            StateTransition::Push(Pause::new().unwrap())
-        } else if self.logic.update(data, update_args, event) {
-            StateTransition::Hold
-        } else {
-            StateTransition::Transition(ScoreScreen::new(data.score, data.dificulty).unwrap())
-        }
+        let transition = self.step(data, update_args, event);
+        self.events.dispatch(data);
+        transition
     }
 
     fn handle_input(&mut self, input: Input, time: Option<TimeStamp>, data: &mut GameData) {
-        match input {
-            Input::Button(buttons) => match buttons.button {
-                Button::Keyboard(key) => match key {
-                    Key::Escape => {
-                        if buttons.state == ButtonState::Press {
-                            self.pause_event = true;
-                        }
-                    }
-                    _ => {}
-                },
-                _ => {}
-            },
-            _ => {}
+        if self.bot.is_some() {
+            if let Input::Button(buttons) = input {
+                if buttons.state == ButtonState::Press {
+                    self.quit_event = true;
+                }
+            }
+            return;
         }
 
         self.logic.handle_input(input, time, data);
     }
 
-    fn render(
-        &mut self,
-        c: Context,
-        g: &mut G2d,
-        arguments: &RenderArgs,
-        device: &mut gfx_device_gl::Device,
-        resources: &mut Resources,
-        data: &GameData,
-    ) {
-        clear([1.0; 4], g);
-        let background = &resources.background;
-        image(background, c.transform, g);
-        draw_play_field(&c, g, arguments, device, resources, data);
-        draw_score(&c, g, arguments, device, resources, data);
-        draw_preview(&c, g, arguments, device, resources, data);
-        // self.logic.render(c, g, arguments, device, resources, data);
+    fn handle_action(&mut self, action: Action, state: ButtonState, data: &mut GameData) {
+        if self.bot.is_some() {
+            return;
+        }
+
+        if action == Action::Pause && state == ButtonState::Press {
+            self.pause_event = true;
+        }
+        self.logic.handle_action(action, state, data);
+        // Inputs have to be counted before the piece they moved locks.
+        self.events.dispatch(data);
+    }
+
+    fn input_context(&self) -> InputContext {
+        InputContext::Gameplay
+    }
+
+    fn transition(&self) -> Option<Effect> {
+        Some(Effect::Wipe)
+    }
+
+    fn render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, data: &GameData) {
+        render_background(ctx, resources);
+        draw_play_field(ctx, resources, data);
+        draw_panel(ctx, resources, data, self.mode());
+        self.logic.render(ctx, resources, data);
+        draw_hud_message(ctx, resources, data);
     }
 
     fn enter(&mut self, data: &mut GameData) {
+        data.audio.start_music(&data.settings.audio);
+        if self.resumed {
+            return;
+        }
+
         data.dificulty = 0;
         data.score = 0;
-        data.play_table = [TetrominoType::E; WIDTH * HEIGHT];
-        data.current_figure = Tetramino::new(GameData::random_tetramino_index());
+        data.total_lines = 0;
+        data.spawn_count = 0;
+        data.finesse = Finesse::new();
+        data.statistics = GameStatistics::new();
+        data.hud = Hud::new();
+        data.queue.clear();
+        data.puzzle = None;
+        data.hold = None;
+        data.randomizer = Randomizer::new();
+
+        match &self.puzzle {
+            Some(puzzle) => data.start_puzzle(puzzle),
+            None => {
+                data.play_table = [TetrominoType::E; WIDTH * HEIGHT];
+                data.current_figure = Tetramino::new(data.randomizer.next_index());
+                data.next_figure = data.randomizer.next_index();
+            }
+        }
+        data.emit(GameEvent::PieceSpawned { piece : data.current_figure.get_type() });
+    }
+
+    fn exit(&mut self, data: &mut GameData) {
+        data.audio.stop_music();
     }
 }
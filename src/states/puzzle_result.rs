@@ -0,0 +1,65 @@
+use crate::states::state_machine::*;
+use crate::states::puzzle_select::*;
+use crate::game_data::*;
+use crate::input::Action;
+use crate::Resources;
+use crate::abstraction::abstraction_layer::*;
+use crate::layout::Layout;
+use piston_window::*;
+use std::error;
+
+pub struct PuzzleResult {
+    interact: bool,
+    name: String,
+    solved: bool,
+}
+
+impl PuzzleResult {
+    pub fn new(name : &str, solved : bool) -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        Ok(Box::new(PuzzleResult {
+            interact: false,
+            name: name.to_string(),
+            solved,
+        }))
+    }
+}
+
+impl State for PuzzleResult {
+    fn update(
+        &mut self,
+        _data: &mut GameData,
+        _update_args: &UpdateArgs,
+        _event: Event,
+    ) -> StateTransition {
+        if self.interact {
+            self.interact = false;
+            return StateTransition::Transition(PuzzleSelect::new().unwrap());
+        }
+        StateTransition::Hold
+    }
+
+    fn handle_action(&mut self, action: Action, state: ButtonState, _data: &mut GameData) {
+        if state == ButtonState::Press && (action == Action::Confirm || action == Action::Back) {
+            self.interact = true;
+        }
+    }
+    fn render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, _data: &GameData) {
+        let font = resources.font;
+        let title_color = resources.colors.title;
+        let layout = Layout::of(ctx);
+
+        render_background(ctx, resources);
+
+        ctx.draw_text(&self.name, font, layout.text_size(32), &layout.screen_point(0.0, 128.0), &title_color);
+
+        let (message, color) = if self.solved {
+            ("Puzzle solved", resources.colors.value)
+        } else {
+            ("Puzzle failed", resources.colors.error)
+        };
+        ctx.draw_text(message, font, layout.text_size(64), &layout.screen_point(0.0, 256.0), &color);
+
+        let help = "Press Enter to return to puzzle select";
+        ctx.draw_text(help, font, layout.text_size(32), &layout.screen_point(0.0, 384.0), &title_color);
+    }
+}
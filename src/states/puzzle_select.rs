@@ -0,0 +1,114 @@
+use crate::states::state_machine::*;
+use crate::states::main_menu::MainMenu;
+use crate::states::play::PlayState;
+use crate::puzzle::*;
+use crate::game_data::*;
+use crate::input::Action;
+use crate::Resources;
+use crate::abstraction::abstraction_layer::*;
+use crate::layout::Layout;
+use piston_window::*;
+use std::error;
+
+const ITEM_TEXT_SIZE: u32 = 48;
+const ITEM_SPACING: f64 = 80.0;
+const FIRST_ITEM_POSITION_Y: f64 = 128.0;
+const DETAIL_TEXT_SIZE: u32 = 24;
+
+pub struct PuzzleSelect {
+    puzzles: Vec<Puzzle>,
+    error: Option<String>,
+    selection: usize,
+    interact: bool,
+    back: bool,
+}
+
+impl PuzzleSelect {
+    pub fn new() -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        let (puzzles, error) = match all_puzzles() {
+            Ok(puzzles) => (puzzles, None),
+            Err(error) => (vec![], Some(error.to_string())),
+        };
+
+        Ok(Box::new(PuzzleSelect {
+            puzzles,
+            error,
+            selection: 0,
+            interact: false,
+            back: false,
+        }))
+    }
+}
+
+impl State for PuzzleSelect {
+    fn update(
+        &mut self,
+        _data: &mut GameData,
+        _update_args: &UpdateArgs,
+        _event: Event,
+    ) -> StateTransition {
+        if self.back {
+            self.back = false;
+            return StateTransition::Transition(MainMenu::new().unwrap());
+        }
+
+        if self.interact {
+            self.interact = false;
+            if let Some(puzzle) = self.puzzles.get(self.selection) {
+                return StateTransition::Transition(PlayState::puzzle(puzzle.clone()).unwrap());
+            }
+        }
+        StateTransition::Hold
+    }
+
+    fn handle_action(&mut self, action: Action, state: ButtonState, _data: &mut GameData) {
+        if state != ButtonState::Press {
+            return;
+        }
+
+        let count = self.puzzles.len();
+        match action {
+            Action::MenuUp if count > 0 => {
+                self.selection = (self.selection + count - 1) % count;
+            }
+            Action::MenuDown if count > 0 => {
+                self.selection = (self.selection + 1) % count;
+            }
+            Action::Confirm => {
+                self.interact = true;
+            }
+            Action::Back => {
+                self.back = true;
+            }
+            _ => {}
+        }
+    }
+    fn render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, _data: &GameData) {
+        let font = resources.font;
+        let layout = Layout::of(ctx);
+        let detail_size = layout.text_size(DETAIL_TEXT_SIZE);
+
+        render_background(ctx, resources);
+
+        if let Some(error) = &self.error {
+            let transform = layout.screen_point(0.0, FIRST_ITEM_POSITION_Y);
+            ctx.draw_text(error, font, detail_size, &transform, &resources.colors.error);
+        }
+
+        let size = layout.text_size(ITEM_TEXT_SIZE);
+        for (index, puzzle) in self.puzzles.iter().enumerate() {
+            let transform = layout.screen_point(0.0, FIRST_ITEM_POSITION_Y + index as f64 * ITEM_SPACING);
+            render_sized_text(&puzzle.name, size, resources, &transform, ctx, self.selection == index);
+        }
+
+        if let Some(puzzle) = self.puzzles.get(self.selection) {
+            let y = FIRST_ITEM_POSITION_Y + self.puzzles.len() as f64 * ITEM_SPACING;
+            let goal = match &puzzle.goal {
+                Some(goal) => goal.description(),
+                None => String::from("Free play"),
+            };
+            let details = format!("{} with {} pieces", goal, puzzle.pieces.len());
+            ctx.draw_text(&details, font, detail_size, &layout.screen_point(0.0, y), &resources.colors.title);
+        }
+    }
+}
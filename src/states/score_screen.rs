@@ -1,24 +1,114 @@
 use crate::states::state_machine::*;
+use crate::states::transition::Effect;
 use crate::states::main_menu::*;
+use crate::states::menu_mouse::*;
 use crate::game_data::*;
+use crate::input::Action;
+use crate::finesse::Finesse;
+use crate::high_scores::*;
+use crate::statistics::*;
+use crate::tetramino::TETRAMINO_NAMES;
 use crate::Resources;
+use crate::abstraction::abstraction_layer::*;
+use crate::layout::Layout;
 use piston_window::*;
 use std::error;
 
+const STATISTICS_TEXT_SIZE: u32 = 28;
+const STATISTICS_POSITION_Y: f64 = 500.0;
+const STATISTICS_LINE_SPACING: f64 = 50.0;
+const HIGH_SCORE_POSITION_Y: f64 = 900.0;
+const RETURN_TEXT: &str = "Press Enter to return to main menu";
+const RETURN_TEXT_SIZE: u32 = 32;
+const RETURN_POSITION_Y: f64 = 800.0;
+
 pub struct ScoreScreen {
     interact: bool,
+    mode : GameMode,
     score : u32,
+    lines : usize,
     level : u32,
+    finesse : Finesse,
+    statistics : GameStatistics,
+    lifetime : Option<LifetimeStatistics>,
+    name : Option<String>,
+    message : Option<String>,
+    mouse : MenuMouse,
 }
 
 impl ScoreScreen {
-    pub fn new(score : u32, level : u32) -> Result<Box<dyn State>, Box<dyn error::Error>> {
+    pub fn new(
+        mode : GameMode,
+        score : u32,
+        lines : usize,
+        level : u32,
+        finesse : Finesse,
+        statistics : GameStatistics,
+        lifetime : Result<Option<LifetimeStatistics>, Box<dyn error::Error>>,
+    ) -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        let (mut name, mut message) = match HighScores::load() {
+            Ok(high_scores) if high_scores.qualifies(mode, score) => (Some(String::new()), None),
+            Ok(_) => (None, None),
+            Err(error) => (None, Some(error.to_string())),
+        };
+
+        // Totals that could not be saved leave little hope for the score.
+        let lifetime = lifetime.unwrap_or_else(|error| {
+            name = None;
+            message = Some(error.to_string());
+            None
+        });
+
         Ok(Box::new(ScoreScreen {
             interact: false,
+            mode,
             score,
-            level
+            lines,
+            level,
+            finesse,
+            statistics,
+            lifetime,
+            name,
+            message,
+            mouse: MenuMouse::new(),
         }))
     }
+
+    fn confirm(&mut self) {
+        if self.name.is_some() {
+            self.submit_name();
+        } else {
+            self.interact = true;
+        }
+    }
+
+    fn submit_name(&mut self) {
+        let name = match self.name.take() {
+            Some(name) if !name.trim().is_empty() => name.trim().to_string(),
+            name => {
+                self.name = name;
+                return;
+            }
+        };
+
+        let entry = HighScore {
+            name,
+            score : self.score,
+            lines : self.lines,
+            level : self.level,
+            date : today(),
+        };
+        let saved = HighScores::load().and_then(|mut high_scores| {
+            let rank = high_scores.insert(self.mode, entry);
+            high_scores.save()?;
+            Ok(rank)
+        });
+        self.message = Some(match saved {
+            Ok(Some(rank)) => format!("Rank {} in {}", rank + 1, self.mode.title()),
+            Ok(None) => String::from("Score did not make the table"),
+            Err(error) => error.to_string(),
+        });
+    }
 }
 
 impl State for ScoreScreen {
@@ -36,98 +126,130 @@ impl State for ScoreScreen {
     }
 
     fn handle_input(&mut self, input: Input, _time: Option<TimeStamp>, _data: &mut GameData) {
-        match input {
-            Input::Button(buttons) => {
-                if buttons.state == ButtonState::Press {
-                    match buttons.button {
-                        Button::Keyboard(key) => match key {
-                            Key::Return => {
-                                self.interact = true;
-                            }
-
-                            Key::Escape => {
-                                self.interact = true;
-                            }
-                            _ => {}
-                        },
-                        _ => {}
+        if let Some(MouseEvent::Click(_)) = self.mouse.handle_input(&input) {
+            self.confirm();
+        }
+
+        if let Some(name) = &mut self.name {
+            match input {
+                Input::Text(text) => {
+                    for character in text.chars().filter(|character| valid_name_character(*character)) {
+                        if name.chars().count() < MAX_NAME_LENGTH {
+                            name.push(character);
+                        }
                     }
                 }
+
+                Input::Button(ButtonArgs {
+                    state: ButtonState::Press,
+                    button: Button::Keyboard(Key::Backspace),
+                    ..
+                }) => {
+                    name.pop();
+                }
+                _ => {}
             }
+        }
+    }
+
+    fn handle_action(&mut self, action: Action, state: ButtonState, _data: &mut GameData) {
+        if state != ButtonState::Press {
+            return;
+        }
+
+        match action {
+            Action::Confirm => self.confirm(),
+            Action::Back if self.name.is_some() => self.name = None,
+            Action::Back => self.interact = true,
             _ => {}
         }
     }
+    fn transition(&self) -> Option<Effect> {
+        Some(Effect::Slide)
+    }
 
-    fn render(
-        &mut self,
-        c: Context,
-        g: &mut G2d,
-        _arguments: &RenderArgs,
-        _device: &mut gfx_device_gl::Device,
-        resources: &mut Resources,
-        _data: &GameData,
-    ) {
-        let texture = &resources.background;
-        let font = &mut resources.font;
-
-        clear([1.0; 4], g);
-        image(texture, c.transform, g);
-
-        text::Text::new_color([0.2, 0.8, 0.3, 1.0], 32)
-            .draw(
-                "Score : ",
-                font,
-                &c.draw_state,
-                c.transform
-                    .trans(0 as f64, 128 as f64),
-                g,
-            )
-            .unwrap();
-
-        text::Text::new_color([0.0, 1.0, 0.0, 1.0], 32)
-            .draw(
-                &self.score.to_string(),
-                font,
-                &c.draw_state,
-                c.transform
-                    .trans(128 as f64, 128 as f64),
-                g,
-            )
-            .unwrap();
-
-        text::Text::new_color([0.2, 0.8, 0.3, 1.0], 32)
-            .draw(
-                "Level : ",
-                font,
-                &c.draw_state,
-                c.transform
-                    .trans(0 as f64, 228 as f64),
-                g,
-            )
-            .unwrap();
-
-        text::Text::new_color([0.0, 1.0, 0.0, 1.0], 32)
-            .draw(
-                &self.level.to_string(),
-                font,
-                &c.draw_state,
-                c.transform
-                    .trans(128 as f64, 228 as f64),
-                g,
-            )
-            .unwrap();
-
-        text::Text::new_color([0.2, 0.8, 0.3, 1.0], 32)
-            .draw(
-                "Press Enter to return to main menu",
-                font,
-                &c.draw_state,
-                c.transform
-                    .trans(0 as f64, 328 as f64),
-                g,
-            )
-            .unwrap();
-
-        font.factory.encoder.flush(_device);
+    fn render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, _data: &GameData) {
+        let font = resources.font;
+        let label_color = resources.colors.title;
+        let value_color = resources.colors.value;
+        let layout = Layout::of(ctx);
+        let size = layout.text_size(32);
+
+        render_background(ctx, resources);
+
+        ctx.draw_text("Score : ", font, size, &layout.screen_point(0.0, 128.0), &label_color);
+        ctx.draw_text(&self.score.to_string(), font, size, &layout.screen_point(128.0, 128.0), &value_color);
+
+        ctx.draw_text("Level : ", font, size, &layout.screen_point(0.0, 228.0), &label_color);
+        ctx.draw_text(&self.level.to_string(), font, size, &layout.screen_point(128.0, 228.0), &value_color);
+
+        ctx.draw_text("Finesse : ", font, size, &layout.screen_point(0.0, 328.0), &label_color);
+
+        let finesse = format!(
+            "{:.0}% ({} errors)",
+            self.finesse.percentage(),
+            self.finesse.total_errors()
+        );
+        ctx.draw_text(&finesse, font, size, &layout.screen_point(128.0, 328.0), &value_color);
+
+        let errors = self.finesse.errors_by_piece();
+        let per_piece = TETRAMINO_NAMES
+            .iter()
+            .zip(errors.iter())
+            .map(|(name, count)| format!("{} {}", name, count))
+            .collect::<Vec<String>>()
+            .join("  ");
+        ctx.draw_text(&per_piece, font, size, &layout.screen_point(0.0, 428.0), &value_color);
+
+        let statistics = &self.statistics;
+        let mut lines = vec![
+            format!(
+                "Pieces {}  PPS {:.2}  KPP {:.2}  Time {}",
+                statistics.pieces,
+                statistics.pieces_per_second(),
+                statistics.keys_per_piece(),
+                format_time(statistics.time)
+            ),
+            format!(
+                "Singles {}  Doubles {}  Triples {}  Tetrises {}",
+                statistics.clears[0], statistics.clears[1], statistics.clears[2], statistics.clears[3]
+            ),
+            format!("T-spins {}  Max combo {}", statistics.tspins, statistics.max_combo),
+            TETRAMINO_NAMES
+                .iter()
+                .zip(statistics.distribution.iter())
+                .map(|(name, count)| format!("{} {}", name, count))
+                .collect::<Vec<String>>()
+                .join("  "),
+        ];
+        if let Some(lifetime) = &self.lifetime {
+            lines.push(format!(
+                "Lifetime : {} games  {} pieces  {} lines  {}",
+                lifetime.games,
+                lifetime.totals.pieces,
+                lifetime.totals.lines(),
+                format_time(lifetime.totals.time)
+            ));
+        }
+        for (index, line) in lines.iter().enumerate() {
+            let y = STATISTICS_POSITION_Y + index as f64 * STATISTICS_LINE_SPACING;
+            ctx.draw_text(line, font, layout.text_size(STATISTICS_TEXT_SIZE), &layout.screen_point(0.0, y), &value_color);
+        }
+
+        let high_score = match (&self.name, &self.message) {
+            (Some(name), _) => Some(format!("New high score! Name : {}_", name)),
+            (None, Some(message)) => Some(message.clone()),
+            (None, None) => None,
+        };
+        if let Some(high_score) = high_score {
+            let transform = layout.screen_point(0.0, HIGH_SCORE_POSITION_Y);
+            ctx.draw_text(&high_score, font, size, &transform, &resources.colors.text);
+        }
+
+        let hovered = self.mouse.hovered().is_some();
+        let return_size = layout.text_size(RETURN_TEXT_SIZE);
+        let transform = layout.screen_point(0.0, RETURN_POSITION_Y);
+        render_sized_text(RETURN_TEXT, return_size, resources, &transform, ctx, hovered);
+        self.mouse.set_item(0, text_bounds(ctx, RETURN_TEXT, return_size, font, transform.x, transform.y));
     }
 }
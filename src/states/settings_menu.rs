@@ -0,0 +1,372 @@
+use crate::states::state_machine::*;
+use crate::states::main_menu::MainMenu;
+use crate::settings::*;
+use crate::game_data::*;
+use crate::input::*;
+use crate::theme::available_themes;
+use crate::Resources;
+use crate::abstraction::abstraction_layer::*;
+use crate::layout::Layout;
+use piston_window::*;
+use std::error;
+
+const ITEM_TEXT_SIZE: u32 = 24;
+const FIRST_ITEM_POSITION_Y: f64 = 64.0;
+const ITEM_SPACING: f64 = 44.0;
+const VALUE_POSITION_X: f64 = 512.0;
+const INTERVAL_STEP: f64 = 0.01;
+const WINDOW_STEP: u32 = 64;
+const MIN_WINDOW_SIZE: u32 = 512;
+const MAX_WINDOW_SIZE: u32 = 4096;
+const VOLUME_STEP: u32 = 10;
+const DEADZONE_STEP: f64 = 0.05;
+const TRANSITION_STEP: f64 = 0.05;
+const MAX_TRANSITION_TIME: f64 = 1.0;
+const HELP_TEXT_SIZE: u32 = 20;
+const HELP_POSITION_Y: f64 = 1240.0;
+// The rows that fit above the help line, the list scrolls past them.
+const VISIBLE_ITEMS: usize = ((HELP_POSITION_Y - FIRST_ITEM_POSITION_Y) / ITEM_SPACING) as usize;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Item {
+    Binding(Action),
+    GravityInterval,
+    ControlInterval,
+    Deadzone,
+    WindowWidth,
+    WindowHeight,
+    Theme,
+    TransitionTime,
+    MasterVolume,
+    MusicVolume,
+    EffectsVolume,
+    HoldEnabled,
+    PreviewEnabled,
+    Back,
+}
+
+const OTHER_ITEMS: [Item; 13] = [
+    Item::GravityInterval,
+    Item::ControlInterval,
+    Item::Deadzone,
+    Item::WindowWidth,
+    Item::WindowHeight,
+    Item::Theme,
+    Item::TransitionTime,
+    Item::MasterVolume,
+    Item::MusicVolume,
+    Item::EffectsVolume,
+    Item::HoldEnabled,
+    Item::PreviewEnabled,
+    Item::Back,
+];
+
+impl Item {
+    fn label(self) -> &'static str {
+        match self {
+            Item::Binding(action) => action.label(),
+            Item::GravityInterval => "Gravity interval",
+            Item::ControlInterval => "Move interval",
+            Item::Deadzone => "Stick deadzone",
+            Item::WindowWidth => "Window width",
+            Item::WindowHeight => "Window height",
+            Item::Theme => "Theme",
+            Item::TransitionTime => "Transitions",
+            Item::MasterVolume => "Master volume",
+            Item::MusicVolume => "Music volume",
+            Item::EffectsVolume => "Effects volume",
+            Item::HoldEnabled => "Hold piece",
+            Item::PreviewEnabled => "Next preview",
+            Item::Back => "Back",
+        }
+    }
+}
+
+fn on_off(value: bool) -> String {
+    String::from(if value { "On" } else { "Off" })
+}
+
+fn step_interval(value: f64, direction: i32) -> f64 {
+    let value = value + INTERVAL_STEP * direction as f64;
+    (value / INTERVAL_STEP).round().clamp(1.0, 100.0) * INTERVAL_STEP
+}
+
+fn step_deadzone(value: f64, direction: i32) -> f64 {
+    let value = value + DEADZONE_STEP * direction as f64;
+    (value / DEADZONE_STEP).round().clamp(1.0, 19.0) * DEADZONE_STEP
+}
+
+fn input_names<T>(inputs: &[T], name: fn(&T) -> String) -> String {
+    if inputs.is_empty() {
+        return String::from("-");
+    }
+    let names: Vec<String> = inputs.iter().map(name).collect();
+    names.join(", ")
+}
+
+fn step_window(value: u32, direction: i32) -> u32 {
+    let value = value as i64 + (WINDOW_STEP as i64) * direction as i64;
+    value.clamp(MIN_WINDOW_SIZE as i64, MAX_WINDOW_SIZE as i64) as u32
+}
+
+fn step_theme(themes: &[String], current: &str, direction: i32) -> Option<String> {
+    if themes.is_empty() {
+        return None;
+    }
+    let count = themes.len() as i32;
+    let index = themes.iter().position(|name| name == current).unwrap_or(0) as i32;
+    Some(themes[(index + direction).rem_euclid(count) as usize].clone())
+}
+
+fn step_transition(value: f64, direction: i32) -> f64 {
+    let value = value + TRANSITION_STEP * direction as f64;
+    (value / TRANSITION_STEP).round().clamp(0.0, MAX_TRANSITION_TIME / TRANSITION_STEP) * TRANSITION_STEP
+}
+
+fn step_volume(value: u32, direction: i32) -> u32 {
+    let value = value as i64 + (VOLUME_STEP as i64) * direction as i64;
+    value.clamp(0, MAX_VOLUME as i64) as u32
+}
+
+pub struct SettingsMenu {
+    items: Vec<Item>,
+    themes: Vec<String>,
+    selection: usize,
+    /// The first row drawn.
+    scroll: usize,
+    rebinding: bool,
+    captured: bool,
+    controller: Option<i32>,
+    back: bool,
+    return_to_menu: bool,
+}
+
+impl SettingsMenu {
+    /// Opened from the main menu it returns there, otherwise it pops back to
+    /// the state below it.
+    pub fn new(return_to_menu: bool) -> Result<Box<dyn State>, Box<dyn error::Error>> {
+        let items = ACTIONS
+            .iter()
+            .map(|action| Item::Binding(*action))
+            .chain(OTHER_ITEMS.iter().cloned())
+            .collect();
+
+        Ok(Box::new(SettingsMenu {
+            items,
+            themes: available_themes(),
+            selection: 0,
+            scroll: 0,
+            rebinding: false,
+            captured: false,
+            controller: None,
+            back: false,
+            return_to_menu,
+        }))
+    }
+
+    fn value(&self, item: Item, settings: &Settings) -> String {
+        match item {
+            Item::Binding(_) if self.rebinding && self.items[self.selection] == item => {
+                String::from("Press a key or button")
+            }
+            Item::Binding(action) => {
+                let pad = match self.controller {
+                    Some(id) => settings.gamepad.bindings(id),
+                    None => &settings.gamepad.default,
+                };
+                format!(
+                    "{} | {}",
+                    input_names(settings.bindings.inputs(action), |key| key_name(*key)),
+                    input_names(pad.inputs(action), |input| input.name())
+                )
+            }
+            Item::GravityInterval => format!("{:.2} s", settings.handling.gravity_interval),
+            Item::ControlInterval => format!("{:.2} s", settings.handling.control_interval),
+            Item::Deadzone => format!("{:.2}", settings.gamepad.deadzone),
+            Item::WindowWidth => format!("{} (restart)", settings.visuals.window_width),
+            Item::WindowHeight => format!("{} (restart)", settings.visuals.window_height),
+            Item::Theme => settings.visuals.theme.clone(),
+            Item::TransitionTime if settings.visuals.transition_time <= 0.0 => on_off(false),
+            Item::TransitionTime => format!("{:.2} s", settings.visuals.transition_time),
+            Item::MasterVolume => settings.audio.master_volume.to_string(),
+            Item::MusicVolume => settings.audio.music_volume.to_string(),
+            Item::EffectsVolume => settings.audio.effects_volume.to_string(),
+            Item::HoldEnabled => on_off(settings.gameplay.hold_enabled),
+            Item::PreviewEnabled => on_off(settings.gameplay.preview_enabled),
+            _ => String::new(),
+        }
+    }
+
+    fn adjust(&mut self, settings: &mut Settings, direction: i32) {
+        match self.items[self.selection] {
+            Item::GravityInterval => {
+                settings.handling.gravity_interval = step_interval(settings.handling.gravity_interval, direction);
+            }
+            Item::ControlInterval => {
+                settings.handling.control_interval = step_interval(settings.handling.control_interval, direction);
+            }
+            Item::Deadzone => {
+                settings.gamepad.deadzone = step_deadzone(settings.gamepad.deadzone, direction);
+            }
+            Item::WindowWidth => {
+                settings.visuals.window_width = step_window(settings.visuals.window_width, direction);
+            }
+            Item::WindowHeight => {
+                settings.visuals.window_height = step_window(settings.visuals.window_height, direction);
+            }
+            Item::Theme => {
+                if let Some(theme) = step_theme(&self.themes, &settings.visuals.theme, direction) {
+                    settings.visuals.theme = theme;
+                }
+            }
+            Item::TransitionTime => {
+                settings.visuals.transition_time = step_transition(settings.visuals.transition_time, direction);
+            }
+            Item::MasterVolume => {
+                settings.audio.master_volume = step_volume(settings.audio.master_volume, direction);
+            }
+            Item::MusicVolume => {
+                settings.audio.music_volume = step_volume(settings.audio.music_volume, direction);
+            }
+            Item::EffectsVolume => {
+                settings.audio.effects_volume = step_volume(settings.audio.effects_volume, direction);
+            }
+            Item::HoldEnabled => settings.gameplay.hold_enabled = !settings.gameplay.hold_enabled,
+            Item::PreviewEnabled => settings.gameplay.preview_enabled = !settings.gameplay.preview_enabled,
+            _ => {}
+        }
+    }
+
+    fn interact(&mut self, settings: &mut Settings) {
+        match self.items[self.selection] {
+            Item::Back => self.back = true,
+            Item::Binding(_) => self.rebinding = true,
+            _ => self.adjust(settings, 1),
+        }
+    }
+
+    fn scroll_to_selection(&mut self) {
+        if self.selection < self.scroll {
+            self.scroll = self.selection;
+        } else if self.selection >= self.scroll + VISIBLE_ITEMS {
+            self.scroll = self.selection + 1 - VISIBLE_ITEMS;
+        }
+    }
+}
+
+impl State for SettingsMenu {
+    fn update(
+        &mut self,
+        data: &mut GameData,
+        _update_args: &UpdateArgs,
+        _event: Event,
+    ) -> StateTransition {
+        if !self.back {
+            return StateTransition::Hold;
+        }
+
+        self.back = false;
+        if let Err(error) = data.settings.save() {
+            data.notice.show(error.to_string());
+        }
+
+        if self.return_to_menu {
+            StateTransition::Transition(MainMenu::new().unwrap())
+        } else {
+            StateTransition::Pop
+        }
+    }
+
+    // Capture reads raw input so that anything can be bound, including the
+    // keys and buttons currently used to navigate the menu.
+    fn handle_input(&mut self, input: Input, _time: Option<TimeStamp>, data: &mut GameData) {
+        self.captured = false;
+        let action = match self.items[self.selection] {
+            Item::Binding(action) => action,
+            _ => return,
+        };
+
+        let settings = &mut data.settings;
+        if let Some((id, pad_input)) = captured_pad_input(&input, settings.gamepad.deadzone) {
+            self.controller = Some(id);
+            if self.rebinding {
+                self.rebinding = false;
+                self.captured = true;
+                settings.gamepad.bindings_mut(id).bind(action, pad_input);
+            }
+            return;
+        }
+
+        let key = match input {
+            Input::Button(ButtonArgs {
+                state: ButtonState::Press,
+                button: Button::Keyboard(key),
+                ..
+            }) => key,
+            _ => return,
+        };
+
+        if self.rebinding {
+            self.rebinding = false;
+            self.captured = true;
+            if key != Key::Escape {
+                settings.bindings.bind(action, key);
+            }
+        } else if key == Key::Backspace {
+            settings.bindings.unbind_last(action);
+        } else if key == Key::Delete {
+            let pad = match self.controller {
+                Some(id) => settings.gamepad.bindings_mut(id),
+                None => &mut settings.gamepad.default,
+            };
+            pad.unbind_last(action);
+        }
+    }
+
+    fn handle_action(&mut self, action: Action, state: ButtonState, data: &mut GameData) {
+        // The key that finished a capture must not also drive the menu.
+        if state != ButtonState::Press || self.rebinding || self.captured {
+            return;
+        }
+
+        let settings = &mut data.settings;
+        let count = self.items.len();
+        match action {
+            Action::MenuUp => {
+                self.selection = (self.selection + count - 1) % count;
+                self.scroll_to_selection();
+            }
+            Action::MenuDown => {
+                self.selection = (self.selection + 1) % count;
+                self.scroll_to_selection();
+            }
+            Action::MenuLeft => self.adjust(settings, -1),
+            Action::MenuRight => self.adjust(settings, 1),
+            Action::Confirm => self.interact(settings),
+            Action::Back => self.back = true,
+            _ => {}
+        }
+        data.audio.update_volume(&data.settings.audio);
+    }
+
+    fn render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, data: &GameData) {
+        let layout = Layout::of(ctx);
+        render_background(ctx, resources);
+
+        let size = layout.text_size(ITEM_TEXT_SIZE);
+        let visible = self.items.iter().enumerate().skip(self.scroll).take(VISIBLE_ITEMS);
+        for (row, (index, item)) in visible.enumerate() {
+            let y = FIRST_ITEM_POSITION_Y + row as f64 * ITEM_SPACING;
+            let selected = self.selection == index;
+            render_sized_text(item.label(), size, resources, &layout.screen_point(0.0, y), ctx, selected);
+            let value = self.value(*item, &data.settings);
+            render_sized_text(&value, size, resources, &layout.screen_point(VALUE_POSITION_X, y), ctx, selected);
+        }
+
+        if let Item::Binding(_) = self.items[self.selection] {
+            let help = "Enter: add binding   Backspace: remove key   Delete: remove button";
+            let transform = layout.screen_point(0.0, HELP_POSITION_Y);
+            render_sized_text(help, layout.text_size(HELP_TEXT_SIZE), resources, &transform, ctx, false);
+        }
+    }
+}
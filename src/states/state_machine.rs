@@ -1,11 +1,13 @@
 use crate::game_data::*;
+use crate::input::{Action, InputContext};
 use crate::Resources;
-use math::Matrix2d;
 use piston_window::*;
 use std::error;
+use std::str::FromStr;
 use std::vec::Vec;
-use crate::abstraction::piston_abstraction::*;
-use crate::abstraction::abstraction_layer::{RenderContext, Transform};
+use crate::abstraction::abstraction_layer::*;
+use crate::layout::Layout;
+use crate::states::transition::{Animation, Change, Effect, Picture};
 
 pub enum StateTransition {
     Push(Box<dyn State>),
@@ -22,45 +24,104 @@ pub trait State {
         event: Event,
     ) -> StateTransition;
 
-    fn handle_input(&mut self, input: Input, time: Option<TimeStamp>, _data: &mut GameData);
+    fn handle_input(&mut self, _input: Input, _time: Option<TimeStamp>, _data: &mut GameData) {}
 
-    fn background_render(
-        &mut self,
-        _c: Context,
-        _g: &mut G2d,
-        _arguments: &RenderArgs,
-        _device: &mut gfx_device_gl::Device,
-        _resources: &mut Resources,
-        _data: &GameData,
-    ) {}
-
-    fn render(
-        &mut self,
-        _c: Context,
-        _g: &mut G2d,
-        _arguments: &RenderArgs,
-        _device: &mut gfx_device_gl::Device,
-        _resources: &mut Resources,
-        _data: &GameData,
-    ) {}
+    /// Called after `handle_input` for every action the input pressed or
+    /// released.
+    fn handle_action(&mut self, _action: Action, _state: ButtonState, _data: &mut GameData) {}
+
+    /// Which of the bound actions the state is sent.
+    fn input_context(&self) -> InputContext {
+        InputContext::Menu
+    }
+
+    /// Draws the state while another one is on top of it. The state is
+    /// frozen then, so by default it looks as it did when it was on top.
+    fn background_render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, data: &GameData) {
+        self.render(ctx, resources, data);
+    }
+
+    fn render(&mut self, _ctx: &mut dyn RenderContext, _resources: &Resources, _data: &GameData) {}
+
+    /// States returning an overlay are drawn over the states below them
+    /// instead of hiding them.
+    fn overlay(&self) -> Option<Overlay> {
+        None
+    }
+
+    /// Played when the state is pushed or replaces another, and backwards
+    /// when it is popped.
+    fn transition(&self) -> Option<Effect> {
+        None
+    }
 
     fn enter(&mut self, _data: &mut GameData) {}
     fn exit(&mut self, _data: &mut GameData) {}
     fn pause(&mut self, _data: &mut GameData) {}
     fn resume(&mut self, _data: &mut GameData) {}
+
+    /// One line describing the state for a saved game, starting with the
+    /// state name. States returning `None` cannot be saved.
+    fn save(&self) -> Option<String> {
+        None
+    }
+}
+
+/// What an overlay does to the states under it before it is drawn.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Overlay {
+    /// How much darker they get, from 0 to 1.
+    pub dim: f32,
+    /// Blur radius in pixels.
+    pub blur: u32,
+}
+
+impl Overlay {
+    /// Draws on top without touching what is below.
+    pub const CLEAR: Overlay = Overlay { dim: 0.0, blur: 0 };
+
+    fn apply(&self, ctx: &mut dyn RenderContext) {
+        if self.blur > 0 {
+            ctx.blur(self.blur);
+        }
+        if self.dim > 0.0 {
+            let window = Layout::of(ctx).window;
+            ctx.draw_rect(&window, &Color::new(0.0, 0.0, 0.0, self.dim));
+        }
+    }
 }
 
 pub struct StateMachine {
     stack: Vec<Box<dyn State>>,
+    animation: Option<Animation>,
 }
 
 impl StateMachine {
     pub fn new(initial_state: Box<dyn State>) -> Result<StateMachine, Box<dyn error::Error>> {
-        let stack = vec![initial_state];
-        Ok(StateMachine { stack })
+        StateMachine::from_stack(vec![initial_state])
     }
 
+    pub fn from_stack(stack: Vec<Box<dyn State>>) -> Result<StateMachine, Box<dyn error::Error>> {
+        if stack.is_empty() {
+            return Err("empty state stack".into());
+        }
+        Ok(StateMachine { stack, animation: None })
+    }
+
+    /// Saved form of every state from the bottom of the stack up.
+    pub fn save(&self) -> Option<Vec<String>> {
+        self.stack.iter().map(|state| state.save()).collect()
+    }
+
+    /// States are frozen while a transition plays.
     pub fn update(&mut self, data: &mut GameData, update_args: &UpdateArgs, event: Event) -> bool {
+        if let Some(animation) = &mut self.animation {
+            if animation.advance(update_args.dt) {
+                return true;
+            }
+            self.animation = None;
+        }
+
         let transition = if let Some(top) = self.stack.last_mut() {
             top.update(data, update_args, event)
         } else {
@@ -70,23 +131,32 @@ impl StateMachine {
         match transition {
             StateTransition::Push(mut pushed_state) => {
                 pushed_state.enter(data);
+                let effect = pushed_state.transition();
                 self.stack.push(pushed_state);
+                self.animate(effect, Change::Pushed, None, data);
             }
 
             StateTransition::Transition(mut transition) => {
                 let mut top = self.stack.pop().unwrap();
                 top.exit( data);
                 transition.enter(data);
+                let effect = transition.transition();
                 self.stack.push(transition);
+                self.animate(effect, Change::Replaced, Some(top), data);
             }
 
             StateTransition::Pop => {
                 let stack = &mut self.stack;
-                if let Some(mut top) = stack.pop() {
+                let popped = stack.pop();
+                if let Some(mut top) = popped {
                     top.exit(data);
-                }
-                if let Some(top) = stack.last_mut() {
-                    top.resume(data);
+                    if let Some(top) = stack.last_mut() {
+                        top.resume(data);
+                    }
+                    // Nothing is left to animate to when the last state goes.
+                    if !self.stack.is_empty() {
+                        self.animate(top.transition(), Change::Popped, Some(top), data);
+                    }
                 }
             }
 
@@ -96,57 +166,190 @@ impl StateMachine {
         !self.stack.is_empty()
     }
 
+    fn animate(&mut self, effect: Option<Effect>, change: Change, outgoing: Option<Box<dyn State>>, data: &GameData) {
+        let duration = data.settings.visuals.transition_time;
+        self.animation = effect
+            .filter(|_| duration > 0.0)
+            .map(|effect| Animation::new(effect, change, outgoing, duration));
+    }
+
+    pub fn animating(&self) -> bool {
+        self.animation.is_some()
+    }
+
     pub fn handle_input(&mut self, input: Input, time: Option<TimeStamp>, data: &mut GameData) {
+        if self.animating() {
+            return;
+        }
         if let Some(top) = self.stack.last_mut() {
             top.handle_input(input, time, data);
         }
     }
 
-    // pub fn render(
-    //     &mut self,
-    //     c: Context,
-    //     g: &mut G2d,
-    //     arguments: &RenderArgs,
-    //     device: &mut gfx_device_gl::Device,
-    //     resources: &mut Resources,
-    //     data: &GameData,
-    // ) {
-    //     self.stack.iter_mut().for_each(|state| {
-    //         state.background_render(c, g, arguments, device, resources, data);
-    //     });
-    //     if let Some(top) = self.stack.last_mut() {
-    //         top.render(c, g, arguments, device, resources, data);
-    //     }
-    // }
-
-    pub fn render(
-        &self,
-        ctx : &mut PistonRenderContext,
-        resources: &Resources
-    ) {
-        let texture = &resources.texture;
-        let t =  Transform{x : 0.0, y : 0.0};
-        ctx.draw_image(texture, &t);
-        // self.stack.iter_mut().for_each(|state| {
-        //     state.background_render(c, g, arguments, device, resources, data);
-        // });
-        // if let Some(top) = self.stack.last_mut() {
-        //     top.render(c, g, arguments, device, resources, data);
-        // }
+    pub fn input_context(&self) -> InputContext {
+        self.stack.last().map_or(InputContext::Menu, |top| top.input_context())
+    }
+
+    pub fn handle_action(&mut self, action: Action, state: ButtonState, data: &mut GameData) {
+        if self.animating() {
+            return;
+        }
+        if let Some(top) = self.stack.last_mut() {
+            top.handle_action(action, state, data);
+        }
     }
+
+    pub fn render(&mut self, ctx: &mut dyn RenderContext, resources: &Resources, data: &GameData) {
+        let animation = match &mut self.animation {
+            Some(animation) => animation,
+            None => {
+                let mut states: Vec<&mut Box<dyn State>> = self.stack.iter_mut().collect();
+                render_layers(&mut states, ctx, resources, data);
+                return;
+            }
+        };
+
+        // The old picture is the stack as it was before the change.
+        let kept = match animation.change {
+            Change::Popped => self.stack.len(),
+            Change::Pushed | Change::Replaced => self.stack.len().saturating_sub(1),
+        };
+        let mut outgoing = animation.outgoing.take();
+        let stack = &mut self.stack;
+        animation.render(ctx, &mut |ctx, picture| {
+            let mut states: Vec<&mut Box<dyn State>> = match picture {
+                Picture::Old => stack.iter_mut().take(kept).chain(outgoing.as_mut()).collect(),
+                Picture::New => stack.iter_mut().collect(),
+            };
+            render_layers(&mut states, ctx, resources, data);
+        });
+        animation.outgoing = outgoing;
+    }
+}
+
+/// Draws `states` from the highest one that is not an overlay up, each
+/// overlay over the states before it.
+fn render_layers(states: &mut [&mut Box<dyn State>], ctx: &mut dyn RenderContext, resources: &Resources, data: &GameData) {
+    let base = states.iter().rposition(|state| state.overlay().is_none()).unwrap_or(0);
+    let top = states.len().saturating_sub(1);
+    for (index, state) in states.iter_mut().enumerate().skip(base) {
+        if let Some(overlay) = state.overlay().filter(|_| index > base) {
+            overlay.apply(ctx);
+        }
+        if index == top {
+            state.render(ctx, resources, data);
+        } else {
+            state.background_render(ctx, resources, data);
+        }
+    }
+}
+
+pub fn state_argument<T: FromStr>(arguments: &[&str], index: usize) -> Result<T, Box<dyn error::Error>> {
+    let argument = arguments.get(index).ok_or("missing state argument")?;
+    argument
+        .parse()
+        .map_err(|_| format!("invalid state argument `{}`", argument).into())
+}
+
+/// Covers the whole window, whatever its size.
+pub fn render_background(ctx: &mut dyn RenderContext, resources: &Resources) {
+    let (transform, scale) = Layout::of(ctx).background();
+    ctx.clear(&Color::WHITE);
+    ctx.draw_image_scaled(resources.background, &transform, scale);
 }
 
-pub fn render_text(
+pub fn render_sized_text(
     text: &str,
-    font: &mut Glyphs,
-    transform: Matrix2d,
-    draw_state: &DrawState,
-    g: &mut G2d,
+    size: u32,
+    resources: &Resources,
+    transform: &Transform,
+    ctx: &mut dyn RenderContext,
     selected: bool,
 ) {
-    let color_pair: (f32, f32) = if selected { (0.2, 1.0) } else { (1.0, 0.2) };
+    let colors = &resources.colors;
+    let color = if selected { &colors.selected } else { &colors.unselected };
+
+    ctx.draw_text(text, resources.font, size, transform, color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // Moves on to `next` when confirmed and counts the actions it sees.
+    struct Screen {
+        effect: Option<Effect>,
+        next: Option<Box<dyn State>>,
+        confirmed: bool,
+        actions: Rc<Cell<u32>>,
+    }
 
-    text::Text::new_color([color_pair.0, color_pair.1, 0.0, 1.0], 128)
-        .draw(text, font, draw_state, transform, g)
-        .unwrap();
+    impl Screen {
+        fn boxed(effect: Option<Effect>, next: Option<Box<dyn State>>, actions: &Rc<Cell<u32>>) -> Box<dyn State> {
+            Box::new(Screen { effect, next, confirmed: false, actions: actions.clone() })
+        }
+    }
+
+    impl State for Screen {
+        fn update(&mut self, _data: &mut GameData, _update_args: &UpdateArgs, _event: Event) -> StateTransition {
+            match self.next.take().filter(|_| self.confirmed) {
+                Some(next) => StateTransition::Transition(next),
+                None => StateTransition::Hold,
+            }
+        }
+
+        fn handle_action(&mut self, _action: Action, _state: ButtonState, _data: &mut GameData) {
+            self.actions.set(self.actions.get() + 1);
+            self.confirmed = true;
+        }
+
+        fn transition(&self) -> Option<Effect> {
+            self.effect
+        }
+    }
+
+    fn step(machine: &mut StateMachine, data: &mut GameData, dt: f64) {
+        let update_args = UpdateArgs { dt };
+        machine.update(data, &update_args, Event::Loop(Loop::Update(update_args)));
+    }
+
+    #[test]
+    fn test_transition_blocks_input() {
+        let mut data = GameData::new(Settings::default()).unwrap();
+        data.settings.visuals.transition_time = 0.3;
+        let first = Rc::new(Cell::new(0));
+        let second = Rc::new(Cell::new(0));
+        let next = Screen::boxed(Some(Effect::Fade), None, &second);
+        let mut machine = StateMachine::new(Screen::boxed(None, Some(next), &first)).unwrap();
+
+        machine.handle_action(Action::Confirm, ButtonState::Press, &mut data);
+        step(&mut machine, &mut data, 0.1);
+        assert_eq!(1, first.get());
+        assert!(machine.animating());
+
+        machine.handle_action(Action::Confirm, ButtonState::Press, &mut data);
+        step(&mut machine, &mut data, 0.1);
+        assert_eq!(0, second.get());
+
+        step(&mut machine, &mut data, 0.25);
+        assert!(!machine.animating());
+        machine.handle_action(Action::Confirm, ButtonState::Press, &mut data);
+        assert_eq!(1, second.get());
+    }
+
+    #[test]
+    fn test_transitions_turned_off() {
+        let mut data = GameData::new(Settings::default()).unwrap();
+        data.settings.visuals.transition_time = 0.0;
+        let actions = Rc::new(Cell::new(0));
+        let next = Screen::boxed(Some(Effect::Wipe), None, &actions);
+        let mut machine = StateMachine::new(Screen::boxed(None, Some(next), &actions)).unwrap();
+
+        machine.handle_action(Action::Confirm, ButtonState::Press, &mut data);
+        step(&mut machine, &mut data, 0.1);
+        assert!(!machine.animating());
+    }
 }
@@ -0,0 +1,230 @@
+use crate::abstraction::abstraction_layer::*;
+use crate::states::state_machine::State;
+
+/// How a state comes into view, or leaves it when popped.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Effect {
+    /// Through black, the old picture darkens and the new one comes out.
+    Fade,
+    /// The new picture pushes the old one off to the left.
+    Slide,
+    /// The new picture is uncovered from the left edge.
+    Wipe,
+}
+
+/// What happened to the stack, which decides what the old picture was.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Change {
+    Replaced,
+    Pushed,
+    Popped,
+}
+
+/// The stack before or after the change.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Picture {
+    Old,
+    New,
+}
+
+pub struct Animation {
+    pub effect: Effect,
+    pub change: Change,
+    /// The state that left the stack, still drawn in the old picture.
+    pub outgoing: Option<Box<dyn State>>,
+    duration: f64,
+    elapsed: f64,
+}
+
+impl Animation {
+    pub fn new(effect: Effect, change: Change, outgoing: Option<Box<dyn State>>, duration: f64) -> Animation {
+        Animation {
+            effect,
+            change,
+            outgoing,
+            duration,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Returns false once the animation is over.
+    pub fn advance(&mut self, dt: f64) -> bool {
+        self.elapsed += dt;
+        self.elapsed < self.duration
+    }
+
+    /// From 0 when it starts to 1 when it is over.
+    pub fn progress(&self) -> f64 {
+        if self.duration > 0.0 {
+            (self.elapsed / self.duration).clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// Mixes the two pictures, `draw` draws the one it is asked for into the
+    /// context it is given.
+    pub fn render(&self, ctx: &mut dyn RenderContext, draw: &mut dyn FnMut(&mut dyn RenderContext, Picture)) {
+        let progress = self.progress();
+        let [width, height] = ctx.size();
+        let window = Rect::new(0.0, 0.0, width, height);
+        // Popping plays the effect backwards.
+        let direction = if self.change == Change::Popped { -1.0 } else { 1.0 };
+
+        match self.effect {
+            Effect::Fade => {
+                let darkness = if progress < 0.5 {
+                    draw(ctx, Picture::Old);
+                    progress * 2.0
+                } else {
+                    draw(ctx, Picture::New);
+                    (1.0 - progress) * 2.0
+                };
+                ctx.draw_rect(&window, &Color::new(0.0, 0.0, 0.0, darkness as f32));
+            }
+            Effect::Slide => {
+                let shift = width * progress * direction;
+                // Each picture is kept to where it is in sight, so blurring
+                // one leaves the other alone.
+                for (offset, picture) in [(-shift, Picture::Old), (width * direction - shift, Picture::New)] {
+                    let frame = intersection(&Rect::new(offset, 0.0, width, height), &window);
+                    draw(&mut FramedContext::new(ctx, [offset, 0.0], frame), picture);
+                }
+            }
+            Effect::Wipe => {
+                draw(ctx, Picture::Old);
+                let edge = width * progress;
+                let frame = if direction > 0.0 {
+                    Rect::new(0.0, 0.0, edge, height)
+                } else {
+                    Rect::new(width - edge, 0.0, edge, height)
+                };
+                draw(&mut FramedContext::new(ctx, [0.0, 0.0], frame), Picture::New);
+            }
+        }
+    }
+}
+
+/// Draws into another context, moved by `offset` and kept inside `frame`.
+/// Clips set through it are kept inside the frame as well.
+pub struct FramedContext<'a> {
+    inner: &'a mut dyn RenderContext,
+    offset: [f64; 2],
+    frame: Rect,
+}
+
+impl<'a> FramedContext<'a> {
+    pub fn new(inner: &'a mut dyn RenderContext, offset: [f64; 2], frame: Rect) -> FramedContext<'a> {
+        inner.set_clip(Some(frame));
+        FramedContext { inner, offset, frame }
+    }
+
+    fn moved(&self, transform: &Transform) -> Transform {
+        Transform::new(transform.x + self.offset[0], transform.y + self.offset[1])
+    }
+
+    fn moved_rect(&self, rect: &Rect) -> Rect {
+        Rect::new(rect.x + self.offset[0], rect.y + self.offset[1], rect.width, rect.height)
+    }
+}
+
+impl<'a> Drop for FramedContext<'a> {
+    fn drop(&mut self) {
+        self.inner.set_clip(None);
+    }
+}
+
+fn intersection(a: &Rect, b: &Rect) -> Rect {
+    let left = a.x.max(b.x);
+    let top = a.y.max(b.y);
+    let right = (a.x + a.width).min(b.x + b.width);
+    let bottom = (a.y + a.height).min(b.y + b.height);
+    Rect::new(left, top, (right - left).max(0.0), (bottom - top).max(0.0))
+}
+
+impl<'a> RenderContext for FramedContext<'a> {
+    fn size(&self) -> [f64; 2] {
+        self.inner.size()
+    }
+
+    // A real clear would ignore the frame.
+    fn clear(&mut self, color: &Color) {
+        let [width, height] = self.size();
+        self.draw_rect(&Rect::new(0.0, 0.0, width, height), color);
+    }
+
+    fn draw_image_scaled(&mut self, image: TextureHandle, transform: &Transform, scale: f64) {
+        let transform = self.moved(transform);
+        self.inner.draw_image_scaled(image, &transform, scale);
+    }
+
+    fn draw_rect(&mut self, rect: &Rect, color: &Color) {
+        let rect = self.moved_rect(rect);
+        self.inner.draw_rect(&rect, color);
+    }
+
+    fn draw_rect_border(&mut self, rect: &Rect, color: &Color, width: f64) {
+        let rect = self.moved_rect(rect);
+        self.inner.draw_rect_border(&rect, color, width);
+    }
+
+    fn draw_text(&mut self, text: &str, font: FontHandle, size: u32, transform: &Transform, color: &Color) {
+        let transform = self.moved(transform);
+        self.inner.draw_text(text, font, size, &transform, color);
+    }
+
+    fn text_width(&mut self, text: &str, font: FontHandle, size: u32) -> f64 {
+        self.inner.text_width(text, font, size)
+    }
+
+    fn set_clip(&mut self, rect: Option<Rect>) {
+        let clip = match rect {
+            Some(rect) => intersection(&self.moved_rect(&rect), &self.frame),
+            None => self.frame,
+        };
+        self.inner.set_clip(Some(clip));
+    }
+
+    fn blur(&mut self, radius: u32) {
+        self.inner.blur(radius);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abstraction::software_abstraction::SoftwareRenderContext;
+    use image::RgbaImage;
+
+    #[test]
+    fn test_animation_progress() {
+        let mut animation = Animation::new(Effect::Fade, Change::Replaced, None, 0.5);
+        assert_eq!(0.0, animation.progress());
+        assert!(animation.advance(0.25));
+        assert_eq!(0.5, animation.progress());
+        assert!(!animation.advance(0.5));
+        assert_eq!(1.0, animation.progress());
+    }
+
+    #[test]
+    fn test_framed_context() {
+        let mut framebuffer = RgbaImage::new(8, 8);
+        let mut ctx = SoftwareRenderContext::new(&mut framebuffer, &[], &[]);
+        ctx.clear(&Color::BLACK);
+        {
+            let mut framed = FramedContext::new(&mut ctx, [2.0, 0.0], Rect::new(0.0, 0.0, 4.0, 8.0));
+            framed.clear(&Color::WHITE);
+            framed.set_clip(Some(Rect::new(0.0, 0.0, 8.0, 2.0)));
+            framed.draw_rect(&Rect::new(0.0, 0.0, 8.0, 8.0), &Color::new(1.0, 0.0, 0.0, 1.0));
+        }
+        ctx.draw_rect(&Rect::new(7.0, 7.0, 1.0, 1.0), &Color::WHITE);
+
+        assert_eq!([0, 0, 0, 255], framebuffer.get_pixel(1, 4).0);
+        assert_eq!([255, 255, 255, 255], framebuffer.get_pixel(3, 4).0);
+        assert_eq!([0, 0, 0, 255], framebuffer.get_pixel(5, 4).0);
+        assert_eq!([255, 0, 0, 255], framebuffer.get_pixel(3, 1).0);
+        assert_eq!([0, 0, 0, 255], framebuffer.get_pixel(5, 1).0);
+        // The clip is gone once the frame is.
+        assert_eq!([255, 255, 255, 255], framebuffer.get_pixel(7, 7).0);
+    }
+}
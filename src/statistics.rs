@@ -0,0 +1,274 @@
+use crate::storage::{data_directory, KeyValueFile};
+use crate::events::*;
+use crate::finesse::is_finesse_input;
+use crate::game_data::GameData;
+use crate::tetramino::*;
+use std::error;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const STATISTICS_FILE: &str = "statistics.txt";
+const CLEAR_NAMES: [&str; 4] = ["singles", "doubles", "triples", "tetrises"];
+
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct GameStatistics {
+    pub pieces: u32,
+    pub keys: u32,
+    pub clears: [u32; 4],
+    pub tspins: u32,
+    pub combo: u32,
+    pub max_combo: u32,
+    pub distribution: [u32; TETRAMINOS_COUNT],
+    pub time: f64,
+}
+
+impl GameStatistics {
+    pub fn new() -> GameStatistics {
+        GameStatistics::default()
+    }
+
+    pub fn record_key(&mut self) {
+        self.keys += 1;
+    }
+
+    /// Called for every locked piece with the lines it completed.
+    pub fn record_lock(&mut self, piece: usize, tspin: bool, lines: usize) {
+        self.pieces += 1;
+        self.distribution[piece] += 1;
+        if tspin {
+            self.tspins += 1;
+        }
+
+        if lines == 0 {
+            self.combo = 0;
+            return;
+        }
+
+        self.clears[lines.min(CLEAR_NAMES.len()) - 1] += 1;
+        self.combo += 1;
+        self.max_combo = self.max_combo.max(self.combo);
+    }
+
+    pub fn add_time(&mut self, dt: f64) {
+        self.time += dt;
+    }
+
+    pub fn pieces_per_second(&self) -> f64 {
+        if self.time > 0.0 {
+            self.pieces as f64 / self.time
+        } else {
+            0.0
+        }
+    }
+
+    pub fn keys_per_piece(&self) -> f64 {
+        if self.pieces > 0 {
+            self.keys as f64 / self.pieces as f64
+        } else {
+            0.0
+        }
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.clears
+            .iter()
+            .enumerate()
+            .map(|(index, count)| (index as u32 + 1) * count)
+            .sum()
+    }
+
+    /// Adds a finished game to these totals.
+    pub fn accumulate(&mut self, game: &GameStatistics) {
+        self.pieces += game.pieces;
+        self.keys += game.keys;
+        self.tspins += game.tspins;
+        self.max_combo = self.max_combo.max(game.max_combo);
+        self.time += game.time;
+        for (total, count) in self.clears.iter_mut().zip(game.clears.iter()) {
+            *total += count;
+        }
+        for (total, count) in self.distribution.iter_mut().zip(game.distribution.iter()) {
+            *total += count;
+        }
+    }
+
+    pub fn write(&self, file: &mut KeyValueFile) {
+        file.push("pieces", &self.pieces.to_string());
+        file.push("keys", &self.keys.to_string());
+        for (name, count) in CLEAR_NAMES.iter().zip(self.clears.iter()) {
+            file.push(name, &count.to_string());
+        }
+        file.push("tspins", &self.tspins.to_string());
+        file.push("combo", &self.combo.to_string());
+        file.push("max_combo", &self.max_combo.to_string());
+        let distribution: Vec<String> = self.distribution.iter().map(u32::to_string).collect();
+        file.push("distribution", &distribution.join(" "));
+        file.push("time", &self.time.to_string());
+    }
+
+    /// Missing keys read as zero so files from older versions still load.
+    pub fn read(file: &KeyValueFile) -> Result<GameStatistics, Box<dyn error::Error>> {
+        let mut statistics = GameStatistics {
+            pieces: field(file, "pieces")?,
+            keys: field(file, "keys")?,
+            tspins: field(file, "tspins")?,
+            combo: field(file, "combo")?,
+            max_combo: field(file, "max_combo")?,
+            time: field(file, "time")?,
+            ..GameStatistics::default()
+        };
+        for (name, count) in CLEAR_NAMES.iter().zip(statistics.clears.iter_mut()) {
+            *count = field(file, name)?;
+        }
+
+        let distribution = file.get("distribution").unwrap_or("");
+        for (index, count) in distribution.split_whitespace().enumerate() {
+            if index >= TETRAMINOS_COUNT {
+                return Err("too many values in `distribution`".into());
+            }
+            statistics.distribution[index] = count.parse()?;
+        }
+
+        Ok(statistics)
+    }
+}
+
+pub struct StatisticsListener;
+
+impl EventListener for StatisticsListener {
+    fn on_event(&mut self, event: &GameEvent, data: &mut GameData) {
+        match event {
+            GameEvent::Tick { dt } => data.statistics.add_time(*dt),
+            GameEvent::KeyPressed { action } => {
+                data.statistics.record_key();
+                if is_finesse_input(*action) {
+                    data.finesse.record_input();
+                }
+            }
+            GameEvent::PieceLocked { piece, tspin, lines } => {
+                data.statistics.record_lock(*piece, *tspin, *lines);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn field<T: FromStr + Default>(file: &KeyValueFile, key: &str) -> Result<T, Box<dyn error::Error>> {
+    match file.get(key) {
+        Some(value) => value.parse().map_err(|_| format!("invalid `{}`", key).into()),
+        None => Ok(T::default()),
+    }
+}
+
+/// Totals over every finished game, kept in the user data directory.
+#[derive(Default)]
+pub struct LifetimeStatistics {
+    pub games: u32,
+    pub totals: GameStatistics,
+}
+
+impl LifetimeStatistics {
+    pub fn path() -> Result<PathBuf, Box<dyn error::Error>> {
+        Ok(data_directory()?.join(STATISTICS_FILE))
+    }
+
+    pub fn load() -> Result<LifetimeStatistics, Box<dyn error::Error>> {
+        let path = LifetimeStatistics::path()?;
+        if !path.exists() {
+            return Ok(LifetimeStatistics::default());
+        }
+
+        let file = KeyValueFile::load(&path)?;
+        Ok(LifetimeStatistics {
+            games: field(&file, "games")?,
+            totals: GameStatistics::read(&file)?,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn error::Error>> {
+        let mut file = KeyValueFile::new();
+        file.push("games", &self.games.to_string());
+        self.totals.write(&mut file);
+        file.save(&LifetimeStatistics::path()?)
+    }
+
+    /// Adds the game to the saved totals and returns the new totals.
+    pub fn record(game: &GameStatistics) -> Result<LifetimeStatistics, Box<dyn error::Error>> {
+        let mut lifetime = LifetimeStatistics::load()?;
+        lifetime.games += 1;
+        lifetime.totals.accumulate(game);
+        lifetime.save()?;
+        Ok(lifetime)
+    }
+}
+
+pub fn format_time(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Action;
+    use crate::settings::Settings;
+
+    #[test]
+    fn test_record_lock() {
+        let mut statistics = GameStatistics::new();
+        statistics.record_lock(TetrominoType::I as usize, false, 4);
+        statistics.record_lock(TetrominoType::T as usize, true, 2);
+        statistics.record_lock(TetrominoType::O as usize, false, 0);
+        statistics.record_lock(TetrominoType::O as usize, false, 1);
+        for _ in 0..10 {
+            statistics.record_key();
+        }
+        statistics.add_time(2.0);
+
+        assert_eq!(4, statistics.pieces);
+        assert_eq!([1, 1, 0, 1], statistics.clears);
+        assert_eq!(7, statistics.lines());
+        assert_eq!(1, statistics.tspins);
+        assert_eq!(2, statistics.max_combo);
+        assert_eq!(1, statistics.combo);
+        assert_eq!(2, statistics.distribution[TetrominoType::O as usize]);
+        assert_eq!(2.0, statistics.pieces_per_second());
+        assert_eq!(2.5, statistics.keys_per_piece());
+    }
+
+    #[test]
+    fn test_statistics_listener() {
+        let mut data = GameData::new(Settings::default()).unwrap();
+        let mut listener = StatisticsListener;
+        listener.on_event(&GameEvent::KeyPressed { action: Action::MoveLeft }, &mut data);
+        listener.on_event(&GameEvent::KeyPressed { action: Action::Hold }, &mut data);
+        listener.on_event(&GameEvent::Tick { dt: 0.5 }, &mut data);
+
+        assert_eq!(2, data.statistics.keys);
+        assert_eq!(1, data.finesse.inputs);
+        assert_eq!(0.5, data.statistics.time);
+    }
+
+    #[test]
+    fn test_statistics_round_trip() {
+        let mut statistics = GameStatistics::new();
+        statistics.record_lock(TetrominoType::L as usize, false, 3);
+        statistics.add_time(12.5);
+
+        let mut file = KeyValueFile::new();
+        statistics.write(&mut file);
+        let parsed = GameStatistics::read(&KeyValueFile::parse(&file.to_string()).unwrap()).unwrap();
+        assert_eq!(statistics, parsed);
+        assert_eq!(GameStatistics::new(), GameStatistics::read(&KeyValueFile::new()).unwrap());
+
+        let mut totals = GameStatistics::new();
+        totals.accumulate(&statistics);
+        totals.accumulate(&statistics);
+        assert_eq!(6, totals.lines());
+        assert_eq!("0:25", format_time(totals.time));
+    }
+}
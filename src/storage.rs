@@ -0,0 +1,169 @@
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const APPLICATION_DIRECTORY: &str = "tetris-rust";
+
+/// Per-user directory for saved games and other files the game writes.
+pub fn data_directory() -> Result<PathBuf, Box<dyn error::Error>> {
+    let base = if let Some(data_home) = env::var_os("XDG_DATA_HOME") {
+        PathBuf::from(data_home)
+    } else if let Some(app_data) = env::var_os("APPDATA") {
+        PathBuf::from(app_data)
+    } else if let Some(home) = env::var_os("HOME") {
+        PathBuf::from(home).join(".local").join("share")
+    } else {
+        return Err("cannot find a data directory, set HOME or XDG_DATA_HOME".into());
+    };
+
+    Ok(base.join(APPLICATION_DIRECTORY))
+}
+
+/// Per-user directory for the settings file.
+pub fn config_directory() -> Result<PathBuf, Box<dyn error::Error>> {
+    let base = if let Some(config_home) = env::var_os("XDG_CONFIG_HOME") {
+        PathBuf::from(config_home)
+    } else if let Some(app_data) = env::var_os("APPDATA") {
+        PathBuf::from(app_data)
+    } else if let Some(home) = env::var_os("HOME") {
+        PathBuf::from(home).join(".config")
+    } else {
+        return Err("cannot find a config directory, set HOME or XDG_CONFIG_HOME".into());
+    };
+
+    Ok(base.join(APPLICATION_DIRECTORY))
+}
+
+/// Plain text file made of `key = value` lines. Blank lines and lines
+/// starting with `#` are ignored and keys may repeat. A `[section]` line
+/// prefixes the keys after it with `section.`. Values are taken as they are,
+/// quotes included, and keys repeat, so these are `.cfg` files rather than
+/// TOML.
+#[derive(Clone, Default)]
+pub struct KeyValueFile {
+    entries: Vec<(String, String)>,
+}
+
+impl KeyValueFile {
+    pub fn new() -> KeyValueFile {
+        KeyValueFile::default()
+    }
+
+    pub fn parse(text: &str) -> Result<KeyValueFile, Box<dyn error::Error>> {
+        let mut file = KeyValueFile::new();
+        let mut section = String::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                section = format!("{}.", line[1..line.len() - 1].trim());
+                continue;
+            }
+
+            let separator = line
+                .find('=')
+                .ok_or_else(|| format!("line {}: expected `key = value`", number + 1))?;
+            let key = line[..separator].trim();
+            let value = line[separator + 1..].trim();
+            file.push(&format!("{}{}", section, key), value);
+        }
+
+        Ok(file)
+    }
+
+    pub fn load(path: &Path) -> Result<KeyValueFile, Box<dyn error::Error>> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+        KeyValueFile::parse(&text).map_err(|error| format!("{}: {}", path.display(), error).into())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn error::Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_string())
+            .map_err(|error| format!("{}: {}", path.display(), error).into())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(entry, _)| entry == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.entries
+            .iter()
+            .filter(|(entry, _)| entry == key)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    pub fn push(&mut self, key: &str, value: &str) {
+        self.entries.push((key.to_string(), value.to_string()));
+    }
+}
+
+impl fmt::Display for KeyValueFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Keys without a section have to come before the first section, or
+        // they would be read back into it.
+        let (plain, sectioned): (Vec<_>, Vec<_>) = self.entries.iter().partition(|(key, _)| !key.contains('.'));
+        let mut current = "";
+        for (key, value) in plain.into_iter().chain(sectioned) {
+            let (section, name) = match key.find('.') {
+                Some(separator) => (&key[..separator], &key[separator + 1..]),
+                None => ("", key.as_str()),
+            };
+            if section != current {
+                writeln!(f, "[{}]", section)?;
+                current = section;
+            }
+            writeln!(f, "{} = {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_value_file_parse() {
+        let file = KeyValueFile::parse("# comment\n\nname = Tuck\nrow = ..\nrow = XX\n").unwrap();
+        assert_eq!(Some("Tuck"), file.get("name"));
+        assert_eq!(vec!["..", "XX"], file.get_all("row"));
+        assert_eq!(None, file.get("goal"));
+        assert!(KeyValueFile::parse("no separator").is_err());
+    }
+
+    #[test]
+    fn test_key_value_file_sections() {
+        let file = KeyValueFile::parse("name = a\n[audio]\nvolume = 5\n[controls]\ndrop = Space\n").unwrap();
+        assert_eq!(Some("a"), file.get("name"));
+        assert_eq!(Some("5"), file.get("audio.volume"));
+        assert_eq!(Some("Space"), file.get("controls.drop"));
+        assert_eq!("name = a\n[audio]\nvolume = 5\n[controls]\ndrop = Space\n", file.to_string());
+    }
+
+    #[test]
+    fn test_key_value_file_round_trip() {
+        let mut file = KeyValueFile::new();
+        file.push("pieces", "T I");
+        file.push("row", "X.........");
+        file.push("audio.volume", "5");
+        file.push("name", "\"Tuck\"");
+        let parsed = KeyValueFile::parse(&file.to_string()).unwrap();
+        assert_eq!(Some("T I"), parsed.get("pieces"));
+        assert_eq!(Some("X........."), parsed.get("row"));
+        assert_eq!(Some("5"), parsed.get("audio.volume"));
+        assert_eq!(Some("\"Tuck\""), parsed.get("name"));
+    }
+}
@@ -4,6 +4,7 @@ use std::ops;
 const ROTATION_COUNT: usize = 4;
 const TETRAMINO_POINTS_COUNT: usize = 4;
 pub const TETRAMINOS_COUNT: usize = 7;
+pub const TETRAMINO_NAMES: [&str; TETRAMINOS_COUNT] = ["I", "O", "T", "S", "Z", "J", "L"];
 
 #[derive(Copy, Clone)]
 pub enum TetrominoType {
@@ -17,9 +18,19 @@ pub enum TetrominoType {
     E,
 }
 
+pub const TETRAMINOES: [TetrominoType; TETRAMINOS_COUNT] = [
+    TetrominoType::I,
+    TetrominoType::O,
+    TetrominoType::T,
+    TetrominoType::S,
+    TetrominoType::Z,
+    TetrominoType::J,
+    TetrominoType::L,
+];
+
 pub type TetraminoesData = [TetrominoData; TETRAMINOS_COUNT];
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Point {
     pub x: i32,
     pub y: i32,
@@ -126,6 +137,7 @@ impl TetrominoData {
     }
 }
 
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Tetramino {
     position: Point,
     tetramino_type_index: usize,
@@ -180,8 +192,21 @@ impl Tetramino {
     pub fn peek_left_rotation(&self) -> usize {
         (self.rotation_index + ROTATION_COUNT - 1) % ROTATION_COUNT
     }
+
+    pub fn peek_half_rotation(&self) -> usize {
+        (self.rotation_index + ROTATION_COUNT / 2) % ROTATION_COUNT
+    }
 }
 
+// Half turns have no SRS kicks. They try the spot itself, then one cell to
+// either side and one cell up.
+static HALF_TURN_KICKS: [Point; 4] = [
+    Point { x: 0, y: 0 },
+    Point { x: 1, y: 0 },
+    Point { x: -1, y: 0 },
+    Point { x: 0, y: -1 },
+];
+
 pub struct RotationCollisionTable {
     tetramino_maping: [usize; TETRAMINOS_COUNT],
     collision_table: [[[Point; 5]; 8]; 3],
@@ -378,6 +403,10 @@ impl RotationCollisionTable {
         j: usize,
         tetramino_type: usize,
     ) -> std::slice::Iter<'_, Point> {
+        if (i + ROTATION_COUNT / 2) % ROTATION_COUNT == j {
+            return HALF_TURN_KICKS.iter();
+        }
+
         let rotation_index = RotationCollisionTable::rotation_collision_table_index(i, j);
         let tetramino_index = self.tetramino_maping[tetramino_type as usize];
         self.collision_table[tetramino_index][rotation_index].iter()
@@ -1,107 +1,198 @@
 use crate::game_data::*;
 use crate::resources::*;
+use crate::assets::{init_assets, AssetWatcher};
+use crate::input::InputMapper;
+use crate::audio::AudioPlayer;
+use crate::settings::Settings;
 use crate::states::main_menu::MainMenu;
 use crate::states::state_machine::*;
-use crate::abstraction::piston_abstraction::*;
-use crate::abstraction::abstraction_layer::AbstractionLayer;
+use crate::abstraction::piston_abstraction::PistonAbstraction;
+use crate::abstraction::terminal_abstraction::TerminalAbstraction;
+use crate::abstraction::abstraction_layer::{AbstractionLayer, Application, RenderContext, ResourceLoader};
+use crate::theme::{theme_directory, Theme, DEFAULT_THEME};
+use crate::timestep::{FixedStep, TICK};
 use piston_window::*;
+use crate::layout::Layout;
 use std::error;
 
-pub type Abstraction = PistonAbstraction;
+const NOTICE_TEXT_SIZE: u32 = 20;
+const NOTICE_POSITION_Y: f64 = 1272.0;
 
 pub struct Tetris {
-    window: PistonWindow,
-    abstraction : Abstraction,
+    abstraction : Box<dyn AbstractionLayer>,
+    game : Game,
+}
+
+/// Everything the abstraction layer drives.
+pub struct Game {
     pub resources: Resources,
     pub data: GameData,
     pub logic: StateMachine,
+    input: InputMapper,
+    theme: String,
+    watcher: Option<AssetWatcher>,
+    step: FixedStep,
 }
 
-const GAME_NAME: &str = "Tetris";
-const WINDOW_WIDTH: u32 = 1024;
-const WINDOW_HEIGHT: u32 = 1280;
-
 impl Tetris {
     pub fn new(resorce_path: &str) -> Result<Tetris, Box<dyn error::Error>> {
-        let opengl = OpenGL::V3_2;
-        let mut window: PistonWindow =
-            WindowSettings::new(GAME_NAME, [WINDOW_WIDTH, WINDOW_HEIGHT])
-                .exit_on_esc(false)
-                .graphics_api(opengl)
-                .build()?;
-
-        let mut abstraction  = Abstraction::new()?;
-        let resources = Resources::new(resorce_path, &mut window, &mut abstraction)?;
-        let game_data = GameData::new()?;
+        Tetris::with_abstraction(resorce_path, |window_size| Ok(Box::new(PistonAbstraction::new(window_size)?)))
+    }
+
+    /// Plays in the terminal instead of a window.
+    pub fn terminal(resorce_path: &str) -> Result<Tetris, Box<dyn error::Error>> {
+        Tetris::with_abstraction(resorce_path, |window_size| Ok(Box::new(TerminalAbstraction::new(window_size))))
+    }
+
+    fn with_abstraction(
+        resorce_path: &str,
+        create: impl FnOnce([u32; 2]) -> Result<Box<dyn AbstractionLayer>, Box<dyn error::Error>>,
+    ) -> Result<Tetris, Box<dyn error::Error>> {
+        init_assets(resorce_path)?;
+        let (settings, settings_error) = match Settings::load() {
+            Ok(settings) => (settings, None),
+            Err(error) => (Settings::default(), Some(error.to_string())),
+        };
+        let mut game_data = GameData::new(settings)?;
+        if let Some(error) = settings_error {
+            game_data.notice.show(error);
+        }
+        game_data.audio = AudioPlayer::open(&mut game_data.notice);
+        let visuals = &game_data.settings.visuals;
+        let window_size = [visuals.window_width, visuals.window_height];
+
+        let mut abstraction = create(window_size)?;
         let game_logic = StateMachine::new(MainMenu::new()?)?;
+        let mut game = Game::new(abstraction.as_mut(), game_data, game_logic)?;
+        game.watch_assets();
 
         Ok(Tetris {
-            window,
             abstraction,
-            resources: resources,
-            data: game_data,
-            logic: game_logic,
+            game,
         })
     }
 
     pub fn run(&mut self) {
-        let abstraction = &mut self.abstraction;
-        abstraction.run(&self.logic, &self.resources);
-        // while let Some(event) = self.window.next() {
-        //     match event {
-        //         Event::Loop(_loop) => {
-        //             if self.loop_handler(_loop, event) {
-        //                 return;
-        //             }
-        //         }
-        //
-        //         Event::Input(args, time) => {
-        //             self.input_handler(args, time);
-        //         }
-        //
-        //         _ => {}
-        //     }
-        //
-        //     if self.data.running == false {
-        //         break;
-        //     }
-        // }
+        self.abstraction.run(&mut self.game);
     }
+}
 
-    fn loop_handler(&mut self, loop_arg: Loop, event: Event) -> bool {
-        match loop_arg {
-            Loop::Update(update_args) => {
-                if !self.update(update_args, event.clone()) {
-                    return true;
-                }
+impl Game {
+    /// Loads the resources through `abstraction`, which has to be the one
+    /// that runs the game. A theme that fails to load falls back to the
+    /// default one.
+    pub fn new(
+        abstraction: &mut dyn ResourceLoader,
+        mut data: GameData,
+        logic: StateMachine,
+    ) -> Result<Game, Box<dyn error::Error>> {
+        let theme = &mut data.settings.visuals.theme;
+        let resources = match load_theme(theme, abstraction) {
+            Ok(resources) => resources,
+            Err(error) => {
+                data.notice.show(error.to_string());
+                *theme = DEFAULT_THEME.to_string();
+                load_theme(theme, abstraction)?
             }
+        };
 
-            Loop::Render(render_args) => {
-                self.render(render_args, event);
-            }
+        Ok(Game {
+            resources,
+            theme: data.settings.visuals.theme.clone(),
+            data,
+            logic,
+            input: InputMapper::new(),
+            watcher: None,
+            step: FixedStep::new(),
+        })
+    }
 
-            _ => {}
+    /// Reloads the theme whenever one of its files changes on disk.
+    pub fn watch_assets(&mut self) {
+        match theme_directory().and_then(|directory| AssetWatcher::new(&directory)) {
+            Ok(watcher) => self.watcher = Some(watcher),
+            Err(error) => self.data.notice.show(format!("assets will not reload: {}", error)),
         }
+    }
 
-        false
+    // The settings menu only changes the name, the resources are swapped
+    // here where the loader is at hand.
+    fn switch_theme(&mut self, loader: &mut dyn ResourceLoader) {
+        let name = self.data.settings.visuals.theme.clone();
+        match Theme::load(&name).and_then(|theme| self.resources.reload(&theme, loader)) {
+            Ok(()) => self.theme = name,
+            Err(error) => {
+                self.data.notice.show(error.to_string());
+                // Part of the new theme may be loaded already.
+                self.data.settings.visuals.theme = self.theme.clone();
+                self.reload_theme(loader);
+            }
+        }
     }
 
-    fn update(&mut self, update: UpdateArgs, event: Event) -> bool {
-        self.logic.update(&mut self.data, &update, event)
+    fn reload_theme(&mut self, loader: &mut dyn ResourceLoader) {
+        if let Err(error) = Theme::load(&self.theme).and_then(|theme| self.resources.reload(&theme, loader)) {
+            self.data.notice.show(error.to_string());
+        }
     }
 
-    fn render(&mut self, arguments: RenderArgs, event: Event) {
-        let resources = &mut self.resources;
-        let game_data = &mut self.data;
-        let game_logic = &mut self.logic;
-        self.window.draw_2d(&event, |c, g, device| {
-            clear([1.0; 4], g);
-            // game_logic.render(c, g, &arguments, device, resources, game_data)
-        });
+    // Themes may share files, so any change under the theme directory
+    // reloads the current one.
+    fn assets_changed(&self) -> bool {
+        self.watcher
+            .as_ref()
+            .is_some_and(|watcher| !watcher.changed().is_empty())
     }
+}
+
+fn load_theme(name: &str, loader: &mut dyn ResourceLoader) -> Result<Resources, Box<dyn error::Error>> {
+    Resources::new(&Theme::load(name)?, loader)
+}
 
-    fn input_handler(&mut self, input: Input, time: Option<TimeStamp>) {
+impl Application for Game {
+    // Frontends report frames of whatever length they get, the states only
+    // ever see whole ticks.
+    fn update(&mut self, update: &UpdateArgs, _event: Event, loader: &mut dyn ResourceLoader) -> bool {
+        let tick = UpdateArgs { dt: TICK };
+        let mut running = true;
+        for _ in 0..self.step.advance(update.dt) {
+            self.data.notice.update(TICK);
+            self.data.interpolation.previous = Some(self.data.current_figure);
+            // Only a falling piece sets it again.
+            self.data.interpolation.fall = 0.0;
+            running = self.logic.update(&mut self.data, &tick, Event::Loop(Loop::Update(tick))) && self.data.running;
+            if !running {
+                break;
+            }
+        }
+
+        if self.data.settings.visuals.theme != self.theme {
+            self.switch_theme(loader);
+        } else if self.assets_changed() {
+            self.reload_theme(loader);
+        }
+        running
+    }
+
+    fn handle_input(&mut self, input: Input, time: Option<TimeStamp>) {
+        let context = self.logic.input_context();
+        let actions = self.input.actions(&input, &self.data.settings, context);
         self.logic
             .handle_input(input, time, &mut self.data);
+        for (action, state) in actions {
+            self.logic.handle_action(action, state, &mut self.data);
+        }
+    }
+
+    fn render(&mut self, ctx: &mut dyn RenderContext, ext_dt: f64) {
+        self.data.interpolation.alpha = self.step.alpha_after(ext_dt);
+        self.logic.render(ctx, &self.resources, &self.data);
+
+        if let Some(message) = self.data.notice.message() {
+            let layout = Layout::of(ctx);
+            let transform = layout.screen_point(0.0, NOTICE_POSITION_Y);
+            let size = layout.text_size(NOTICE_TEXT_SIZE);
+            ctx.draw_text(message, self.resources.font, size, &transform, &self.resources.colors.error);
+        }
     }
 }
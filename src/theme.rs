@@ -0,0 +1,205 @@
+use crate::abstraction::abstraction_layer::Color;
+use crate::assets::asset_directory;
+use crate::storage::KeyValueFile;
+use crate::tetramino::{TETRAMINOS_COUNT, TETRAMINO_NAMES};
+use crate::widgets::HudLayout;
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_THEME: &str = "classic";
+const THEME_DIRECTORY: &str = "themes";
+const MANIFEST_FILE: &str = "theme.cfg";
+
+/// Colors the states draw their text with.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ThemeColors {
+    pub text: Color,
+    pub title: Color,
+    pub value: Color,
+    pub error: Color,
+    pub selected: Color,
+    pub unselected: Color,
+    pub message: Color,
+}
+
+impl ThemeColors {
+    pub const NAMES: [&'static str; 7] = ["text", "title", "value", "error", "selected", "unselected", "message"];
+
+    pub fn get(&self, name: &str) -> Option<Color> {
+        let color = match name {
+            "text" => self.text,
+            "title" => self.title,
+            "value" => self.value,
+            "error" => self.error,
+            "selected" => self.selected,
+            "unselected" => self.unselected,
+            "message" => self.message,
+            _ => return None,
+        };
+        Some(color)
+    }
+}
+
+impl Default for ThemeColors {
+    fn default() -> ThemeColors {
+        ThemeColors {
+            text: Color::new(1.0, 1.0, 0.0, 1.0),
+            title: Color::new(0.2, 0.8, 0.3, 1.0),
+            value: Color::new(0.0, 1.0, 0.0, 1.0),
+            error: Color::new(1.0, 0.2, 0.0, 1.0),
+            selected: Color::new(0.2, 1.0, 0.0, 1.0),
+            unselected: Color::new(1.0, 0.2, 0.0, 1.0),
+            message: Color::WHITE,
+        }
+    }
+}
+
+/// A skin read from `assets/themes/<name>/theme.cfg`. File names in the
+/// manifest are relative to the theme's directory.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Theme {
+    pub name: String,
+    pub title: String,
+    pub background: PathBuf,
+    pub empty_block: PathBuf,
+    pub blocks: [PathBuf; TETRAMINOS_COUNT],
+    pub font: PathBuf,
+    pub colors: ThemeColors,
+    pub hud: HudLayout,
+}
+
+impl Theme {
+    pub fn load(name: &str) -> Result<Theme, Box<dyn error::Error>> {
+        let directory = theme_directory()?.join(name);
+        let file = KeyValueFile::load(&directory.join(MANIFEST_FILE))?;
+        Theme::parse(name, &directory, &file).map_err(|error| format!("theme {}: {}", name, error).into())
+    }
+
+    pub fn parse(name: &str, directory: &Path, file: &KeyValueFile) -> Result<Theme, Box<dyn error::Error>> {
+        let path = |key: &str| -> Result<PathBuf, Box<dyn error::Error>> {
+            let value = file.get(key).ok_or_else(|| format!("missing `{}`", key))?;
+            Ok(directory.join(value))
+        };
+
+        // Pieces without their own texture use `blocks.default`.
+        let default_block = file.get("blocks.default");
+        let mut blocks: [PathBuf; TETRAMINOS_COUNT] = Default::default();
+        for (block, name) in blocks.iter_mut().zip(TETRAMINO_NAMES.iter()) {
+            let value = file
+                .get(&format!("blocks.{}", name))
+                .or(default_block)
+                .ok_or_else(|| format!("missing `blocks.{}`", name))?;
+            *block = directory.join(value);
+        }
+
+        let mut colors = ThemeColors::default();
+        let entries = [
+            ("colors.text", &mut colors.text),
+            ("colors.title", &mut colors.title),
+            ("colors.value", &mut colors.value),
+            ("colors.error", &mut colors.error),
+            ("colors.selected", &mut colors.selected),
+            ("colors.unselected", &mut colors.unselected),
+            ("colors.message", &mut colors.message),
+        ];
+        for (key, color) in entries {
+            if let Some(value) = file.get(key) {
+                *color = parse_color(value).map_err(|error| format!("{}: {}", key, error))?;
+            }
+        }
+
+        Ok(Theme {
+            name: name.to_string(),
+            title: file.get("name").unwrap_or(name).to_string(),
+            background: path("background")?,
+            empty_block: path("empty_block")?,
+            blocks,
+            font: path("font")?,
+            colors,
+            hud: HudLayout::parse(file)?,
+        })
+    }
+}
+
+pub fn theme_directory() -> Result<PathBuf, Box<dyn error::Error>> {
+    Ok(asset_directory()?.join(THEME_DIRECTORY))
+}
+
+/// Names of the directories under `assets/themes` that have a manifest.
+pub fn available_themes() -> Vec<String> {
+    let entries = match theme_directory().and_then(|directory| Ok(fs::read_dir(directory)?)) {
+        Ok(entries) => entries,
+        Err(_) => return vec![DEFAULT_THEME.to_string()],
+    };
+
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join(MANIFEST_FILE).is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Reads `#rrggbb` or `#rrggbbaa`.
+pub fn parse_color(text: &str) -> Result<Color, Box<dyn error::Error>> {
+    let digits = text
+        .strip_prefix('#')
+        .filter(|digits| (digits.len() == 6 || digits.len() == 8) && digits.is_ascii())
+        .ok_or_else(|| format!("expected `#rrggbb` or `#rrggbbaa`, got `{}`", text))?;
+
+    let mut channels = [1.0; 4];
+    for (index, channel) in channels.iter_mut().enumerate().take(digits.len() / 2) {
+        let value = u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16)
+            .map_err(|_| format!("`{}` is not a color", text))?;
+        *channel = value as f32 / 255.0;
+    }
+
+    Ok(Color::new(channels[0], channels[1], channels[2], channels[3]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(Color::new(1.0, 0.2, 0.0, 1.0), parse_color("#ff3300").unwrap());
+        assert_eq!(Color::new(0.0, 0.0, 1.0, 0.2), parse_color("#0000ff33").unwrap());
+        assert!(parse_color("ff3300").is_err());
+        assert!(parse_color("#ff33").is_err());
+        assert!(parse_color("#gg3300").is_err());
+    }
+
+    #[test]
+    fn test_parse_theme() {
+        let text = "name = Test\nbackground = back.png\nempty_block = empty.png\nfont = font.ttf\n\
+                    [blocks]\ndefault = block.png\nT = purple.png\n[colors]\ntext = #ffffff\n";
+        let file = KeyValueFile::parse(text).unwrap();
+        let theme = Theme::parse("test", Path::new("themes/test"), &file).unwrap();
+
+        assert_eq!("Test", theme.title);
+        assert_eq!(Path::new("themes/test/back.png"), theme.background);
+        assert_eq!(Path::new("themes/test/purple.png"), theme.blocks[2]);
+        assert_eq!(Path::new("themes/test/block.png"), theme.blocks[0]);
+        assert_eq!(Color::WHITE, theme.colors.text);
+        assert_eq!(ThemeColors::default().error, theme.colors.error);
+
+        let missing = KeyValueFile::parse("background = back.png\nempty_block = empty.png\nfont = font.ttf").unwrap();
+        assert!(Theme::parse("test", Path::new("."), &missing).is_err());
+    }
+
+    #[test]
+    fn test_shipped_themes() {
+        let themes = available_themes();
+        assert!(themes.iter().any(|name| name == DEFAULT_THEME));
+        for name in themes {
+            let theme = Theme::load(&name).unwrap();
+            let files = [&theme.background, &theme.empty_block, &theme.font];
+            for file in files.iter().copied().chain(theme.blocks.iter()) {
+                assert!(file.is_file(), "{}: {} is missing", name, file.display());
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::tetramino::Tetramino;
+
+pub const TICK_RATE: f64 = 60.0;
+pub const TICK: f64 = 1.0 / TICK_RATE;
+// After a stall (a dragged window, a breakpoint) the time that would need
+// more ticks than this is dropped instead of being played back at once.
+const MAX_TICKS: u32 = 8;
+
+/// Turns frames of any length into whole ticks of `TICK` seconds.
+#[derive(Clone, Default, Debug)]
+pub struct FixedStep {
+    accumulator: f64,
+}
+
+impl FixedStep {
+    pub fn new() -> FixedStep {
+        FixedStep::default()
+    }
+
+    /// Adds `dt` seconds and returns how many ticks are due.
+    pub fn advance(&mut self, dt: f64) -> u32 {
+        // Time past what the capped ticks need is dropped before counting,
+        // so a long stall costs no more than a short one.
+        let backlog = (MAX_TICKS + 1) as f64 * TICK;
+        self.accumulator = (self.accumulator + dt.max(0.0)).min(backlog);
+        let ticks = (self.accumulator / TICK).floor();
+        self.accumulator = (self.accumulator - ticks * TICK).max(0.0);
+        (ticks as u32).min(MAX_TICKS)
+    }
+
+    /// How far into the next tick the time is, from 0 to 1, once `elapsed`
+    /// more seconds have passed. Frontends draw between updates.
+    pub fn alpha_after(&self, elapsed: f64) -> f64 {
+        ((self.accumulator + elapsed.max(0.0)) / TICK).min(1.0)
+    }
+}
+
+/// Lets rendering draw the falling piece between where it was on the last
+/// tick and where it is now, and part way down to the next row.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Interpolation {
+    pub previous: Option<Tetramino>,
+    pub alpha: f64,
+    /// How far the piece got to the row below on the last tick, in rows.
+    pub fall: f64,
+    /// Rows per second, carries `fall` on past the last tick.
+    pub fall_speed: f64,
+}
+
+impl Interpolation {
+    /// Where to draw `current`, in cells. Sideways moves are eased over a
+    /// tick, a piece that spawned, rotated or jumped more than a cell since
+    /// the last tick is drawn in its column. Falling is eased over the
+    /// gravity interval unless the piece rests on something.
+    pub fn position(&self, current: &Tetramino, can_fall: bool) -> (f64, f64) {
+        let position = current.get_position();
+        let alpha = self.alpha.clamp(0.0, 1.0);
+        let fall = if can_fall {
+            (self.fall + self.fall_speed * alpha * TICK).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let (x, y) = (position.x as f64, position.y as f64 + fall);
+        let previous = match &self.previous {
+            Some(previous) => previous,
+            None => return (x, y),
+        };
+
+        let from = previous.get_position();
+        let moved = (position.x - from.x).abs().max((position.y - from.y).abs());
+        let same_piece = previous.get_type() == current.get_type() && previous.get_rotation() == current.get_rotation();
+        // Pieces only move up when a new one spawns.
+        if !same_piece || moved > 1 || position.y < from.y {
+            return (x, y);
+        }
+
+        (from.x as f64 + (x - from.x as f64) * alpha, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetramino::Point;
+
+    #[test]
+    fn test_fixed_step() {
+        let mut step = FixedStep::new();
+        assert_eq!(0, step.advance(TICK / 2.0));
+        assert!((step.alpha_after(0.0) - 0.5).abs() < 1e-9);
+        assert_eq!(1, step.advance(TICK));
+        assert!((step.alpha_after(0.0) - 0.5).abs() < 1e-9);
+
+        // Frames at the tick rate give exactly one tick each.
+        let mut step = FixedStep::new();
+        assert!((0..600).all(|_| step.advance(TICK) == 1));
+
+        let mut step = FixedStep::new();
+        assert_eq!(MAX_TICKS, step.advance(10.0));
+        assert!(step.alpha_after(0.0) < 1.0);
+    }
+
+    #[test]
+    fn test_long_stall() {
+        let mut step = FixedStep::new();
+        assert_eq!(MAX_TICKS, step.advance(1e12));
+        assert!(step.alpha_after(0.0) < 1.0);
+        assert_eq!(1, step.advance(TICK));
+
+        let mut step = FixedStep::new();
+        assert_eq!(MAX_TICKS, step.advance(f64::INFINITY));
+        assert_eq!(0, step.advance(f64::NAN));
+    }
+
+    #[test]
+    fn test_alpha_after() {
+        let mut step = FixedStep::new();
+        step.advance(TICK / 4.0);
+        assert!((step.alpha_after(TICK / 2.0) - 0.75).abs() < 1e-9);
+        assert_eq!(1.0, step.alpha_after(TICK));
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let mut current = Tetramino::new(0);
+        let mut interpolation = Interpolation { previous: None, alpha: 0.25, fall: 0.0, fall_speed: 0.0 };
+        assert_eq!((5.0, 0.0), interpolation.position(&current, true));
+
+        interpolation.previous = Some(current);
+        current.move_it(&Point { x: 1, y: 0 });
+        assert_eq!((5.25, 0.0), interpolation.position(&current, true));
+
+        current.move_it(&Point { x: 0, y: 5 });
+        assert_eq!((6.0, 5.0), interpolation.position(&current, true));
+
+        interpolation.previous = Some(current);
+        assert_eq!((5.0, 0.0), interpolation.position(&Tetramino::new(0), true));
+
+        let mut rotated = Tetramino::new(0);
+        rotated.rotate_right();
+        rotated.move_it(&Point { x: 1, y: 0 });
+        assert_eq!((6.0, 0.0), interpolation.position(&rotated, true));
+    }
+
+    #[test]
+    fn test_fall_interpolation() {
+        let current = Tetramino::new(0);
+        // Two rows a second, a quarter tick after the piece got half way.
+        let interpolation = Interpolation { previous: Some(current), alpha: 0.25, fall: 0.5, fall_speed: 2.0 };
+        let (_, y) = interpolation.position(&current, true);
+        assert!((y - (0.5 + 2.0 * 0.25 * TICK)).abs() < 1e-9);
+        assert_eq!((5.0, 0.0), interpolation.position(&current, false));
+
+        let interpolation = Interpolation { fall: 0.99, fall_speed: 1000.0, ..interpolation };
+        assert_eq!((5.0, 1.0), interpolation.position(&current, true));
+    }
+}
@@ -0,0 +1,332 @@
+use crate::abstraction::abstraction_layer::{RenderContext, TextureHandle};
+use crate::game_data::GameData;
+use crate::high_scores::GameMode;
+use crate::layout::{Layout, REFERENCE_CELL};
+use crate::statistics::format_time;
+use crate::storage::KeyValueFile;
+use crate::tetramino::TetrominoSequence;
+use crate::theme::ThemeColors;
+use crate::Resources;
+use std::error;
+
+const LABEL_SIZE: u32 = 32;
+const VALUE_SIZE: u32 = 16;
+const VALUE_OFFSET_X: f64 = 130.0;
+const VALUE_OFFSET_Y: f64 = -8.0;
+const HOLD_PIECE_OFFSET_Y: f64 = 100.0;
+const GOAL_PIECES_OFFSET_Y: f64 = 50.0;
+// Rows of text are this many times their size apart.
+const LINE_SPACING: f64 = 1.5;
+// Pieces in the next queue are this many cells apart.
+const QUEUE_SPACING: f64 = 3.0;
+const MODES: [GameMode; 3] = [GameMode::Marathon, GameMode::Puzzle, GameMode::Demo];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum WidgetKind {
+    Score,
+    Level,
+    Lines,
+    Timer,
+    Next,
+    Hold,
+    Stats,
+    Goal,
+}
+
+impl WidgetKind {
+    fn parse(name: &str) -> Result<WidgetKind, Box<dyn error::Error>> {
+        let kind = match name {
+            "score" => WidgetKind::Score,
+            "level" => WidgetKind::Level,
+            "lines" => WidgetKind::Lines,
+            "timer" => WidgetKind::Timer,
+            "next" => WidgetKind::Next,
+            "hold" => WidgetKind::Hold,
+            "stats" => WidgetKind::Stats,
+            "goal" => WidgetKind::Goal,
+            _ => return Err(format!("unknown widget `{}`", name).into()),
+        };
+        Ok(kind)
+    }
+}
+
+/// One item of the side panel. Positions are reference pixels from the
+/// panel's top left corner, `value` is where the value or piece goes
+/// relative to the label.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Widget {
+    pub kind: WidgetKind,
+    pub x: f64,
+    pub y: f64,
+    pub label: String,
+    pub size: u32,
+    pub value: (f64, f64),
+    pub value_size: u32,
+    pub color: &'static str,
+    pub count: usize,
+}
+
+impl Widget {
+    pub fn new(kind: WidgetKind, x: f64, y: f64) -> Widget {
+        let (label, value) = match kind {
+            WidgetKind::Score => ("Score : ", (VALUE_OFFSET_X, VALUE_OFFSET_Y)),
+            WidgetKind::Level => ("Level : ", (VALUE_OFFSET_X, VALUE_OFFSET_Y)),
+            WidgetKind::Lines => ("Lines : ", (VALUE_OFFSET_X, VALUE_OFFSET_Y)),
+            WidgetKind::Timer => ("Time : ", (VALUE_OFFSET_X, VALUE_OFFSET_Y)),
+            WidgetKind::Hold => ("Hold : ", (VALUE_OFFSET_X, HOLD_PIECE_OFFSET_Y)),
+            WidgetKind::Goal => ("Pieces : ", (0.0, GOAL_PIECES_OFFSET_Y)),
+            WidgetKind::Next | WidgetKind::Stats => ("", (0.0, 0.0)),
+        };
+
+        Widget {
+            kind,
+            x,
+            y,
+            label: label.to_string(),
+            size: LABEL_SIZE,
+            value,
+            value_size: VALUE_SIZE,
+            color: "text",
+            count: 1,
+        }
+    }
+
+    /// Reads `<kind> <x> <y>` followed by any of `size=32`,
+    /// `value=<x>,<y>[,<size>]`, `color=<name>`, `count=<n>` and, last,
+    /// `label=<text>` which takes the rest of the line.
+    pub fn parse(text: &str) -> Result<Widget, Box<dyn error::Error>> {
+        let (fields, label) = match text.find("label=") {
+            Some(index) => (&text[..index], Some(text[index + "label=".len()..].trim())),
+            None => (text, None),
+        };
+
+        let mut fields = fields.split_whitespace();
+        let kind = WidgetKind::parse(fields.next().ok_or("empty widget")?)?;
+        let x = parse_number(fields.next(), "x")?;
+        let y = parse_number(fields.next(), "y")?;
+        let mut widget = Widget::new(kind, x, y);
+        if let Some(label) = label {
+            widget.label = label.to_string();
+        }
+
+        for option in fields {
+            let (key, value) = option
+                .split_once('=')
+                .ok_or_else(|| format!("expected `option=value`, got `{}`", option))?;
+            match key {
+                "size" => widget.size = value.parse().map_err(|_| format!("bad size `{}`", value))?,
+                "count" => widget.count = value.parse().map_err(|_| format!("bad count `{}`", value))?,
+                "color" => {
+                    widget.color = ThemeColors::NAMES
+                        .iter()
+                        .find(|name| **name == value)
+                        .ok_or_else(|| format!("unknown color `{}`", value))?;
+                }
+                "value" => {
+                    let mut parts = value.split(',');
+                    widget.value = (parse_number(parts.next(), "value x")?, parse_number(parts.next(), "value y")?);
+                    if let Some(size) = parts.next() {
+                        widget.value_size = size.parse().map_err(|_| format!("bad value size `{}`", size))?;
+                    }
+                }
+                _ => return Err(format!("unknown option `{}`", key).into()),
+            }
+        }
+
+        Ok(widget)
+    }
+}
+
+fn parse_number(text: Option<&str>, what: &str) -> Result<f64, Box<dyn error::Error>> {
+    let text = text.ok_or_else(|| format!("missing {}", what))?;
+    text.parse().map_err(|_| format!("bad {} `{}`", what, text).into())
+}
+
+/// The side panel of every mode. A theme may replace all of them with a
+/// `[hud]` section and single ones with `[hud.<mode>]`, each listing
+/// `widget = ...` lines.
+#[derive(Clone, PartialEq, Debug)]
+pub struct HudLayout {
+    panels: Vec<(GameMode, Vec<Widget>)>,
+}
+
+impl Default for HudLayout {
+    fn default() -> HudLayout {
+        let panel = |goal: bool| {
+            let mut widgets = vec![
+                Widget::new(WidgetKind::Score, 10.0, 32.0),
+                Widget::new(WidgetKind::Level, 10.0, 82.0),
+                Widget::new(WidgetKind::Next, 140.0, 240.0),
+                Widget::new(WidgetKind::Hold, 10.0, 600.0),
+            ];
+            if goal {
+                widgets.push(Widget::new(WidgetKind::Goal, 10.0, 450.0));
+            }
+            widgets
+        };
+
+        HudLayout {
+            panels: MODES.iter().map(|mode| (*mode, panel(*mode == GameMode::Puzzle))).collect(),
+        }
+    }
+}
+
+impl HudLayout {
+    pub fn parse(file: &KeyValueFile) -> Result<HudLayout, Box<dyn error::Error>> {
+        let parse_all = |key: &str| -> Result<Vec<Widget>, Box<dyn error::Error>> {
+            file.get_all(key)
+                .into_iter()
+                .map(|line| Widget::parse(line).map_err(|error| format!("{}: {}", key, error).into()))
+                .collect()
+        };
+
+        let mut layout = HudLayout::default();
+        let shared = parse_all("hud.widget")?;
+        for (mode, widgets) in layout.panels.iter_mut() {
+            let own = parse_all(&format!("hud.{}.widget", mode.key()))?;
+            if !own.is_empty() {
+                *widgets = own;
+            } else if !shared.is_empty() {
+                *widgets = shared.clone();
+            }
+        }
+        Ok(layout)
+    }
+
+    pub fn panel(&self, mode: GameMode) -> &[Widget] {
+        self.panels
+            .iter()
+            .find(|(panel, _)| *panel == mode)
+            .map_or(&[], |(_, widgets)| widgets.as_slice())
+    }
+}
+
+pub fn draw_panel(ctx: &mut dyn RenderContext, resources: &Resources, data: &GameData, mode: GameMode) {
+    let layout = Layout::of(ctx);
+    for widget in resources.hud.panel(mode) {
+        draw_widget(ctx, &layout, resources, data, widget);
+    }
+}
+
+fn draw_widget(ctx: &mut dyn RenderContext, layout: &Layout, resources: &Resources, data: &GameData, widget: &Widget) {
+    let color = resources.colors.get(widget.color).unwrap_or(resources.colors.text);
+    let value_x = widget.x + widget.value.0;
+    let value_y = widget.y + widget.value.1;
+    let text = |ctx: &mut dyn RenderContext, text: &str, size: u32, x: f64, y: f64| {
+        ctx.draw_text(text, resources.font, layout.text_size(size), &layout.panel_point(x, y), &color);
+    };
+
+    let value = match widget.kind {
+        WidgetKind::Score => data.score.to_string(),
+        WidgetKind::Level => data.dificulty.to_string(),
+        WidgetKind::Lines => data.total_lines.to_string(),
+        WidgetKind::Timer => format_time(data.statistics.time),
+        WidgetKind::Next => {
+            if !data.has_next_figure() || !data.settings.gameplay.preview_enabled {
+                return;
+            }
+            // Only puzzles know their pieces past the next one.
+            let upcoming = std::iter::once(&data.next_figure).chain(data.queue.iter());
+            for (index, piece) in upcoming.take(widget.count).enumerate() {
+                let offset = &data.tetraminoes_data[*piece].offset;
+                let x = value_x + offset.x as f64;
+                let y = value_y + offset.y as f64 + index as f64 * QUEUE_SPACING * REFERENCE_CELL;
+                let sequence = &data.tetraminoes_data[*piece].rotations[0].sequence;
+                draw_panel_piece(ctx, layout, resources.blocks[*piece], sequence, x, y);
+            }
+            return;
+        }
+        WidgetKind::Hold => {
+            let hold = match data.hold {
+                Some(hold) => hold,
+                None => return,
+            };
+            text(ctx, &widget.label, widget.size, widget.x, widget.y);
+            let sequence = &data.tetraminoes_data[hold].rotations[0].sequence;
+            draw_panel_piece(ctx, layout, resources.blocks[hold], sequence, value_x, value_y);
+            return;
+        }
+        WidgetKind::Goal => {
+            let puzzle = match &data.puzzle {
+                Some(puzzle) => puzzle,
+                None => return,
+            };
+            text(ctx, &puzzle.goal.description(), widget.value_size, widget.x, widget.y);
+            let pieces = format!("{}{}", widget.label, puzzle.pieces_left);
+            text(ctx, &pieces, widget.value_size, value_x, value_y);
+            return;
+        }
+        WidgetKind::Stats => {
+            let statistics = &data.statistics;
+            let rows = [
+                format!("Pieces : {}", statistics.pieces),
+                format!("PPS : {:.2}", statistics.pieces_per_second()),
+                format!("Tetrises : {}", statistics.clears[3]),
+                format!("T-spins : {}", statistics.tspins),
+                format!("Max combo : {}", statistics.max_combo),
+            ];
+            if !widget.label.is_empty() {
+                text(ctx, &widget.label, widget.size, widget.x, widget.y);
+            }
+            let spacing = widget.value_size as f64 * LINE_SPACING;
+            for (index, row) in rows.iter().enumerate() {
+                text(ctx, row, widget.value_size, value_x, value_y + index as f64 * spacing);
+            }
+            return;
+        }
+    };
+
+    text(ctx, &widget.label, widget.size, widget.x, widget.y);
+    text(ctx, &value, widget.value_size, value_x, value_y);
+}
+
+// Pieces in the panel are placed in reference pixels like the text around them.
+fn draw_panel_piece(ctx: &mut dyn RenderContext, layout: &Layout, block: TextureHandle, sequence: &TetrominoSequence, x: f64, y: f64) {
+    for position in sequence.iter() {
+        let x = x + position.x as f64 * REFERENCE_CELL;
+        let y = y + position.y as f64 * REFERENCE_CELL;
+        ctx.draw_image_scaled(block, &layout.panel_point(x, y), layout.scale());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_widget() {
+        let widget = Widget::parse("score 10 32").unwrap();
+        assert_eq!(Widget::new(WidgetKind::Score, 10.0, 32.0), widget);
+
+        let widget = Widget::parse("next 140 240 count=3 value=0,10 color=title label=Next up").unwrap();
+        assert_eq!(WidgetKind::Next, widget.kind);
+        assert_eq!(3, widget.count);
+        assert_eq!((0.0, 10.0), widget.value);
+        assert_eq!(VALUE_SIZE, widget.value_size);
+        assert_eq!("title", widget.color);
+        assert_eq!("Next up", widget.label);
+
+        assert!(Widget::parse("clock 10 32").is_err());
+        assert!(Widget::parse("score 10").is_err());
+        assert!(Widget::parse("score 10 32 color=pink").is_err());
+        assert!(Widget::parse("score 10 32 spin").is_err());
+    }
+
+    #[test]
+    fn test_parse_hud_layout() {
+        let empty = KeyValueFile::parse("name = Test").unwrap();
+        assert_eq!(HudLayout::default(), HudLayout::parse(&empty).unwrap());
+        assert!(HudLayout::default().panel(GameMode::Puzzle).iter().any(|widget| widget.kind == WidgetKind::Goal));
+
+        let text = "[hud]\nwidget = score 10 32\n[hud.demo]\nwidget = stats 10 900\nwidget = timer 10 850\n";
+        let layout = HudLayout::parse(&KeyValueFile::parse(text).unwrap()).unwrap();
+        assert_eq!(vec![Widget::new(WidgetKind::Score, 10.0, 32.0)], layout.panel(GameMode::Marathon));
+        assert_eq!(layout.panel(GameMode::Marathon), layout.panel(GameMode::Puzzle));
+        let kinds: Vec<WidgetKind> = layout.panel(GameMode::Demo).iter().map(|widget| widget.kind).collect();
+        assert_eq!(vec![WidgetKind::Stats, WidgetKind::Timer], kinds);
+
+        let broken = KeyValueFile::parse("[hud.marathon]\nwidget = score ten 32").unwrap();
+        let error = HudLayout::parse(&broken).unwrap_err().to_string();
+        assert!(error.starts_with("hud.marathon.widget"), "{}", error);
+    }
+}